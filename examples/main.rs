@@ -1,13 +1,16 @@
+#[cfg(target_os = "android")]
 use std::collections::HashMap;
 #[cfg(not(target_arch = "wasm32"))]
 use std::time::Instant;
 
-use sprite_render::{Camera, SpriteInstance, SpriteRender, Texture, TextureId};
+use sprite_render::{Camera, SpriteInstance, SpriteRender, SurfaceId, Texture, TextureId};
+#[cfg(target_os = "android")]
+use winit::event::{Touch, TouchPhase};
 use winit::{
     dpi::{LogicalSize, PhysicalPosition},
     event::{
-        ElementState, Event, KeyboardInput, MouseButton, MouseScrollDelta, Touch, TouchPhase,
-        VirtualKeyCode, WindowEvent,
+        ElementState, Event, KeyboardInput, MouseButton, MouseScrollDelta, VirtualKeyCode,
+        WindowEvent,
     },
     event_loop::EventLoop,
     window::WindowBuilder,
@@ -72,14 +75,15 @@ pub fn main() {
     let window = wb.build(&event_loop).unwrap();
 
     // create the SpriteRender
+    let size = window.inner_size();
     let mut render: Box<dyn SpriteRender> = {
         cfg_if::cfg_if! {
             if #[cfg(all(feature = "opengl", target_os = "android"))] {
-                Box::new(sprite_render::GlSpriteRender::new(&window, true).unwrap())
+                Box::new(sprite_render::GlSpriteRender::new(&window, size.width, size.height, true).unwrap())
             } else if #[cfg(all(feature = "opengl", not(target_os = "android")))] {
-                Box::new(sprite_render::GlSpriteRender::new(&window, true).unwrap())
+                Box::new(sprite_render::GlSpriteRender::new(&window, size.width, size.height, true).unwrap())
             } else if #[cfg(all(target_arch = "wasm32", feature = "webgl"))] {
-                Box::new(sprite_render::WebGLSpriteRender::new(&window))
+                Box::new(sprite_render::WebGLSpriteRender::new(&window, size.width, size.height))
             } else {
                 log::warn!("No sprite-render backend was choosen. \
                            Enable one of them by enabling a feature, like `--features=opengl`");
@@ -88,7 +92,7 @@ pub fn main() {
         }
     };
 
-    let mut camera = Camera::new(window.inner_size().width, window.inner_size().height, 2.0);
+    let mut camera = Camera::new(size.width, size.height, 2.0);
 
     use rand::{Rng, SeedableRng};
     let mut rng = rand::rngs::StdRng::seed_from_u64(42);
@@ -116,7 +120,7 @@ pub fn main() {
         .with_color(COLORS[i % COLORS.len()]);
     }
 
-    #[cfg(not(target_arch = "android"))]
+    #[cfg(not(target_os = "android"))]
     create_textures(render.as_mut(), &mut instances);
 
     let mut clock = Instant::now();
@@ -141,7 +145,8 @@ pub fn main() {
             #[cfg(target_os = "android")]
             Event::Resumed => {
                 log::info!("creating sprite-render");
-                render.resume(&window);
+                let size = window.inner_size();
+                render.resume(&window, size.width, size.height);
                 create_textures(render.as_mut(), &mut instances);
             }
             #[cfg(target_os = "android")]
@@ -295,19 +300,15 @@ pub fn main() {
                         },
                     ..
                 } => match key {
-                    VirtualKeyCode::Right => {
-                        if number_of_sprites < instances.len() - 100 {
-                            number_of_sprites += 100;
-                            change_clock = Instant::now();
-                            change_frame = frame_count;
-                        }
+                    VirtualKeyCode::Right if number_of_sprites < instances.len() - 100 => {
+                        number_of_sprites += 100;
+                        change_clock = Instant::now();
+                        change_frame = frame_count;
                     }
-                    VirtualKeyCode::Left => {
-                        if number_of_sprites > 100 {
-                            number_of_sprites -= 100;
-                            change_clock = Instant::now();
-                            change_frame = frame_count;
-                        }
+                    VirtualKeyCode::Left if number_of_sprites > 100 => {
+                        number_of_sprites -= 100;
+                        change_clock = Instant::now();
+                        change_frame = frame_count;
                     }
                     VirtualKeyCode::Up => {
                         sprite_size *= 1.1;
@@ -332,7 +333,7 @@ pub fn main() {
                     _ => (),
                 },
                 WindowEvent::Resized(size) => {
-                    render.resize(window_id, size.width, size.height);
+                    render.resize(SurfaceId::default(), size.width, size.height);
                     camera.resize(size.width, size.height);
                 }
                 _ => (),
@@ -382,7 +383,7 @@ pub fn main() {
                     log::debug!("{}", title);
                 }
                 render
-                    .render(window.id())
+                    .render(SurfaceId::default())
                     .clear_screen(&[0.0f32, 0.0, 1.0, 1.0])
                     .draw_sprites(&mut camera, &instances[0..number_of_sprites])
                     .finish();
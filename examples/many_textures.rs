@@ -1,10 +1,7 @@
-use sprite_render::{Camera, SpriteInstance, SpriteRender, Texture, TextureId};
+use sprite_render::{Camera, SpriteInstance, SpriteRender, SurfaceId, Texture};
 use winit::{
-    dpi::{LogicalSize, PhysicalPosition},
-    event::{
-        ElementState, Event, KeyboardInput, MouseButton, MouseScrollDelta, Touch, TouchPhase,
-        VirtualKeyCode, WindowEvent,
-    },
+    dpi::LogicalSize,
+    event::{Event, WindowEvent},
     event_loop::EventLoop,
     window::WindowBuilder,
 };
@@ -21,12 +18,13 @@ pub fn main() {
     let window = wb.build(&event_loop).unwrap();
 
     // create the SpriteRender
+    let size = window.inner_size();
     let mut render: Box<dyn SpriteRender> = {
         cfg_if::cfg_if! {
             if #[cfg(feature = "opengl")] {
-                Box::new(sprite_render::GlSpriteRender::new(&window, true).unwrap())
+                Box::new(sprite_render::GlSpriteRender::new(&window, size.width, size.height, true).unwrap())
             } else if #[cfg(all(target_arch = "wasm32", feature = "webgl"))] {
-                Box::new(sprite_render::WebGLSpriteRender::new(&window))
+                Box::new(sprite_render::WebGLSpriteRender::new(&window, size.width, size.height))
             } else {
                 log::warn!("No sprite-render backend was choosen. Enable one of them by enabling a feature, like `--features=opengl`");
                 Box::new(sprite_render::NoopSpriteRender)
@@ -34,7 +32,7 @@ pub fn main() {
         }
     };
 
-    let mut camera = Camera::new(window.inner_size().width, window.inner_size().height, 2.0);
+    let mut camera = Camera::new(size.width, size.height, 2.0);
 
     use rand::Rng;
     let mut rng = rand::thread_rng();
@@ -81,7 +79,7 @@ pub fn main() {
                 WindowEvent::CloseRequested => *control_flow = winit::event_loop::ControlFlow::Exit,
 
                 WindowEvent::Resized(size) => {
-                    render.resize(window_id, size.width, size.height);
+                    render.resize(SurfaceId::default(), size.width, size.height);
                     camera.resize(size.width, size.height);
                 }
                 _ => (),
@@ -91,7 +89,7 @@ pub fn main() {
             }
             Event::RedrawRequested(_) => {
                 render
-                    .render(window.id())
+                    .render(SurfaceId::default())
                     .clear_screen(&[0.0f32, 0.0, 1.0, 1.0])
                     .draw_sprites(&mut camera, &instances)
                     .finish();
@@ -1,7 +1,7 @@
 use std::{collections::HashMap, fmt::Error, time::Instant};
 
 use rand::Rng;
-use sprite_render::{Camera, SpriteInstance, SpriteRender, Texture, TextureId};
+use sprite_render::{Camera, SpriteInstance, SpriteRender, SurfaceId, Texture, TextureId};
 use winit::{
     dpi::{LogicalSize, PhysicalPosition},
     event::{
@@ -15,6 +15,7 @@ use winit::{
 struct Scene {
     instances: Box<[SpriteInstance]>,
     window: Window,
+    surface: SurfaceId,
     camera: Camera,
     number_of_sprites: usize,
     sprite_size: f32,
@@ -47,6 +48,7 @@ impl Scene {
         fruit_texture: TextureId,
         jelly_texture: TextureId,
         window: Window,
+        surface: SurfaceId,
     ) -> Self {
         let camera = Camera::new(window.inner_size().width, window.inner_size().height, 2.0);
         let mut instances = vec![SpriteInstance::default(); 16384].into_boxed_slice();
@@ -78,6 +80,7 @@ impl Scene {
         Self {
             camera,
             window,
+            surface,
             instances,
             time: 0.0,
             number_of_sprites: 100,
@@ -106,9 +109,18 @@ fn main() {
     let mut render: Box<dyn SpriteRender> = {
         cfg_if::cfg_if! {
             if #[cfg(feature = "opengl")] {
-                Box::new(sprite_render::GlSpriteRender::new(&window, true).unwrap())
+                Box::new(sprite_render::GlSpriteRender::new(
+                    &window,
+                    window.inner_size().width,
+                    window.inner_size().height,
+                    true,
+                ).unwrap())
             } else if #[cfg(all(target_arch = "wasm32", feature = "webgl"))] {
-                Box::new(sprite_render::WebGLSpriteRender::new(&window))
+                Box::new(sprite_render::WebGLSpriteRender::new(
+                    &window,
+                    window.inner_size().width,
+                    window.inner_size().height,
+                ))
             } else {
                 log::warn!("No sprite-render backend was choosen. \
                            Enable one of them by enabling a feature, like `--features=opengl`");
@@ -122,7 +134,8 @@ fn main() {
         .with_inner_size(LogicalSize::new(800.0, 400.0))
         .build(&event_loop)
         .unwrap();
-    render.add_window(&window_2);
+    let size_2 = window_2.inner_size();
+    let surface_2 = render.add_window(&window_2, size_2.width, size_2.height);
     let fruit_texture = {
         let image = image::open("examples/fruits.png")
             .expect("File not Found!")
@@ -153,11 +166,17 @@ fn main() {
     let mut scenes = HashMap::new();
     scenes.insert(
         window.id(),
-        Scene::new(&mut rng, fruit_texture, jelly_texture, window),
+        Scene::new(
+            &mut rng,
+            fruit_texture,
+            jelly_texture,
+            window,
+            SurfaceId::default(),
+        ),
     );
     scenes.insert(
         window_2.id(),
-        Scene::new(&mut rng, fruit_texture, jelly_texture, window_2),
+        Scene::new(&mut rng, fruit_texture, jelly_texture, window_2, surface_2),
     );
 
     // let mut frame_clock = Instant::now();
@@ -205,12 +224,12 @@ fn main() {
                         state: ElementState::Pressed,
                         ..
                     }, ..} => match key {
-                        VirtualKeyCode::Right => if scene.number_of_sprites < scene.instances.len() - 100 {
+                        VirtualKeyCode::Right if scene.number_of_sprites < scene.instances.len() - 100 => {
                             scene.number_of_sprites += 100;
                             change_clock = Instant::now();
                             change_frame = frame_count;
                         },
-                        VirtualKeyCode::Left => if scene.number_of_sprites > 100 {
+                        VirtualKeyCode::Left if scene.number_of_sprites > 100 => {
                             scene.number_of_sprites -= 100;
                             change_clock = Instant::now();
                             change_frame = frame_count;
@@ -238,7 +257,7 @@ fn main() {
                         _ => ()
                     }
                     WindowEvent::Resized(size) => {
-                        render.resize(window_id, size.width, size.height);
+                        render.resize(scene.surface, size.width, size.height);
                         scene.camera.resize(size.width, size.height);
                     }
                     _ => (),
@@ -276,7 +295,7 @@ fn main() {
                         mean_fps, 1000.0 / mean_fps
                     ));
                 }
-                render.render(window_id)
+                render.render(scene.surface)
                     .clear_screen(&[0.0f32, 0.0, 1.0, 1.0])
                     .draw_sprites(&mut scene.camera, &scene.instances[0..scene.number_of_sprites])
                     .finish();
@@ -0,0 +1,447 @@
+use crate::{SpriteInstance, TextureId};
+
+/// A frame of a [AnimationClip].
+#[derive(Clone, Debug, PartialEq)]
+pub struct Frame {
+    pub texture: TextureId,
+    pub uv_rect: [f32; 4],
+    /// How long the frame is shown, in seconds.
+    pub duration: f32,
+    /// The point of the frame that is kept in place, in normalized coordinates of the frame, with
+    /// (0.0, 0.0) in the top left corner. None is the center of the frame.
+    pub pivot: Option<[f32; 2]>,
+}
+impl Frame {
+    pub fn new(texture: TextureId, uv_rect: [f32; 4], duration: f32) -> Self {
+        Self {
+            texture,
+            uv_rect,
+            duration,
+            pivot: None,
+        }
+    }
+
+    /// set the pivot of the frame, in a functional way (get owership of the value, and return it
+    /// modified).
+    #[inline]
+    pub fn with_pivot(mut self, x: f32, y: f32) -> Self {
+        self.pivot = Some([x, y]);
+        self
+    }
+}
+
+/// How a [AnimationClip] continues after its last frame.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PlayMode {
+    /// Start again from the first frame.
+    #[default]
+    Loop,
+    /// Play the frames backwards to the first frame, and then forwards again.
+    PingPong,
+    /// Stop in the last frame.
+    Once,
+}
+
+/// A sequence of frames, played by a [AnimationPlayer].
+///
+/// The frames are played in order, each one for its duration, and the clip continues according to
+/// its [PlayMode]. Events can be attached to frames, to be reported by [AnimationPlayer::update]
+/// each time the frame is shown.
+#[derive(Clone, Debug)]
+pub struct AnimationClip {
+    frames: Vec<Frame>,
+    mode: PlayMode,
+    events: Vec<(usize, String)>,
+    /// The frame index of each step of a period of the clip, and the time where the step ends.
+    timeline: Vec<(usize, f32)>,
+}
+impl AnimationClip {
+    /// Create a new AnimationClip with the given frames, and [PlayMode::Loop].
+    pub fn new(frames: Vec<Frame>) -> Self {
+        let mut clip = Self {
+            frames,
+            mode: PlayMode::Loop,
+            events: Vec::new(),
+            timeline: Vec::new(),
+        };
+        clip.build_timeline();
+        clip
+    }
+
+    /// Create a new AnimationClip from the first `frame_count` cells of a sprite sheet divided in
+    /// a grid of `columns` x `rows` frames of the same size, numbered from left to right and from
+    /// top to bottom, each shown for `duration` seconds.
+    pub fn from_grid(
+        texture: TextureId,
+        columns: u32,
+        rows: u32,
+        frame_count: u32,
+        duration: f32,
+    ) -> Self {
+        let (w, h) = (1.0 / columns as f32, 1.0 / rows as f32);
+        let frames = (0..frame_count.min(columns * rows))
+            .map(|i| {
+                let uv_rect = [(i % columns) as f32 * w, (i / columns) as f32 * h, w, h];
+                Frame::new(texture, uv_rect, duration)
+            })
+            .collect();
+        Self::new(frames)
+    }
+
+    /// set the play mode, in a functional way (get owership of the value, and return it modified).
+    pub fn with_mode(mut self, mode: PlayMode) -> Self {
+        self.mode = mode;
+        self.build_timeline();
+        self
+    }
+
+    /// add a event to the frame with index `frame`, in a functional way (get owership of the value,
+    /// and return it modified). A frame may have multiple events.
+    pub fn with_event(mut self, frame: usize, name: impl Into<String>) -> Self {
+        if frame >= self.frames.len() {
+            log::warn!("animation event in frame {}, out of the clip", frame);
+        }
+        self.events.push((frame, name.into()));
+        self
+    }
+
+    pub fn frames(&self) -> &[Frame] {
+        &self.frames
+    }
+
+    pub fn mode(&self) -> PlayMode {
+        self.mode
+    }
+
+    /// The duration of a period of the clip, in seconds. For [PlayMode::PingPong] it includes the
+    /// way back.
+    pub fn duration(&self) -> f32 {
+        self.timeline.last().map_or(0.0, |x| x.1)
+    }
+
+    fn build_timeline(&mut self) {
+        let n = self.frames.len();
+        let back = match self.mode {
+            // the first and last frames are not repeated when turning.
+            PlayMode::PingPong => (1..n.saturating_sub(1)).rev().collect(),
+            _ => Vec::new(),
+        };
+        let mut end = 0.0;
+        self.timeline = (0..n)
+            .chain(back)
+            .map(|i| {
+                end += self.frames[i].duration.max(0.0);
+                (i, end)
+            })
+            .collect();
+    }
+
+    /// The step of the timeline at `time`, which must be in a period.
+    fn step_at(&self, time: f32) -> usize {
+        let step = self.timeline.partition_point(|x| x.1 <= time);
+        step.min(self.timeline.len().saturating_sub(1))
+    }
+
+    /// The time in a period of the clip, after `time` seconds from its start.
+    fn period_time(&self, time: f32) -> f32 {
+        let duration = self.duration();
+        if self.mode == PlayMode::Once || duration <= 0.0 {
+            time.min(duration)
+        } else {
+            time.rem_euclid(duration)
+        }
+    }
+
+    /// The index of the frame shown after `time` seconds from the start of the clip, or None if
+    /// the clip has no frames.
+    pub fn frame_index_at(&self, time: f32) -> Option<usize> {
+        if self.frames.is_empty() {
+            return None;
+        }
+        Some(self.timeline[self.step_at(self.period_time(time))].0)
+    }
+
+    /// The frame shown after `time` seconds from the start of the clip, or None if the clip has
+    /// no frames.
+    pub fn frame_at(&self, time: f32) -> Option<&Frame> {
+        Some(&self.frames[self.frame_index_at(time)?])
+    }
+}
+
+/// The playback state of a [AnimationClip].
+///
+/// The player does not own the clip, so a clip can be shared by many players, and a player can
+/// switch between clips. [AnimationPlayer::update] advances the time, and
+/// [AnimationPlayer::apply] sets the texture, uv_rect and position of a [SpriteInstance] to the
+/// current frame.
+///
+/// ```
+/// use sprite_render::{AnimationClip, AnimationPlayer, SpriteInstance, TextureId};
+///
+/// let clip = AnimationClip::from_grid(TextureId(1), 3, 2, 5, 0.1).with_event(2, "step");
+/// let mut player = AnimationPlayer::new();
+/// let mut sprite = SpriteInstance::default();
+///
+/// let mut events = Vec::new();
+/// player.update(&clip, 0.25, &mut events);
+/// player.apply(&clip, &mut sprite, 0.0, 0.0);
+/// assert_eq!(events, ["step"]);
+/// assert_eq!(player.frame_index(&clip), Some(2));
+/// assert_eq!(sprite.uv_rect, clip.frames()[2].uv_rect);
+/// ```
+#[derive(Clone, Debug)]
+pub struct AnimationPlayer {
+    /// The time in the current period of the clip, in seconds.
+    time: f32,
+    speed: f32,
+    playing: bool,
+    /// The last step of the timeline whose events were reported.
+    step: Option<usize>,
+}
+impl Default for AnimationPlayer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl AnimationPlayer {
+    /// Create a new AnimationPlayer, playing from the start with speed 1.0.
+    pub fn new() -> Self {
+        Self {
+            time: 0.0,
+            speed: 1.0,
+            playing: true,
+            step: None,
+        }
+    }
+
+    /// set the speed, in a functional way (get owership of the value, and return it modified).
+    #[inline]
+    pub fn with_speed(mut self, speed: f32) -> Self {
+        self.set_speed(speed);
+        self
+    }
+
+    /// Set the multiplier of the elapsed time. Negative speeds are clamped to 0.0.
+    pub fn set_speed(&mut self, speed: f32) {
+        self.speed = speed.max(0.0);
+    }
+
+    pub fn speed(&self) -> f32 {
+        self.speed
+    }
+
+    pub fn play(&mut self) {
+        self.playing = true;
+    }
+
+    pub fn pause(&mut self) {
+        self.playing = false;
+    }
+
+    /// If the player is not paused, and has not reached the end of a [PlayMode::Once] clip.
+    pub fn is_playing(&self) -> bool {
+        self.playing
+    }
+
+    /// Go back to the start of the clip and play it. The events of the first frame are reported
+    /// again in the next update.
+    pub fn restart(&mut self) {
+        self.time = 0.0;
+        self.step = None;
+        self.playing = true;
+    }
+
+    /// The time since the start of the current period of the clip, in seconds.
+    pub fn time(&self) -> f32 {
+        self.time
+    }
+
+    /// Advance the animation by `dt` seconds, multiplied by the speed, and push to `events` the
+    /// names of the events of each frame entered, in order.
+    ///
+    /// If `dt` spans more than a period of the clip, the events of each frame are reported at most
+    /// once.
+    pub fn update<'a>(&mut self, clip: &'a AnimationClip, dt: f32, events: &mut Vec<&'a str>) {
+        if !self.playing || clip.timeline.is_empty() {
+            return;
+        }
+        let len = clip.timeline.len();
+        let duration = clip.duration();
+        let time = self.time + dt * self.speed;
+        let periods = if duration > 0.0 && clip.mode != PlayMode::Once {
+            (time / duration).floor() as usize
+        } else {
+            0
+        };
+        self.time = clip.period_time(time);
+        if clip.mode == PlayMode::Once && time >= duration {
+            self.playing = false;
+        }
+
+        // the steps entered since the last update, counted from the start of the last period.
+        let step = clip.step_at(self.time);
+        let first = self.step.map_or(0, |x| x + 1);
+        let last = periods.saturating_mul(len).saturating_add(step);
+        let count = (last + 1).saturating_sub(first).min(len);
+        for s in last + 1 - count..=last {
+            let frame = clip.timeline[s % len].0;
+            events.extend(
+                clip.events
+                    .iter()
+                    .filter(|x| x.0 == frame)
+                    .map(|x| x.1.as_str()),
+            );
+        }
+        self.step = Some(step);
+    }
+
+    /// The index of the current frame, or None if the clip has no frames.
+    pub fn frame_index(&self, clip: &AnimationClip) -> Option<usize> {
+        clip.frame_index_at(self.time)
+    }
+
+    /// The current frame, or None if the clip has no frames.
+    pub fn frame<'a>(&self, clip: &'a AnimationClip) -> Option<&'a Frame> {
+        clip.frame_at(self.time)
+    }
+
+    /// Set the texture and uv_rect of `sprite` to the current frame, and move it so the pivot of
+    /// the frame is in (x, y), taking in account the scale and the angle of the sprite.
+    pub fn apply(&self, clip: &AnimationClip, sprite: &mut SpriteInstance, x: f32, y: f32) {
+        let Some(frame) = self.frame(clip) else {
+            return;
+        };
+        sprite.texture = frame.texture;
+        sprite.uv_rect = frame.uv_rect;
+
+        let [px, py] = frame.pivot.unwrap_or([0.5, 0.5]);
+        let dx = (px - 0.5) * sprite.scale[0];
+        let dy = (py - 0.5) * sprite.scale[1];
+        let (sin, cos) = sprite.angle.sin_cos();
+        sprite.pos = [x - (cos * dx - sin * dy), y - (sin * dx + cos * dy)];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A clip of `count` frames of 1 second.
+    fn clip(count: usize, mode: PlayMode) -> AnimationClip {
+        let frames = (0..count)
+            .map(|i| Frame::new(TextureId(i as u32), [0.0, 0.0, 1.0, 1.0], 1.0))
+            .collect();
+        AnimationClip::new(frames).with_mode(mode)
+    }
+
+    #[test]
+    fn ping_pong() {
+        let clip = clip(4, PlayMode::PingPong);
+        // the first and last frames are not repeated when turning.
+        assert_eq!(clip.duration(), 6.0);
+        let frames = [0.5, 1.5, 2.5, 3.5, 4.5, 5.5, 6.5].map(|t| clip.frame_index_at(t));
+        assert_eq!(frames, [0, 1, 2, 3, 2, 1, 0].map(Some));
+
+        let mut player = AnimationPlayer::new();
+        let mut events = Vec::new();
+        player.update(&clip, 3.5, &mut events);
+        assert_eq!(player.frame_index(&clip), Some(3));
+        player.update(&clip, 2.0, &mut events);
+        assert_eq!(player.frame_index(&clip), Some(1));
+        player.update(&clip, 1.0, &mut events);
+        assert_eq!(player.frame_index(&clip), Some(0));
+        assert_eq!(player.time(), 0.5);
+        assert!(player.is_playing());
+    }
+
+    #[test]
+    fn once() {
+        let clip = clip(3, PlayMode::Once);
+        let mut player = AnimationPlayer::new();
+        let mut events = Vec::new();
+        player.update(&clip, 2.5, &mut events);
+        assert_eq!(player.frame_index(&clip), Some(2));
+        assert!(player.is_playing());
+
+        // stops in the last frame.
+        player.update(&clip, 1.0, &mut events);
+        assert_eq!(player.frame_index(&clip), Some(2));
+        assert_eq!(player.time(), 3.0);
+        assert!(!player.is_playing());
+        player.update(&clip, 1.0, &mut events);
+        assert_eq!(player.time(), 3.0);
+        assert_eq!(clip.frame_index_at(100.0), Some(2));
+
+        player.restart();
+        assert!(player.is_playing());
+        assert_eq!(player.frame_index(&clip), Some(0));
+    }
+
+    #[test]
+    fn speed_and_pause() {
+        let clip = clip(3, PlayMode::Loop);
+        let mut player = AnimationPlayer::new().with_speed(2.0);
+        let mut events = Vec::new();
+        player.update(&clip, 0.75, &mut events);
+        assert_eq!(player.time(), 1.5);
+        assert_eq!(player.frame_index(&clip), Some(1));
+
+        player.pause();
+        player.update(&clip, 1.0, &mut events);
+        assert_eq!(player.time(), 1.5);
+        player.play();
+        player.set_speed(-1.0);
+        player.update(&clip, 1.0, &mut events);
+        assert_eq!(player.time(), 1.5);
+    }
+
+    #[test]
+    fn events() {
+        let clip = clip(3, PlayMode::Loop)
+            .with_event(0, "start")
+            .with_event(2, "step")
+            .with_event(2, "sound");
+        let mut player = AnimationPlayer::new();
+        let mut events = Vec::new();
+        let mut update = |dt| {
+            events.clear();
+            player.update(&clip, dt, &mut events);
+            events.clone()
+        };
+        assert_eq!(update(0.5), ["start"]);
+        assert_eq!(update(0.1), Vec::<&str>::new());
+        assert_eq!(update(1.0), Vec::<&str>::new());
+        assert_eq!(update(1.0), ["step", "sound"]);
+        // crossing the end of the clip enters the first frame again.
+        assert_eq!(update(1.0), ["start"]);
+        // the frames skipped by a long update are reported once, in order.
+        assert_eq!(update(10.0), ["step", "sound", "start"]);
+    }
+
+    #[test]
+    fn ping_pong_events() {
+        let clip = clip(3, PlayMode::PingPong).with_event(1, "middle");
+        let mut player = AnimationPlayer::new();
+        let mut events = Vec::new();
+        player.update(&clip, 0.5, &mut events);
+        assert!(events.is_empty());
+        // the middle frame is entered on the way forward and on the way back.
+        for (dt, expected) in [(1.0, 1), (1.0, 1), (1.0, 2), (1.0, 2)] {
+            player.update(&clip, dt, &mut events);
+            assert_eq!(events.len(), expected);
+        }
+    }
+
+    #[test]
+    fn apply_pivot() {
+        let frames = vec![Frame::new(TextureId(1), [0.0, 0.0, 0.5, 0.5], 1.0).with_pivot(0.0, 1.0)];
+        let clip = AnimationClip::new(frames);
+        let player = AnimationPlayer::new();
+        let mut sprite = SpriteInstance::new(0.0, 0.0, 4.0, 2.0, TextureId(0), [0.0; 4]);
+        player.apply(&clip, &mut sprite, 10.0, 10.0);
+        assert_eq!(sprite.texture, TextureId(1));
+        assert_eq!(sprite.uv_rect, [0.0, 0.0, 0.5, 0.5]);
+        // the bottom left corner is in (10, 10).
+        assert_eq!(sprite.pos, [12.0, 9.0]);
+    }
+}
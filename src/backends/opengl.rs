@@ -18,10 +18,25 @@ use glutin::{
     },
     surface::{GlSurface, Surface, SurfaceAttributesBuilder, SwapInterval, WindowSurface},
 };
-use raw_window_handle::{HasRawDisplayHandle, HasRawWindowHandle};
-use winit::window::{Window, WindowId};
 
-use crate::{common::*, Renderer, SpriteRender, Texture, TextureError, TextureFilter, TextureId};
+#[cfg(feature = "hot-reload")]
+use crate::hot_reload;
+use crate::{
+    clip::{ClipRect, ClipStack},
+    common::*,
+    fallback::{FeedbackLoops, MissingTextures, FALLBACK_TEXTURE},
+    material::{
+        self, BlendMode, Material, NormalMapFlags, Uniform, SRGB_DECODE, SWIZZLE_ALPHA,
+        SWIZZLE_LUMINANCE_ALPHA, SWIZZLE_NONE,
+    },
+    mesh::{self, Indices, Mesh, Vertex},
+    post_process::{self, PostEffect},
+    shape::{self, Quad, WHITE_TEXTURE},
+    static_batch::{self, BatchGroup},
+    BatchError, BatchId, ColorSpace, FallbackTexture, MaterialId, Mipmaps, RawWindow, Renderer,
+    Sampler, ShaderError, Shapes, SpriteRender, SurfaceId, Texture, TextureError, TextureFilter,
+    TextureFormat, TextureId, TextureWrap, Transform,
+};
 
 mod gl {
     include!(concat!(env!("OUT_DIR"), "/gl_bindings.rs"));
@@ -33,8 +48,18 @@ use gl::types::*;
 const MAX_NUMBER_OF_SPRITES: usize = (u16::max_value() as usize + 1) / 4;
 const SPRITE_VERTEX_STRIDE: usize = mem::size_of::<f32>() * 6;
 
+// Formats of OpenGL 2.0 and OpenGL ES 2.0, missing from the core profile bindings.
+const LUMINANCE: GLenum = 0x1909;
+const LUMINANCE_ALPHA: GLenum = 0x190A;
+// Compressed formats from the EXT_texture_compression_s3tc and KHR_texture_compression_astc_ldr
+// extensions, missing from the core profile bindings.
+const COMPRESSED_RGBA_S3TC_DXT1_EXT: GLenum = 0x83F1;
+const COMPRESSED_RGBA_S3TC_DXT3_EXT: GLenum = 0x83F2;
+const COMPRESSED_RGBA_S3TC_DXT5_EXT: GLenum = 0x83F3;
+const COMPRESSED_RGBA_ASTC_4X4_KHR: GLenum = 0x93B0;
+
+/// The vertex shader of the sprites, without the `#version` directive and color space prelude.
 const VERTEX_SHADER_SOURCE: &str = r#"
-#version 100
 attribute vec2 position;
 attribute vec2 uv;
 attribute vec4 aColor;
@@ -44,14 +69,21 @@ uniform mat3 view;
 
 varying vec4 color;
 varying vec2 TexCoord;
+varying vec2 WorldPos;
 varying float textureIndex;
+varying float textureSwizzle;
 
 void main() {
     gl_Position = vec4((vec3(position, 1.0) * view).xy, 0.0, 1.0);
     gl_Position.y *= -1.0;
     color = aColor;
+#ifdef LINEAR_COLOR
+    color.rgb = srgbToLinear(aColor.rgb);
+#endif
     TexCoord = uv;
-    textureIndex = aTexture;
+    WorldPos = position;
+    textureIndex = mod(aTexture, 256.0);
+    textureSwizzle = floor(aTexture / 256.0);
 }
 "#;
 
@@ -106,144 +138,554 @@ unsafe fn get_uniform_location(shader_program: u32, name: &str) -> i32 {
     gl::GetUniformLocation(shader_program, s.as_ptr())
 }
 
+/// The vertices drawn by [GlRenderer::draw_batch].
+#[derive(Clone, Copy)]
+enum Vertices<'a> {
+    /// Vertices uploaded to the shared vertex buffer.
+    Data(&'a [u8]),
+    /// Vertices already in a buffer, starting from `first_vertex`.
+    Buffer { buffer: u32, first_vertex: usize },
+}
+
 pub struct GlRenderer<'a> {
     render: &'a mut GlSpriteRender,
+    /// The render texture being rendered to, or None if rendering to the window surface.
+    target: Option<TextureId>,
+    clip: ClipStack,
 }
-impl<'a> Renderer for GlRenderer<'a> {
-    fn clear_screen(&mut self, color: &[f32; 4]) -> &mut dyn Renderer {
-        log::trace!(
-            "clear screen to [{:5.3}, {:5.3}, {:5.3}, {:5.3}]",
-            color[0],
-            color[1],
-            color[2],
-            color[3]
-        );
-        unsafe {
-            gl::ClearColor(color[0], color[1], color[2], color[3]);
-            gl::Clear(gl::COLOR_BUFFER_BIT);
-        }
-        self
-    }
-
-    fn draw_sprites(
-        &mut self,
-        camera: &mut Camera,
-        sprites: &[SpriteInstance],
-    ) -> &mut dyn Renderer {
+impl<'a> GlRenderer<'a> {
+    /// Draw `len` quads, split in draw calls by material and by the number of texture units.
+    fn draw_quads(&mut self, camera: &mut Camera, len: usize, quads: impl Iterator<Item = Quad>) {
         let Some(res) = &mut self.render.shared_resources else {
             panic!("OpenGL context don't exist.")
         };
 
-        log::trace!("draw {} sprites", sprites.len());
-        if sprites.is_empty() {
-            return self;
+        if len == 0 {
+            return;
         }
-        if sprites.len() > res.buffer_size as usize {
-            res.reallocate_vertex_buffer(sprites.len());
+        if len > res.buffer_size as usize {
+            res.reallocate_vertex_buffer(len);
         }
 
         let mut data: Vec<u8> =
-            Vec::with_capacity(sprites.len().min(MAX_NUMBER_OF_SPRITES) * SPRITE_VERTEX_STRIDE * 4);
+            Vec::with_capacity(len.min(MAX_NUMBER_OF_SPRITES) * SPRITE_VERTEX_STRIDE * 4);
 
-        let mut sprites = sprites.iter();
-        while sprites.len() > 0 {
+        let view = self.view(camera);
+
+        let mut quads = quads.peekable();
+        while let Some(first) = quads.peek() {
             let res = &mut self.render.shared_resources.as_mut().unwrap();
+            // split rendering by material
+            let material = first.material;
+            let texture_units = res.program(material).1;
+            let normal_maps = res.uses_normal_maps(material);
             res.texture_unit_map.clear();
+            res.clear_normal_maps(texture_units);
             data.clear();
             let mut count = 0;
             unsafe {
-                for sprite in &mut sprites {
-                    let texture_unit = if let Some(t) = res.texture_unit_map.get(&sprite.texture) {
+                while let Some(quad) = quads.next_if(|x| x.material == material) {
+                    let id = res.feedback_loops.texture(quad.texture, self.target);
+                    let texture_unit = if let Some(t) = res.texture_unit_map.get(&id) {
                         *t
+                    } else if let Some(t) = res.missing_texture_unit(id) {
+                        t
                     } else {
-                        let Some(texture) = res.get_gl_texture(sprite.texture) else {
-                        log::debug!("{:?}", res.textures);
-                        log::error!("texture {:} not found", sprite.texture.0);
-                        continue;
-                    };
+                        let texture = match res.get_gl_texture(id) {
+                            Some(x) => x,
+                            None => {
+                                res.missing_textures.warn(id);
+                                match res.get_gl_texture(FALLBACK_TEXTURE) {
+                                    Some(x) => x,
+                                    None => continue,
+                                }
+                            }
+                        };
 
                         let unit = res.texture_unit_map.len() as u32;
-                        log::trace!("active texture {}, and bind to {}", unit, sprite.texture);
+                        log::trace!("active texture {}, and bind to {}", unit, id);
 
                         gl::ActiveTexture(gl::TEXTURE0 + unit);
                         gl::BindTexture(gl::TEXTURE_2D, texture.name);
+                        if normal_maps {
+                            res.bind_normal_map(texture.id, unit, texture_units);
+                        }
 
-                        res.texture_unit_map.insert(sprite.texture, unit);
+                        let index = unit | (texture.gl_format.swizzle as u32) << 8;
+                        res.texture_unit_map.insert(texture.id, index);
 
-                        unit
+                        index
                     };
-                    GlSpriteRender::write_sprite(&mut data, sprite, texture_unit as u16).unwrap();
+                    GlSpriteRender::write_quad(&mut data, &quad, texture_unit as u16).unwrap();
 
                     count += 1;
 
                     // split rendering in multiple draw calls if necessary
-                    if res.texture_unit_map.len() == res.max_texture_units as usize
+                    if res.texture_unit_map.len() == texture_units as usize
                         || count == MAX_NUMBER_OF_SPRITES as i32
                     {
                         break;
                     }
                 }
 
-                gl::BindBuffer(gl::ARRAY_BUFFER, res.vertex_buffer);
-                gl::BufferSubData(
-                    gl::ARRAY_BUFFER,
-                    0,
-                    data.len() as GLsizeiptr,
-                    data.as_ptr() as *const c_void,
-                );
-                log::trace!(
-                    "buffer subdata: len {}, buffer size {}",
-                    data.len(),
-                    res.buffer_size
+                let index_buffer = res.indice_buffer;
+                self.draw_batch(
+                    &view,
+                    material,
+                    Vertices::Data(&data),
+                    index_buffer,
+                    count * 6,
                 );
+            }
+        }
+    }
 
-                // render
-                gl::UseProgram(res.shader_program);
-                let text_units = (0..res.max_texture_units).collect::<Vec<i32>>();
-                gl::Uniform1iv(
-                    get_uniform_location(res.shader_program, "text"),
-                    16,
-                    text_units.as_ptr(),
-                );
-                gl::UniformMatrix3fv(
-                    get_uniform_location(res.shader_program, "view"),
-                    1,
-                    gl::FALSE,
-                    camera.view().as_ptr(),
-                );
+    /// Draw the groups of a static batch with the given view matrix.
+    fn draw_static(&mut self, view: &[f32; 9], batch: BatchId) {
+        let Some(res) = &self.render.shared_resources else {
+            panic!("OpenGL context don't exist.")
+        };
+        let Some(static_batch) = res.static_batches.get(&batch) else {
+            log::error!("static batch {} not found", batch);
+            return;
+        };
+        log::trace!("draw static batch {}", batch);
+        let buffer = static_batch.buffer;
+        let groups = static_batch.groups.clone();
+        for group in groups {
+            let res = self.render.shared_resources.as_mut().unwrap();
+            if group.len > res.buffer_size as usize {
+                // the index buffer must have indices for all sprites in the group.
+                res.reallocate_vertex_buffer(group.len);
+            }
+            let texture_units = res.program(group.material).1;
+            let normal_maps = res.uses_normal_maps(group.material);
+            res.clear_normal_maps(texture_units);
+            for (unit, &id) in group.textures.iter().enumerate() {
+                let id = res.feedback_loops.texture(id, self.target);
+                let texture = res.get_gl_texture(id).or_else(|| {
+                    res.missing_textures.warn(id);
+                    res.get_gl_texture(FALLBACK_TEXTURE)
+                });
+                unsafe {
+                    gl::ActiveTexture(gl::TEXTURE0 + unit as u32);
+                    gl::BindTexture(gl::TEXTURE_2D, texture.map_or(0, |x| x.name));
+                    if normal_maps {
+                        res.bind_normal_map(id, unit as u32, texture_units);
+                    }
+                }
+            }
+            let vertices = Vertices::Buffer {
+                buffer,
+                first_vertex: group.start * 4,
+            };
+            let (index_buffer, count) = (res.indice_buffer, group.len as i32 * 6);
+            unsafe { self.draw_batch(view, group.material, vertices, index_buffer, count) };
+        }
+    }
+
+    /// The view matrix of the camera, flipped when rendering to a texture.
+    fn view(&self, camera: &mut Camera) -> [f32; 9] {
+        let mut view = *camera.view();
+        if self.target.is_some() {
+            // The vertex shader flips the y axis to match the window coordinates, but the rows of a
+            // texture go upwards, so flip it back.
+            view[3..6].iter_mut().for_each(|x| *x = -*x);
+        }
+        view
+    }
+
+    /// Draw `index_count` u16 indices of `index_buffer` with the program of `material`. The
+    /// textures must be already bound.
+    unsafe fn draw_batch(
+        &mut self,
+        view: &[f32; 9],
+        material: MaterialId,
+        vertices: Vertices,
+        index_buffer: u32,
+        index_count: i32,
+    ) {
+        let encode_srgb = self.render.color_space == ColorSpace::Linear
+            && !self.render.current_context.as_ref().unwrap().1.srgb;
+        let vao = self.render.vao();
+        let Some(res) = &mut self.render.shared_resources else {
+            panic!("OpenGL context don't exist.")
+        };
+
+        if let Vertices::Data(data) = vertices {
+            gl::BindBuffer(gl::ARRAY_BUFFER, res.vertex_buffer);
+            gl::BufferSubData(
+                gl::ARRAY_BUFFER,
+                0,
+                data.len() as GLsizeiptr,
+                data.as_ptr() as *const c_void,
+            );
+            log::trace!(
+                "buffer subdata: len {}, buffer size {}",
+                data.len(),
+                res.buffer_size
+            );
+        }
+
+        // render
+        if material != MaterialId::default() && !res.materials.contains_key(&material) {
+            log::error!("material {} not found", material);
+        }
+        let (shader_program, texture_units) = res.program(material);
+        gl::UseProgram(shader_program);
+        let text_units = (0..texture_units).collect::<Vec<i32>>();
+        gl::Uniform1iv(
+            get_uniform_location(shader_program, "text"),
+            texture_units,
+            text_units.as_ptr(),
+        );
+        gl::UniformMatrix3fv(
+            get_uniform_location(shader_program, "view"),
+            1,
+            gl::FALSE,
+            view.as_ptr(),
+        );
+        gl::Uniform1i(
+            get_uniform_location(shader_program, "encodeSrgb"),
+            encode_srgb as i32,
+        );
+        if res.uses_normal_maps(material) {
+            let normal_units = (texture_units..2 * texture_units).collect::<Vec<i32>>();
+            gl::Uniform1iv(
+                get_uniform_location(shader_program, "normalMaps"),
+                texture_units,
+                normal_units.as_ptr(),
+            );
+            gl::Uniform1iv(
+                get_uniform_location(shader_program, "hasNormalMap"),
+                texture_units,
+                res.normal_map_flags.uniform(texture_units).as_ptr(),
+            );
+        }
+        let blend = res
+            .materials
+            .get(&material)
+            .map_or(BlendMode::Alpha, |x| x.blend);
+        let (src, dst) = blend_factors(blend);
+        gl::BlendFunc(src, dst);
+        if let Some(material) = res.materials.get(&material) {
+            GlSpriteRender::set_material_uniforms(res, material);
+        }
+
+        if let Some(vao) = vao {
+            gl::BindVertexArray(vao);
+        }
+        if let Vertices::Buffer {
+            buffer,
+            first_vertex,
+        } = vertices
+        {
+            GlSpriteRender::set_sprite_attributes(res.shader_program, buffer, first_vertex);
+        }
+        gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, index_buffer);
+        gl_check_error!("draw elements");
+        gl::DrawElements(gl::TRIANGLES, index_count, gl::UNSIGNED_SHORT, ptr::null());
+        if let Vertices::Buffer { .. } = vertices {
+            GlSpriteRender::set_sprite_attributes(res.shader_program, res.vertex_buffer, 0);
+        }
+
+        gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+        gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, 0);
+        if vao.is_some() {
+            gl::BindVertexArray(0);
+        }
+
+        gl_check_error!("end frame");
+    }
+
+    /// The size of the render target, in pixels.
+    fn target_size(&self) -> (u32, u32) {
+        match self.target {
+            Some(texture) => {
+                let res = self.render.shared_resources.as_ref().unwrap();
+                res.get_gl_texture(texture)
+                    .map_or((0, 0), |x| (x.width, x.height))
+            }
+            None => self.render.current_context.as_ref().unwrap().1.size,
+        }
+    }
+
+    /// Set the scissor to the current clip rectangle, or disable it if there is none.
+    unsafe fn apply_clip_rect(&self) {
+        let Some([x, y, width, height]) = self.clip.current() else {
+            gl::Disable(gl::SCISSOR_TEST);
+            return;
+        };
+        // The scissor is from the bottom left corner of the framebuffer, but the rows of a render
+        // texture are already flipped by the view.
+        let y = match self.target {
+            Some(_) => y,
+            None => self.target_size().1 as i32 - y - height,
+        };
+        gl::Enable(gl::SCISSOR_TEST);
+        gl::Scissor(x, y, width, height);
+    }
+
+    /// Set the stencil test to draw only inside all the pushed masks.
+    unsafe fn apply_masks(&self) {
+        gl::ColorMask(gl::TRUE, gl::TRUE, gl::TRUE, gl::TRUE);
+        gl::StencilOp(gl::KEEP, gl::KEEP, gl::KEEP);
+        match self.clip.masks() {
+            0 => gl::Disable(gl::STENCIL_TEST),
+            masks => gl::StencilFunc(gl::EQUAL, masks as i32, 0xFF),
+        }
+    }
+
+    /// Draw a quad that covers all the render target, with the current stencil state. Used to
+    /// update the stencil buffer without drawing the shapes of a mask again.
+    unsafe fn fill_target(&mut self) {
+        let res = self.render.shared_resources.as_mut().unwrap();
+        if res.buffer_size == 0 {
+            res.reallocate_vertex_buffer(1);
+        }
+        let Some(white) = res.get_gl_texture(WHITE_TEXTURE) else {
+            return;
+        };
+        gl::ActiveTexture(gl::TEXTURE0);
+        gl::BindTexture(gl::TEXTURE_2D, white.name);
+        res.texture_unit_map.clear();
+        // the positions are already in normalized device coordinates.
+        let quad = Quad {
+            positions: [[-1.0, -1.0], [1.0, -1.0], [-1.0, 1.0], [1.0, 1.0]],
+            uvs: [[0.0, 0.0], [1.0, 0.0], [0.0, 1.0], [1.0, 1.0]],
+            color: [255; 4],
+            texture: WHITE_TEXTURE,
+            material: MaterialId::default(),
+        };
+        let mut data = Vec::with_capacity(SPRITE_VERTEX_STRIDE * 4);
+        let texture_unit = white.gl_format.swizzle << 8;
+        GlSpriteRender::write_quad(&mut data, &quad, texture_unit).unwrap();
+        let index_buffer = res.indice_buffer;
+        let identity = [1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0];
+        let vertices = Vertices::Data(&data);
+        self.draw_batch(&identity, MaterialId::default(), vertices, index_buffer, 6);
+    }
+}
+impl<'a> Renderer for GlRenderer<'a> {
+    fn clear_screen(&mut self, color: &[f32; 4]) -> &mut dyn Renderer {
+        log::trace!(
+            "clear screen to [{:5.3}, {:5.3}, {:5.3}, {:5.3}]",
+            color[0],
+            color[1],
+            color[2],
+            color[3]
+        );
+        let mut color = *color;
+        let context = &self.render.current_context.as_ref().unwrap().1;
+        if context.srgb {
+            // the framebuffer converts the clear color to sRGB.
+            color[..3]
+                .iter_mut()
+                .for_each(|x| *x = material::srgb_to_linear(*x));
+        }
+        unsafe {
+            gl::ClearColor(color[0], color[1], color[2], color[3]);
+            gl::Clear(gl::COLOR_BUFFER_BIT);
+        }
+        self
+    }
+
+    fn draw_sprites(
+        &mut self,
+        camera: &mut Camera,
+        sprites: &[SpriteInstance],
+    ) -> &mut dyn Renderer {
+        log::trace!("draw {} sprites", sprites.len());
+        self.draw_quads(camera, sprites.len(), sprites.iter().map(Quad::from));
+        self
+    }
+
+    fn draw_shapes(&mut self, camera: &mut Camera, shapes: &Shapes) -> &mut dyn Renderer {
+        log::trace!("draw {} shape quads", shapes.quads.len());
+        self.draw_quads(camera, shapes.quads.len(), shapes.quads.iter().cloned());
+        self
+    }
 
-                let Some(res) = &self.render.shared_resources else {
-                    panic!("OpenGL context don't exist.")
-                };
+    fn draw_mesh(&mut self, camera: &mut Camera, mesh: &Mesh) -> &mut dyn Renderer {
+        log::trace!(
+            "draw mesh with {} vertices and {} indices",
+            mesh.vertices.len(),
+            match mesh.indices {
+                Indices::U16(x) => x.len(),
+                Indices::U32(x) => x.len(),
+            }
+        );
+        let chunks = mesh::chunks(mesh);
+        if chunks.is_empty() {
+            return self;
+        }
+        let view = self.view(camera);
 
-                if let Some(vao) = self.render.vao() {
-                    gl::BindVertexArray(vao);
+        let Some(res) = &mut self.render.shared_resources else {
+            panic!("OpenGL context don't exist.")
+        };
+        let id = res.feedback_loops.texture(mesh.texture, self.target);
+        let texture = match res.get_gl_texture(id) {
+            Some(x) => x,
+            None => {
+                res.missing_textures.warn(id);
+                match res.get_gl_texture(FALLBACK_TEXTURE) {
+                    Some(x) => x,
+                    None => return self,
+                }
+            }
+        };
+        let texture_units = res.program(mesh.material).1;
+        res.texture_unit_map.clear();
+        res.clear_normal_maps(texture_units);
+        if res.uses_normal_maps(mesh.material) {
+            unsafe { res.bind_normal_map(texture.id, 0, texture_units) };
+        }
+        let texture_unit = texture.gl_format.swizzle << 8;
+
+        let mut data = Vec::new();
+        for chunk in chunks {
+            let res = self.render.shared_resources.as_mut().unwrap();
+            let sprites_need = chunk.vertices.len().div_ceil(4);
+            if sprites_need > res.buffer_size as usize {
+                res.reallocate_vertex_buffer(sprites_need);
+            }
+            data.clear();
+            unsafe {
+                for vertex in chunk.vertices.iter() {
+                    GlSpriteRender::write_vertex(&mut data, vertex, texture_unit).unwrap();
                 }
-                gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, res.indice_buffer);
-                gl_check_error!("draw arrays instanced");
-                gl::DrawElements(gl::TRIANGLES, count * 6, gl::UNSIGNED_SHORT, ptr::null());
 
-                gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+                gl::ActiveTexture(gl::TEXTURE0);
+                gl::BindTexture(gl::TEXTURE_2D, texture.name);
+
+                let index_buffer = res.mesh_indice_buffer;
+                gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, index_buffer);
+                gl::BufferData(
+                    gl::ELEMENT_ARRAY_BUFFER,
+                    mem::size_of_val(&*chunk.indices) as GLsizeiptr,
+                    chunk.indices.as_ptr() as *const c_void,
+                    gl::STREAM_DRAW,
+                );
                 gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, 0);
-                if self.render.vao().is_some() {
-                    gl::BindVertexArray(0);
-                }
 
-                gl_check_error!("end frame");
+                let count = chunk.indices.len() as i32;
+                self.draw_batch(
+                    &view,
+                    mesh.material,
+                    Vertices::Data(&data),
+                    index_buffer,
+                    count,
+                );
+            }
+        }
+        self
+    }
+
+    fn draw_static_batch(&mut self, camera: &mut Camera, batch: BatchId) -> &mut dyn Renderer {
+        let view = self.view(camera);
+        self.draw_static(&view, batch);
+        self
+    }
+
+    fn draw_static_batch_transformed(
+        &mut self,
+        camera: &mut Camera,
+        batch: BatchId,
+        transform: &Transform,
+    ) -> &mut dyn Renderer {
+        let view = transform.apply(&self.view(camera));
+        self.draw_static(&view, batch);
+        self
+    }
+
+    fn push_clip_rect(&mut self, rect: ClipRect) -> &mut dyn Renderer {
+        let (width, height) = self.target_size();
+        let rect = self.clip.push(rect.to_pixels(width, height));
+        log::trace!("push clip rect {:?}", rect);
+        unsafe { self.apply_clip_rect() };
+        self
+    }
+
+    fn pop_clip_rect(&mut self) -> &mut dyn Renderer {
+        if !self.clip.pop() {
+            log::warn!("pop_clip_rect called without a pushed clip rect");
+            return self;
+        }
+        unsafe { self.apply_clip_rect() };
+        self
+    }
+
+    fn begin_mask(&mut self) -> &mut dyn Renderer {
+        if !self.clip.begin_mask() {
+            return self;
+        }
+        let masks = self.clip.masks();
+        unsafe {
+            if masks == 0 {
+                // the scissor also limits the clear.
+                gl::Disable(gl::SCISSOR_TEST);
+                gl::ClearStencil(0);
+                gl::Clear(gl::STENCIL_BUFFER_BIT);
+                self.apply_clip_rect();
+                gl::Enable(gl::STENCIL_TEST);
+            }
+            // increment the pixels inside all the pushed masks, without drawing colors.
+            gl::ColorMask(gl::FALSE, gl::FALSE, gl::FALSE, gl::FALSE);
+            gl::StencilFunc(gl::EQUAL, masks as i32, 0xFF);
+            gl::StencilOp(gl::KEEP, gl::KEEP, gl::INCR);
+        }
+        self
+    }
+
+    fn end_mask(&mut self) -> &mut dyn Renderer {
+        if self.clip.end_mask() {
+            unsafe { self.apply_masks() };
+        }
+        self
+    }
+
+    fn pop_mask(&mut self) -> &mut dyn Renderer {
+        if !self.clip.pop_mask() {
+            return self;
+        }
+        let masks = self.clip.masks();
+        if masks > 0 {
+            // decrement the pixels inside the popped mask, including the ones outside the scissor.
+            unsafe {
+                gl::Disable(gl::SCISSOR_TEST);
+                gl::ColorMask(gl::FALSE, gl::FALSE, gl::FALSE, gl::FALSE);
+                gl::StencilFunc(gl::EQUAL, masks as i32 + 1, 0xFF);
+                gl::StencilOp(gl::KEEP, gl::KEEP, gl::DECR);
+                self.fill_target();
+                self.apply_clip_rect();
             }
         }
+        unsafe { self.apply_masks() };
         self
     }
 
     fn finish(&mut self) {
         log::trace!("finish");
-        self.render
-            .current_context
-            .as_ref()
-            .unwrap()
-            .1
-            .swap_buffers()
-            .unwrap();
+        if !self.clip.is_empty() {
+            log::warn!("finish called with clip rects or masks still pushed");
+            self.clip = ClipStack::default();
+        }
+        unsafe {
+            self.apply_clip_rect();
+            self.apply_masks();
+        }
+        if self.target.is_some() {
+            let context = &self.render.current_context.as_ref().unwrap().1;
+            unsafe {
+                gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+                gl::Viewport(0, 0, context.size.0 as i32, context.size.1 as i32);
+            }
+            return;
+        }
+        unsafe { self.render.apply_post_effects() };
+        let context = &self.render.current_context.as_ref().unwrap().1;
+        context.swap_buffers().unwrap();
     }
 }
 
@@ -256,6 +698,8 @@ pub enum Error {
     CouldNotQueryVersion,
     /// OpenGL major version is smaller than 2.
     UnsupportedOpenGlVersion,
+    /// The built-in shaders failed to compile.
+    Shader(ShaderError),
 }
 impl From<glutin::error::Error> for Error {
     fn from(value: glutin::error::Error) -> Self {
@@ -267,8 +711,17 @@ struct Context<T> {
     context: T,
     surface: Surface<WindowSurface>,
     config: glutin::config::Config,
+    /// If the surface converts the written colors to sRGB, in [ColorSpace::Linear].
+    srgb: bool,
     /// It is None when OpenGL version is 2.0
     vao: Option<u32>,
+    /// The size of the surface, in pixels.
+    size: (u32, u32),
+    /// Framebuffer objects are not shared between contexts, so each context has its own
+    /// framebuffer for each render texture.
+    framebuffers: HashMap<TextureId, u32>,
+    /// The post-processing of the surface, if it has any post effect.
+    post_process: Option<PostProcess>,
 }
 impl<T> Context<T> {
     fn map<U, F: FnOnce(T, &Surface<WindowSurface>) -> glutin::error::Result<U>>(
@@ -279,13 +732,21 @@ impl<T> Context<T> {
             context,
             surface,
             config,
+            srgb,
             vao,
+            size,
+            framebuffers,
+            post_process,
         } = self;
         Ok(Context {
             context: f(context, &surface)?,
             surface,
             config,
+            srgb,
             vao,
+            size,
+            framebuffers,
+            post_process,
         })
     }
 }
@@ -296,8 +757,11 @@ impl Context<NotCurrentContext> {
 }
 impl Context<PossiblyCurrentContext> {
     fn new(
-        window: &Window,
+        window: &dyn RawWindow,
+        width: u32,
+        height: u32,
         vsync: bool,
+        color_space: ColorSpace,
         shared: Option<&Context<PossiblyCurrentContext>>,
     ) -> Result<Self, Error> {
         let raw_window_handle = window.raw_window_handle();
@@ -316,13 +780,19 @@ impl Context<PossiblyCurrentContext> {
 
         let template = ConfigTemplateBuilder::new()
             .compatible_with_native_window(raw_window_handle)
+            .with_stencil_size(8)
             .build();
 
+        let linear = color_space == ColorSpace::Linear;
         let config = {
             let configs = unsafe { display.find_configs(template)? };
+            // in the linear color space, prefer a sRGB capable config over more samples.
+            let key = |config: &glutin::config::Config| {
+                (linear && config.srgb_capable(), config.num_samples())
+            };
             configs
                 .reduce(|accum, config| {
-                    if config.num_samples() > accum.num_samples() {
+                    if key(&config) > key(&accum) {
                         config
                     } else {
                         accum
@@ -347,17 +817,15 @@ impl Context<PossiblyCurrentContext> {
 
         let context = unsafe { display.create_context(&config, &context_attributes)? };
 
-        let size = window.inner_size();
-        let (width, height) = match (NonZeroU32::new(size.width), NonZeroU32::new(size.height)) {
+        let (width, height) = match (NonZeroU32::new(width), NonZeroU32::new(height)) {
             (Some(w), Some(h)) => (w, h),
             _ => return Err(Error::BadDimensions),
         };
 
-        let surface_attributes = SurfaceAttributesBuilder::<WindowSurface>::new().build(
-            raw_window_handle,
-            width,
-            height,
-        );
+        let srgb = linear && config.srgb_capable();
+        let surface_attributes = SurfaceAttributesBuilder::<WindowSurface>::new()
+            .with_srgb(linear.then_some(srgb))
+            .build(raw_window_handle, width, height);
         let surface = unsafe { display.create_window_surface(&config, &surface_attributes)? };
 
         let context = context.make_current(&surface)?;
@@ -370,7 +838,11 @@ impl Context<PossiblyCurrentContext> {
             context,
             surface,
             config,
+            srgb,
             vao: None,
+            size: (width.get(), height.get()),
+            framebuffers: HashMap::new(),
+            post_process: None,
         })
     }
 
@@ -387,20 +859,197 @@ impl Context<PossiblyCurrentContext> {
     }
 }
 
+/// The post-processing state of a window surface.
+struct PostProcess {
+    effects: Vec<PostEffect>,
+    /// The two render targets, as `(texture, framebuffer)`, that the effects ping-pong between.
+    /// The scene is rendered to the first one.
+    targets: Vec<(u32, u32)>,
+    /// The stencil renderbuffer of the first render target, or 0 if it could not be attached.
+    stencil: u32,
+    /// The size of the render targets, in pixels.
+    size: (u32, u32),
+    /// The VAO used to draw the full-screen quad. It is None when OpenGL version is 2.0
+    vao: Option<u32>,
+}
+
+/// How a `TextureFormat` is stored in OpenGL.
+#[derive(Debug, Clone, Copy)]
+struct GlFormat {
+    internal_format: i32,
+    format: u32,
+    type_: u32,
+    /// How the sampled color is remapped in the shader.
+    swizzle: u16,
+}
+impl GlFormat {
+    /// Returns None if the format is not supported in the given OpenGL version, or if it is a
+    /// compressed format that is not in `compressed_formats`.
+    fn new(
+        format: TextureFormat,
+        major_version: u8,
+        compressed_formats: &[GLenum],
+        color_space: ColorSpace,
+    ) -> Option<Self> {
+        // In the linear color space, the colors are stored in sRGB, and converted to linear when
+        // sampled, by the hardware when there is a sRGB internal format, or else by the shader.
+        let (srgb_format, srgb_swizzle) = match (color_space, format) {
+            (ColorSpace::Gamma, _)
+            | (_, TextureFormat::R8 | TextureFormat::Rg8 | TextureFormat::Rgba16F) => (None, 0),
+            (_, TextureFormat::Rgba8888) if major_version > 2 => (Some(gl::SRGB8_ALPHA8), 0),
+            (_, TextureFormat::Rgb888) if major_version > 2 => (Some(gl::SRGB8), 0),
+            _ => (None, SRGB_DECODE),
+        };
+        let compressed = |internal_format: GLenum| {
+            compressed_formats.contains(&internal_format).then_some((
+                internal_format,
+                internal_format,
+                0,
+                SWIZZLE_NONE,
+            ))
+        };
+        let (internal_format, format, type_, swizzle) = match format {
+            TextureFormat::Rgba8888 => (gl::RGBA, gl::RGBA, gl::UNSIGNED_BYTE, SWIZZLE_NONE),
+            TextureFormat::R8 if major_version > 2 => {
+                (gl::R8, gl::RED, gl::UNSIGNED_BYTE, SWIZZLE_ALPHA)
+            }
+            // LUMINANCE is sampled as (l, l, l, 1), so it is swizzled in the same way as R8.
+            TextureFormat::R8 => (LUMINANCE, LUMINANCE, gl::UNSIGNED_BYTE, SWIZZLE_ALPHA),
+            TextureFormat::Rg8 if major_version > 2 => {
+                (gl::RG8, gl::RG, gl::UNSIGNED_BYTE, SWIZZLE_NONE)
+            }
+            TextureFormat::Rg8 => return None,
+            TextureFormat::Rgb888 => (gl::RGB, gl::RGB, gl::UNSIGNED_BYTE, SWIZZLE_NONE),
+            TextureFormat::Rgb565 => (gl::RGB, gl::RGB, gl::UNSIGNED_SHORT_5_6_5, SWIZZLE_NONE),
+            TextureFormat::Rgba4444 => {
+                (gl::RGBA, gl::RGBA, gl::UNSIGNED_SHORT_4_4_4_4, SWIZZLE_NONE)
+            }
+            TextureFormat::Rgba16F if major_version > 2 => {
+                (gl::RGBA16F, gl::RGBA, gl::HALF_FLOAT, SWIZZLE_NONE)
+            }
+            TextureFormat::Rgba16F => return None,
+            // LUMINANCE_ALPHA does not exist in the core profile.
+            TextureFormat::LuminanceAlpha if major_version > 2 => {
+                (gl::RG8, gl::RG, gl::UNSIGNED_BYTE, SWIZZLE_LUMINANCE_ALPHA)
+            }
+            TextureFormat::LuminanceAlpha => (
+                LUMINANCE_ALPHA,
+                LUMINANCE_ALPHA,
+                gl::UNSIGNED_BYTE,
+                SWIZZLE_NONE,
+            ),
+            TextureFormat::Bc1 => compressed(COMPRESSED_RGBA_S3TC_DXT1_EXT)?,
+            TextureFormat::Bc2 => compressed(COMPRESSED_RGBA_S3TC_DXT3_EXT)?,
+            TextureFormat::Bc3 => compressed(COMPRESSED_RGBA_S3TC_DXT5_EXT)?,
+            TextureFormat::Etc2Rgb8 => compressed(gl::COMPRESSED_RGB8_ETC2)?,
+            TextureFormat::Etc2Rgba8 => compressed(gl::COMPRESSED_RGBA8_ETC2_EAC)?,
+            TextureFormat::Astc4x4 => compressed(COMPRESSED_RGBA_ASTC_4X4_KHR)?,
+        };
+        Some(Self {
+            internal_format: srgb_format.unwrap_or(internal_format) as i32,
+            format,
+            type_,
+            swizzle: swizzle | srgb_swizzle,
+        })
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 struct GlTexture {
     id: TextureId,
     name: u32,
     width: u32,
     height: u32,
+    format: TextureFormat,
+    gl_format: GlFormat,
+    /// If this texture was created with `new_render_texture`.
+    render_texture: bool,
+    /// The stencil renderbuffer attached to the framebuffers of a render texture, or 0 if it was
+    /// not created yet.
+    stencil: u32,
+    /// If the mipmaps of this texture are generated, and must be regenerated on update.
+    generate_mipmaps: bool,
+}
+
+/// The `TEXTURE_MIN_FILTER` of the given sampler.
+fn min_filter(sampler: &Sampler, has_mipmaps: bool) -> GLenum {
+    use TextureFilter::*;
+    match (
+        sampler.min_filter,
+        has_mipmaps.then_some(sampler.mip_filter),
+    ) {
+        (Nearest, None) => gl::NEAREST,
+        (Linear, None) => gl::LINEAR,
+        (Nearest, Some(Nearest)) => gl::NEAREST_MIPMAP_NEAREST,
+        (Linear, Some(Nearest)) => gl::LINEAR_MIPMAP_NEAREST,
+        (Nearest, Some(Linear)) => gl::NEAREST_MIPMAP_LINEAR,
+        (Linear, Some(Linear)) => gl::LINEAR_MIPMAP_LINEAR,
+    }
+}
+
+fn filter(filter: TextureFilter) -> GLenum {
+    match filter {
+        TextureFilter::Nearest => gl::NEAREST,
+        TextureFilter::Linear => gl::LINEAR,
+    }
+}
+
+/// The source and destination factors of a blend mode.
+fn blend_factors(blend: BlendMode) -> (GLenum, GLenum) {
+    match blend {
+        BlendMode::Alpha => (gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA),
+        BlendMode::Additive => (gl::SRC_ALPHA, gl::ONE),
+        BlendMode::Multiply => (gl::DST_COLOR, gl::ZERO),
+        BlendMode::Premultiplied => (gl::ONE, gl::ONE_MINUS_SRC_ALPHA),
+    }
+}
+
+fn wrap(wrap: TextureWrap) -> GLenum {
+    match wrap {
+        TextureWrap::ClampToEdge => gl::CLAMP_TO_EDGE,
+        TextureWrap::Repeat => gl::REPEAT,
+        TextureWrap::MirroredRepeat => gl::MIRRORED_REPEAT,
+    }
+}
+
+/// A material compiled by `new_material`.
+struct GlMaterial {
+    /// The OpenGL object for the Shader.
+    program: u32,
+    /// The number of texture units used by the sprite textures. If `normal_maps` is true, their
+    /// normal maps use the same number of units after these. The texture uniforms of the material
+    /// use the units after all of them.
+    texture_units: i32,
+    normal_maps: bool,
+    uniforms: Vec<(String, Uniform)>,
+    blend: BlendMode,
+}
+
+/// A static batch created by `new_static_batch`.
+struct GlStaticBatch {
+    /// The OpenGL object for the Vertex Buffer with the vertices of all sprites of the batch.
+    buffer: u32,
+    groups: Vec<BatchGroup>,
+    /// The sprites of the batch, to group them again when a range update changes the groups.
+    sprites: Vec<SpriteInstance>,
 }
 
 /// OpenGL resources that are created only once, and are shader by all OpenGL contexts.
 struct SharedResources {
-    /// The OpenGL object for the Shader.
+    /// The OpenGL object for the Shader of the default material.
     shader_program: u32,
+    /// The materials created by the user.
+    materials: HashMap<MaterialId, GlMaterial>,
+    /// The id that will be given to the next created material.
+    next_material_id: u32,
     /// The OpenGL object for the Indice Buffer.
     indice_buffer: u32,
+    /// The index buffer of the meshes, rewritten for each mesh.
+    mesh_indice_buffer: u32,
+    /// The static batches created by the user.
+    static_batches: HashMap<BatchId, GlStaticBatch>,
+    /// The id that will be given to the next created static batch.
+    next_batch_id: u32,
     /// The OpenGL object for the Vertex Buffer.
     vertex_buffer: u32,
 
@@ -408,16 +1057,104 @@ struct SharedResources {
     buffer_size: u32,
     // Textures currently loaded in OpenGL.
     textures: Vec<GlTexture>,
-    /// maps a texture to a texture unit
+    /// maps a texture to its index in the vertices, `unit | swizzle << 8`.
     texture_unit_map: HashMap<TextureId, u32>,
+    /// maps a texture to its normal map, set with `set_normal_map`.
+    normal_maps: HashMap<TextureId, TextureId>,
+    /// If a normal map is bound for each texture unit of the current batch, for the
+    /// `hasNormalMap` uniform.
+    normal_map_flags: NormalMapFlags,
     /// The maximum number of Textures Units supported by the curretn OpenGL context.
     max_texture_units: i32,
+    /// The compressed texture formats supported by the current OpenGL context.
+    compressed_formats: Vec<GLenum>,
+    /// The textures that were not found when drawing.
+    missing_textures: MissingTextures,
+    /// The render textures that were drawn to themselves.
+    feedback_loops: FeedbackLoops,
+    /// The maximum anisotropy supported by the current OpenGL context, or 1.0 if anisotropic
+    /// filtering is not supported.
+    max_anisotropy: f32,
+
+    /// The OpenGL object for the Vertex Buffer of the full-screen quad, used by post effects.
+    post_quad_buffer: u32,
+    /// The shader programs of the post effects, indexed by `PostEffect::kind`.
+    post_programs: HashMap<usize, u32>,
 }
 impl SharedResources {
     pub fn get_gl_texture(&self, id: TextureId) -> Option<GlTexture> {
         self.textures.iter().find(|x| x.id == id).copied()
     }
 
+    /// The texture unit of the fallback texture, if it is already bound in the current batch and
+    /// `id` does not exist.
+    fn missing_texture_unit(&self, id: TextureId) -> Option<u32> {
+        let unit = *self.texture_unit_map.get(&FALLBACK_TEXTURE)?;
+        (!self.textures.iter().any(|x| x.id == id)).then_some(unit)
+    }
+
+    /// The shader program of a material, and the number of texture units it uses for the sprite
+    /// textures. Unknown materials use the default program.
+    fn program(&self, material: MaterialId) -> (u32, i32) {
+        match self.materials.get(&material) {
+            Some(x) => (x.program, x.texture_units),
+            None => (self.shader_program, self.max_texture_units),
+        }
+    }
+
+    /// If the program of a material samples the normal maps of the sprite textures.
+    fn uses_normal_maps(&self, material: MaterialId) -> bool {
+        self.materials.get(&material).is_some_and(|x| x.normal_maps)
+    }
+
+    /// Mark all `texture_units` of a new batch as not having a normal map.
+    fn clear_normal_maps(&mut self, texture_units: i32) {
+        self.normal_map_flags.clear(texture_units);
+    }
+
+    /// Bind the normal map of `texture`, if it has one, to the unit `texture_units` after the
+    /// unit of the texture.
+    unsafe fn bind_normal_map(&mut self, texture: TextureId, unit: u32, texture_units: i32) {
+        let Some(&normal_map) = self.normal_maps.get(&texture) else {
+            return;
+        };
+        let Some(normal_map) = self.get_gl_texture(normal_map) else {
+            self.missing_textures.warn(normal_map);
+            return;
+        };
+        gl::ActiveTexture(gl::TEXTURE0 + texture_units as u32 + unit);
+        gl::BindTexture(gl::TEXTURE_2D, normal_map.name);
+        self.normal_map_flags.set(unit);
+    }
+
+    /// Write the vertices of `sprites` to `buffer`, returning the groups they are drawn in.
+    fn write_static_batch(&self, buffer: u32, sprites: &[SpriteInstance]) -> Vec<BatchGroup> {
+        let mut data = Vec::with_capacity(sprites.len() * SPRITE_VERTEX_STRIDE * 4);
+        let groups = static_batch::group_quads(
+            sprites.iter().map(Quad::from),
+            |material| self.program(material).1 as usize,
+            |texture| {
+                self.get_gl_texture(texture)
+                    .map_or(0, |x| x.gl_format.swizzle)
+            },
+            |quad, texture| unsafe {
+                GlSpriteRender::write_quad(&mut data, quad, texture).unwrap();
+            },
+        );
+        unsafe {
+            gl::BindBuffer(gl::ARRAY_BUFFER, buffer);
+            gl::BufferData(
+                gl::ARRAY_BUFFER,
+                data.len() as GLsizeiptr,
+                data.as_ptr() as *const c_void,
+                gl::STATIC_DRAW,
+            );
+            gl_check_error!("static batch buffer data");
+            gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+        }
+        groups
+    }
+
     fn reallocate_vertex_buffer(&mut self, size_need: usize) {
         let new_size = size_need.next_power_of_two();
         log::trace!("reallocating vertex buffer: size need {size_need}, new_size {new_size}");
@@ -452,21 +1189,52 @@ impl SharedResources {
 
 pub struct GlSpriteRender {
     vsync: bool,
-    contexts: HashMap<WindowId, Option<Context<NotCurrentContext>>>,
-    current_context: Option<(WindowId, Context<PossiblyCurrentContext>)>,
+    color_space: ColorSpace,
+    fallback: FallbackTexture,
+    contexts: HashMap<SurfaceId, Option<Context<NotCurrentContext>>>,
+    current_context: Option<(SurfaceId, Context<PossiblyCurrentContext>)>,
+    /// The id that will be given to the next added window.
+    next_surface_id: u32,
     major_version: u8,
 
     shared_resources: Option<SharedResources>,
+    /// Watch the shader source files of the materials, to reload them on change.
+    #[cfg(feature = "hot-reload")]
+    shader_watcher: hot_reload::ShaderWatcher,
 }
 impl GlSpriteRender {
-    /// Get a WindowBuilder and a event_loop (for opengl support), and return a window and Self.
-    pub fn new(window: &Window, vsync: bool) -> Result<Self, Error> {
+    /// Create a new GlSpriteRender that renders to the given window, whose size in pixels is
+    /// `width` x `height`.
+    ///
+    /// The window surface will have the default [SurfaceId].
+    pub fn new(
+        window: &dyn RawWindow,
+        width: u32,
+        height: u32,
+        vsync: bool,
+    ) -> Result<Self, Error> {
+        Self::new_with_color_space(window, width, height, vsync, ColorSpace::Gamma)
+    }
+
+    /// Same as [GlSpriteRender::new], but blending the colors in the given [ColorSpace].
+    pub fn new_with_color_space(
+        window: &dyn RawWindow,
+        width: u32,
+        height: u32,
+        vsync: bool,
+        color_space: ColorSpace,
+    ) -> Result<Self, Error> {
         let mut sprite_render = Self {
             vsync,
+            color_space,
+            fallback: FallbackTexture::default(),
             contexts: HashMap::new(),
             current_context: None,
+            next_surface_id: 1,
             major_version: 0,
             shared_resources: None,
+            #[cfg(feature = "hot-reload")]
+            shader_watcher: Default::default(),
         };
 
         #[cfg(target_os = "android")]
@@ -474,19 +1242,76 @@ impl GlSpriteRender {
             // TODO: need to find a reliable way of detecting if Android's window raw_window_handle
             // is null.
             // This may panic in the future: https://github.com/rust-windowing/winit/issues/2482
-            if window.inner_size() == (0, 0).into() {
+            if width == 0 && height == 0 {
                 return Ok(sprite_render);
             }
         }
 
-        sprite_render.create_context_and_resources(window)?;
+        sprite_render.create_context_and_resources(window, width, height)?;
 
         Ok(sprite_render)
     }
 
+    /// Load the fragment shader source of the default material from the given file, in the same
+    /// format of a [Material] source.
+    ///
+    /// The file is loaded before the next frame, and reloaded between frames when it changes. If
+    /// the compilation fails, the error is logged and the previous shader is kept.
+    #[cfg(feature = "hot-reload")]
+    pub fn watch_default_material(
+        &mut self,
+        path: impl AsRef<std::path::Path>,
+    ) -> std::io::Result<()> {
+        let path = path.as_ref();
+        // fail early if the file does not exist.
+        std::fs::metadata(path)?;
+        self.shader_watcher
+            .watch(MaterialId::default(), path.to_path_buf(), None);
+        Ok(())
+    }
+
+    /// Recompile the materials whose shader source file has changed. The shared resources must
+    /// exist, and a context must be current.
+    #[cfg(feature = "hot-reload")]
+    fn reload_shaders(&mut self) {
+        let Some(res) = &mut self.shared_resources else {
+            return;
+        };
+        for (id, source) in self.shader_watcher.poll() {
+            let (texture_units, normal_maps) = if id == MaterialId::default() {
+                (res.max_texture_units, false)
+            } else {
+                match res.materials.get(&id) {
+                    Some(material) => (material.texture_units, material.normal_maps),
+                    None => continue,
+                }
+            };
+            let program = match unsafe {
+                Self::create_sprite_program(&source, texture_units, normal_maps, self.color_space)
+            } {
+                Ok(x) => x,
+                Err(err) => {
+                    log::error!("failed to reload material {}: {}", id, err);
+                    continue;
+                }
+            };
+            log::info!("reloaded material {}", id);
+            let old_program = match res.materials.get_mut(&id) {
+                Some(material) => mem::replace(&mut material.program, program),
+                None => mem::replace(&mut res.shader_program, program),
+            };
+            unsafe { gl::DeleteProgram(old_program) };
+        }
+    }
+
     /// Create the first context and resources that will be shared by all following contexts.
-    fn create_context_and_resources(&mut self, window: &Window) -> Result<(), Error> {
-        let mut context = Context::new(window, self.vsync, None)?;
+    fn create_context_and_resources(
+        &mut self,
+        window: &dyn RawWindow,
+        width: u32,
+        height: u32,
+    ) -> Result<(), Error> {
+        let mut context = Context::new(window, width, height, self.vsync, self.color_space, None)?;
 
         gl::load_with(|symbol| {
             let symbol = CString::new(symbol).unwrap();
@@ -507,7 +1332,7 @@ impl GlSpriteRender {
         let major_version = if let Some(version) = get_gl_string(gl::VERSION) {
             log::info!("OpenGL Version {}", version.to_string_lossy());
             let Some((major_version, _)) = parse_version_number(version) else {
-                return Err(Error::CouldNotQueryVersion)
+                return Err(Error::CouldNotQueryVersion);
             };
             if major_version < 2 {
                 return Err(Error::UnsupportedOpenGlVersion);
@@ -530,11 +1355,56 @@ impl GlSpriteRender {
         }
         log::info!("MAX_TEXTURE_IMAGE_UNITS: {}", max_texture_units);
 
+        let compressed_formats = unsafe {
+            let mut len = 0;
+            gl::GetIntegerv(gl::NUM_COMPRESSED_TEXTURE_FORMATS, &mut len);
+            let mut formats = vec![0; len.max(0) as usize];
+            if len > 0 {
+                gl::GetIntegerv(gl::COMPRESSED_TEXTURE_FORMATS, formats.as_mut_ptr());
+            }
+            formats.into_iter().map(|x| x as GLenum).collect::<Vec<_>>()
+        };
+        log::info!("COMPRESSED_TEXTURE_FORMATS: {:x?}", compressed_formats);
+
+        let has_extension = |name: &str| unsafe {
+            if major_version > 2 {
+                let mut len = 0;
+                gl::GetIntegerv(gl::NUM_EXTENSIONS, &mut len);
+                (0..len.max(0) as u32).any(|i| {
+                    let s = gl::GetStringi(gl::EXTENSIONS, i);
+                    !s.is_null() && CStr::from_ptr(s.cast()).to_bytes() == name.as_bytes()
+                })
+            } else {
+                get_gl_string(gl::EXTENSIONS).is_some_and(|x| {
+                    x.to_bytes()
+                        .split(|&c| c == b' ')
+                        .any(|x| x == name.as_bytes())
+                })
+            }
+        };
+        let mut max_anisotropy = 1.0;
+        if has_extension("GL_EXT_texture_filter_anisotropic")
+            || has_extension("GL_ARB_texture_filter_anisotropic")
+        {
+            unsafe { gl::GetFloatv(gl::MAX_TEXTURE_MAX_ANISOTROPY, &mut max_anisotropy) };
+        }
+        log::info!("MAX_TEXTURE_MAX_ANISOTROPY: {}", max_anisotropy);
+
+        // GL_FRAMEBUFFER_SRGB is only core since OpenGL 3.0.
+        context.srgb &= major_version > 2;
         unsafe {
-            Self::init_context();
+            Self::init_context(context.srgb);
         }
 
-        let shared_resources = unsafe { Self::create_resources(max_texture_units) };
+        let shared_resources = unsafe {
+            Self::create_resources(
+                max_texture_units,
+                compressed_formats,
+                max_anisotropy,
+                self.color_space,
+            )
+        }
+        .map_err(Error::Shader)?;
 
         context.vao = unsafe {
             Self::create_vao(
@@ -546,82 +1416,444 @@ impl GlSpriteRender {
 
         log::trace!("finished sprite-render creation");
         let mut contexts = HashMap::new();
-        let window_id = window.id();
-        contexts.insert(window_id, None);
+        let surface_id = SurfaceId::default();
+        contexts.insert(surface_id, None);
 
         self.contexts = contexts;
-        self.current_context = Some((window.id(), context));
+        self.current_context = Some((surface_id, context));
         self.major_version = major_version;
         self.shared_resources = Some(shared_resources);
 
-        let size = window.inner_size();
-        self.resize(window.id(), size.width, size.height);
+        let fallback = self.fallback;
+        fallback.create(self);
+        shape::create_white_texture(self);
+
+        self.resize(surface_id, width, height);
 
         Ok(())
     }
 
-    unsafe fn init_context() {
+    unsafe fn init_context(srgb: bool) {
         gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
         gl::Enable(gl::BLEND);
+        if srgb {
+            gl::Enable(gl::FRAMEBUFFER_SRGB);
+        }
+        // the rows of formats with less than 4 bytes per pixel are not aligned.
+        gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
     }
 
-    unsafe fn create_resources(max_texture_units: i32) -> SharedResources {
-        log::trace!("compiling vert shader");
-        let vert_shader = Self::compile_shader(gl::VERTEX_SHADER, VERTEX_SHADER_SOURCE).unwrap();
-        log::trace!("compiling vert shader");
-        let frag_shader = Self::compile_shader(
-            gl::FRAGMENT_SHADER,
-            &format!(
-                r#"
-#version 100
-#define MAX_TEXTURE_IMAGE_UNITS {}
-precision mediump float;
-
-uniform sampler2D text[MAX_TEXTURE_IMAGE_UNITS];
-
-varying vec4 color;
-varying vec2 TexCoord;
-varying float textureIndex;
-
-void main() {{
-    int t = int(textureIndex);
-    vec4 textureColor;
-    for (int i = 0; i < MAX_TEXTURE_IMAGE_UNITS; i++ ) {{
-        if (i == t) textureColor = texture2D(text[i], TexCoord);
-    }}
-    
-    if (textureColor.a == 0.0 || color.a == 0.0) {{
-        discard;
-    }}
-    gl_FragColor = textureColor*color;
-}}
-"#,
-                max_texture_units,
-            ),
-        )
-        .unwrap();
-        log::trace!("linking shader");
-        let shader_program = Self::link_program(vert_shader, frag_shader).unwrap();
+    unsafe fn create_resources(
+        max_texture_units: i32,
+        compressed_formats: Vec<GLenum>,
+        max_anisotropy: f32,
+        color_space: ColorSpace,
+    ) -> Result<SharedResources, ShaderError> {
+        log::trace!("compiling default material");
+        let shader_program = Self::create_sprite_program(
+            material::DEFAULT_FRAGMENT_SOURCE,
+            max_texture_units,
+            false,
+            color_space,
+        )?;
         gl_check_error!("linked program");
         gl::UseProgram(shader_program);
         log::trace!("generating buffers");
-        let mut buffers = [0; 2];
-        gl::GenBuffers(2, buffers.as_mut_ptr() as *mut GLuint);
-        let [vertex_buffer, indice_buffer] = buffers;
+        let mut buffers = [0; 4];
+        gl::GenBuffers(4, buffers.as_mut_ptr() as *mut GLuint);
+        let [vertex_buffer, indice_buffer, mesh_indice_buffer, post_quad_buffer] = buffers;
         log::debug!("buffers: {} {}", vertex_buffer, indice_buffer);
         gl_check_error!("gen buffers");
 
-        SharedResources {
+        gl::BindBuffer(gl::ARRAY_BUFFER, post_quad_buffer);
+        gl::BufferData(
+            gl::ARRAY_BUFFER,
+            mem::size_of_val(&post_process::POST_QUAD_VERTICES) as GLsizeiptr,
+            post_process::POST_QUAD_VERTICES.as_ptr() as *const c_void,
+            gl::STATIC_DRAW,
+        );
+        gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+        gl_check_error!("fill post quad buffer");
+
+        Ok(SharedResources {
             shader_program,
+            materials: HashMap::new(),
+            next_material_id: 1,
             indice_buffer,
+            mesh_indice_buffer,
+            static_batches: HashMap::new(),
+            next_batch_id: 1,
             vertex_buffer,
 
             buffer_size: 0,
 
-            textures: Vec::new(),
-            texture_unit_map: HashMap::new(),
-            max_texture_units,
+            textures: Vec::new(),
+            texture_unit_map: HashMap::new(),
+            normal_maps: HashMap::new(),
+            normal_map_flags: NormalMapFlags::default(),
+            max_texture_units,
+            compressed_formats,
+            missing_textures: MissingTextures::default(),
+            feedback_loops: FeedbackLoops::default(),
+            max_anisotropy,
+
+            post_quad_buffer,
+            post_programs: HashMap::new(),
+        })
+    }
+
+    /// Create a shader program for drawing sprites, with the given fragment shader source of a
+    /// material, where `texture_units` is the number of texture units used by the sprite textures,
+    /// and `normal_maps` is if their normal maps are sampled.
+    unsafe fn create_sprite_program(
+        source: &str,
+        texture_units: i32,
+        normal_maps: bool,
+        color_space: ColorSpace,
+    ) -> Result<u32, ShaderError> {
+        let vert_shader = Self::compile_shader(
+            gl::VERTEX_SHADER,
+            &format!(
+                "#version 100\n{}{}",
+                material::color_space_prelude(color_space),
+                VERTEX_SHADER_SOURCE
+            ),
+        )
+        .map_err(ShaderError::Compile)?;
+        let frag_shader = match Self::compile_shader(
+            gl::FRAGMENT_SHADER,
+            &format!(
+                "#version 100\n{}",
+                material::fragment_source(source, texture_units, normal_maps, color_space)
+            ),
+        ) {
+            Ok(x) => x,
+            Err(err) => {
+                gl::DeleteShader(vert_shader);
+                return Err(ShaderError::Compile(err));
+            }
+        };
+        Self::link_program(vert_shader, frag_shader).map_err(ShaderError::Link)
+    }
+
+    /// Set the uniforms of the given material in its program, which must be in use. The texture
+    /// uniforms are bound to the texture units after the ones used by the sprite textures.
+    unsafe fn set_material_uniforms(res: &SharedResources, material: &GlMaterial) {
+        let mut unit = material.texture_units * if material.normal_maps { 2 } else { 1 };
+        for (name, value) in &material.uniforms {
+            let location = get_uniform_location(material.program, name);
+            match *value {
+                Uniform::Float(x) => gl::Uniform1f(location, x),
+                Uniform::Vec2(x) => gl::Uniform2fv(location, 1, x.as_ptr()),
+                Uniform::Vec3(x) => gl::Uniform3fv(location, 1, x.as_ptr()),
+                Uniform::Vec4(x) => gl::Uniform4fv(location, 1, x.as_ptr()),
+                Uniform::Texture(texture) => {
+                    gl::ActiveTexture(gl::TEXTURE0 + unit as u32);
+                    match res.get_gl_texture(texture) {
+                        Some(texture) => gl::BindTexture(gl::TEXTURE_2D, texture.name),
+                        None => {
+                            log::error!("texture {} of uniform '{}' not found", texture, name);
+                            gl::BindTexture(gl::TEXTURE_2D, 0);
+                        }
+                    }
+                    gl::Uniform1i(location, unit);
+                    unit += 1;
+                }
+            }
+        }
+        gl_check_error!("set material uniforms");
+    }
+
+    /// Create the shader program of the given post effect, or of a effect that only copies the
+    /// image if `effect` is None.
+    unsafe fn create_post_program(effect: Option<&PostEffect>) -> Result<u32, String> {
+        let vert_shader = Self::compile_shader(
+            gl::VERTEX_SHADER,
+            &format!("#version 100\n{}", post_process::POST_VERTEX_SHADER_SOURCE),
+        )?;
+        let fragment_source = match effect {
+            Some(effect) => effect.fragment_source(),
+            None => post_process::copy_fragment_source(),
+        };
+        let frag_shader = match Self::compile_shader(
+            gl::FRAGMENT_SHADER,
+            &format!("#version 100\n{}", fragment_source),
+        ) {
+            Ok(x) => x,
+            Err(err) => {
+                gl::DeleteShader(vert_shader);
+                return Err(err);
+            }
+        };
+        Self::link_program(vert_shader, frag_shader)
+    }
+
+    /// Create a framebuffer object with the given texture as its color attachment.
+    ///
+    /// The framebuffer is left bound.
+    unsafe fn create_framebuffer(texture: u32) -> u32 {
+        let mut framebuffer = 0;
+        gl::GenFramebuffers(1, &mut framebuffer);
+        gl::BindFramebuffer(gl::FRAMEBUFFER, framebuffer);
+        gl::FramebufferTexture2D(
+            gl::FRAMEBUFFER,
+            gl::COLOR_ATTACHMENT0,
+            gl::TEXTURE_2D,
+            texture,
+            0,
+        );
+        let status = gl::CheckFramebufferStatus(gl::FRAMEBUFFER);
+        if status != gl::FRAMEBUFFER_COMPLETE {
+            log::error!("framebuffer is incomplete: {:#x}", status);
+        }
+        gl_check_error!("create framebuffer");
+        framebuffer
+    }
+
+    /// The internal format of the stencil renderbuffers, and the attachment point they use. The
+    /// packed depth-stencil format is the only one required to be supported since OpenGL 3.0.
+    fn stencil_format(major_version: u8) -> (GLenum, GLenum) {
+        if major_version > 2 {
+            (gl::DEPTH24_STENCIL8, gl::DEPTH_STENCIL_ATTACHMENT)
+        } else {
+            (gl::STENCIL_INDEX8, gl::STENCIL_ATTACHMENT)
+        }
+    }
+
+    /// Create a renderbuffer to be used as the stencil buffer of a render target, for the masks of
+    /// [Renderer::begin_mask].
+    unsafe fn create_stencil_buffer(width: u32, height: u32, major_version: u8) -> u32 {
+        let mut renderbuffer = 0;
+        gl::GenRenderbuffers(1, &mut renderbuffer);
+        Self::resize_stencil_buffer(renderbuffer, width, height, major_version);
+        renderbuffer
+    }
+
+    /// Reallocate the storage of a stencil renderbuffer with the given size.
+    unsafe fn resize_stencil_buffer(renderbuffer: u32, width: u32, height: u32, major_version: u8) {
+        let (internal_format, _) = Self::stencil_format(major_version);
+        gl::BindRenderbuffer(gl::RENDERBUFFER, renderbuffer);
+        gl::RenderbufferStorage(
+            gl::RENDERBUFFER,
+            internal_format,
+            width as i32,
+            height as i32,
+        );
+        gl::BindRenderbuffer(gl::RENDERBUFFER, 0);
+        gl_check_error!("stencil renderbuffer storage");
+    }
+
+    /// Attach a stencil renderbuffer to the bound framebuffer.
+    ///
+    /// If the framebuffer is incomplete with it, the renderbuffer is detached and false is
+    /// returned, and the masks don't clip the draws in that framebuffer.
+    unsafe fn attach_stencil_buffer(renderbuffer: u32, major_version: u8) -> bool {
+        let (_, attachment) = Self::stencil_format(major_version);
+        gl::FramebufferRenderbuffer(gl::FRAMEBUFFER, attachment, gl::RENDERBUFFER, renderbuffer);
+        let status = gl::CheckFramebufferStatus(gl::FRAMEBUFFER);
+        if status != gl::FRAMEBUFFER_COMPLETE {
+            log::warn!(
+                "framebuffer is incomplete with a stencil buffer ({:#x}), masks will not clip",
+                status
+            );
+            gl::FramebufferRenderbuffer(gl::FRAMEBUFFER, attachment, gl::RENDERBUFFER, 0);
+            return false;
+        }
+        gl_check_error!("attach stencil renderbuffer");
+        true
+    }
+
+    /// If the current surface has post effects, bind the render target that the scene will be
+    /// rendered to, recreating the render targets if the surface was resized.
+    unsafe fn begin_post_process(&mut self) {
+        let major_version = self.major_version;
+        let Some((_, context)) = &mut self.current_context else {
+            return;
+        };
+        let Some(post) = &mut context.post_process else {
+            return;
+        };
+        if post.size != context.size || post.targets.is_empty() {
+            log::trace!("create post process targets of size {:?}", context.size);
+            for (texture, framebuffer) in post.targets.drain(..) {
+                gl::DeleteFramebuffers(1, &framebuffer);
+                gl::DeleteTextures(1, &texture);
+            }
+            if post.stencil != 0 {
+                gl::DeleteRenderbuffers(1, &post.stencil);
+            }
+            let (width, height) = context.size;
+            // in the linear color space, store the colors in sRGB to not lose precision in the
+            // dark tones. They are converted back to linear when the targets are sampled.
+            let internal_format = if context.srgb {
+                gl::SRGB8_ALPHA8
+            } else {
+                gl::RGBA
+            };
+            for _ in 0..2 {
+                let mut texture = 0;
+                gl::GenTextures(1, &mut texture);
+                gl::BindTexture(gl::TEXTURE_2D, texture);
+                gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as i32);
+                gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as i32);
+                gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as i32);
+                gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
+                gl::TexImage2D(
+                    gl::TEXTURE_2D,
+                    0,
+                    internal_format as i32,
+                    width as i32,
+                    height as i32,
+                    0,
+                    gl::RGBA,
+                    gl::UNSIGNED_BYTE,
+                    ptr::null(),
+                );
+                let framebuffer = Self::create_framebuffer(texture);
+                post.targets.push((texture, framebuffer));
+            }
+            // the scene, and its masks, are rendered to the first target.
+            gl::BindFramebuffer(gl::FRAMEBUFFER, post.targets[0].1);
+            post.stencil = Self::create_stencil_buffer(width, height, major_version);
+            if !Self::attach_stencil_buffer(post.stencil, major_version) {
+                gl::DeleteRenderbuffers(1, &post.stencil);
+                post.stencil = 0;
+            }
+            post.size = context.size;
+        }
+        gl::BindFramebuffer(gl::FRAMEBUFFER, post.targets[0].1);
+    }
+
+    /// Apply the post effects of the current surface to the scene rendered in its first render
+    /// target, and draw the result to the surface.
+    unsafe fn apply_post_effects(&mut self) {
+        let Some(res) = &mut self.shared_resources else {
+            return;
+        };
+        let Some((_, context)) = &mut self.current_context else {
+            return;
+        };
+        let Some(post) = &mut context.post_process else {
+            return;
+        };
+        log::trace!("apply {} post effects", post.effects.len());
+
+        gl::Disable(gl::BLEND);
+        if self.major_version > 2 {
+            let vao = *post.vao.get_or_insert_with(|| {
+                let mut vao = 0;
+                gl::GenVertexArrays(1, &mut vao);
+                vao
+            });
+            gl::BindVertexArray(vao);
+        } else {
+            Self::disable_sprite_attributes(res.shader_program);
+        }
+        gl::BindBuffer(gl::ARRAY_BUFFER, res.post_quad_buffer);
+
+        let (width, height) = post.size;
+        let count = post.effects.len();
+        for (i, effect) in post.effects.iter().enumerate() {
+            let program = *res.post_programs.entry(effect.kind()).or_insert_with(|| {
+                Self::create_post_program(Some(effect))
+                    .or_else(|err| {
+                        log::error!("failed to create the program of {:?}: {}", effect, err);
+                        Self::create_post_program(None)
+                    })
+                    .unwrap()
+            });
+
+            let framebuffer = if i + 1 == count {
+                0
+            } else {
+                post.targets[(i + 1) % 2].1
+            };
+            gl::BindFramebuffer(gl::FRAMEBUFFER, framebuffer);
+            gl::UseProgram(program);
+
+            let position = gl::GetAttribLocation(program, cstr!("position")) as u32;
+            gl::VertexAttribPointer(position, 2, gl::FLOAT, gl::FALSE, 0, ptr::null());
+            gl::EnableVertexAttribArray(position);
+
+            gl::ActiveTexture(gl::TEXTURE0);
+            gl::BindTexture(gl::TEXTURE_2D, post.targets[i % 2].0);
+            if let Some(lut) = effect.texture() {
+                match res.get_gl_texture(lut) {
+                    Some(t) => {
+                        gl::ActiveTexture(gl::TEXTURE1);
+                        gl::BindTexture(gl::TEXTURE_2D, t.name);
+                    }
+                    None => log::error!("texture {} not found", lut),
+                }
+            }
+
+            gl::Uniform1i(get_uniform_location(program, "u_texture"), 0);
+            gl::Uniform1i(get_uniform_location(program, "u_lut"), 1);
+            gl::Uniform2f(
+                get_uniform_location(program, "u_resolution"),
+                width as f32,
+                height as f32,
+            );
+            gl::Uniform4fv(
+                get_uniform_location(program, "u_params"),
+                1,
+                effect.params().as_ptr(),
+            );
+            gl::DrawArrays(gl::TRIANGLE_STRIP, 0, 4);
+            gl::DisableVertexAttribArray(position);
+            gl_check_error!("apply post effect {:?}", effect);
+        }
+
+        gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+        if self.major_version > 2 {
+            gl::BindVertexArray(0);
+        } else {
+            Self::set_sprite_attributes(res.shader_program, res.vertex_buffer, 0);
+        }
+        gl::Enable(gl::BLEND);
+    }
+
+    /// Delete the OpenGL objects of a post-processing. Its context must be current.
+    unsafe fn delete_post_process(post: PostProcess) {
+        for (texture, framebuffer) in post.targets {
+            gl::DeleteFramebuffers(1, &framebuffer);
+            gl::DeleteTextures(1, &texture);
+        }
+        if post.stencil != 0 {
+            gl::DeleteRenderbuffers(1, &post.stencil);
+        }
+        if let Some(vao) = post.vao {
+            gl::DeleteVertexArrays(1, &vao);
+        }
+    }
+
+    /// Delete the framebuffers and the VAO of the current context, and the stencil renderbuffers of
+    /// the render textures that no other context has a framebuffer for.
+    unsafe fn delete_context_objects(&mut self) {
+        let Some((_, context)) = &mut self.current_context else {
+            return;
+        };
+        for (texture, framebuffer) in context.framebuffers.drain() {
+            gl::DeleteFramebuffers(1, &framebuffer);
+            // the current context is not in `contexts`.
+            let in_use = self
+                .contexts
+                .values()
+                .flatten()
+                .any(|x| x.framebuffers.contains_key(&texture));
+            let t = self
+                .shared_resources
+                .as_mut()
+                .and_then(|res| res.textures.iter_mut().find(|x| x.id == texture));
+            if let Some(t) = t.filter(|t| !in_use && t.stencil != 0) {
+                gl::DeleteRenderbuffers(1, &t.stencil);
+                t.stencil = 0;
+            }
+        }
+        if let Some(vao) = context.vao.take() {
+            gl::DeleteVertexArrays(1, &vao);
         }
+        gl_check_error!("delete context objects");
     }
 
     unsafe fn compile_shader(shader_type: u32, source: &str) -> Result<u32, String> {
@@ -680,6 +1912,18 @@ void main() {{
         let shader_program = gl::CreateProgram();
         gl::AttachShader(shader_program, vertex_shader);
         gl::AttachShader(shader_program, fragment_shader);
+        // All programs use the same attribute locations, so they can share the same VAO.
+        for (location, name) in [
+            cstr!("position"),
+            cstr!("uv"),
+            cstr!("aColor"),
+            cstr!("aTexture"),
+        ]
+        .iter()
+        .enumerate()
+        {
+            gl::BindAttribLocation(shader_program, location as u32, *name);
+        }
         gl::LinkProgram(shader_program);
 
         // Check for linking errors
@@ -711,63 +1955,22 @@ void main() {{
         result
     }
 
-    unsafe fn write_sprite<W: Write>(
+    unsafe fn write_quad<W: Write>(writer: &mut W, quad: &Quad, texture: u16) -> io::Result<()> {
+        for (&position, &uv) in quad.positions.iter().zip(&quad.uvs) {
+            Self::write_vertex(writer, &Vertex::new(position, uv, quad.color), texture)?;
+        }
+        Ok(())
+    }
+
+    unsafe fn write_vertex<W: Write>(
         writer: &mut W,
-        sprite: &SpriteInstance,
+        vertex: &Vertex,
         texture: u16,
     ) -> io::Result<()> {
-        let cos = sprite.angle.cos();
-        let sin = sprite.angle.sin();
-        let width = sprite.get_width() / 2.0;
-        let height = sprite.get_height() / 2.0;
-        let x = sprite.get_x();
-        let y = sprite.get_y();
-        let u = sprite.uv_rect[0];
-        let v = sprite.uv_rect[1];
-        let w = sprite.uv_rect[2];
-        let h = sprite.uv_rect[3];
-
-        // bottom left
-        writer.write_all(transmute_slice(&[
-            -cos * width + sin * height + x,
-            -sin * width - cos * height + y,
-            u,
-            v,
-        ]))?;
-        writer.write_all(&sprite.color)?;
-        writer.write_all(&texture.to_ne_bytes())?;
-        writer.write_all(&[0, 0])?; //complete the stride
-
-        // bottom right
-        writer.write_all(transmute_slice(&[
-            cos * width + sin * height + x,
-            sin * width - cos * height + y,
-            u + w,
-            v,
-        ]))?;
-        writer.write_all(&sprite.color)?;
-        writer.write_all(&texture.to_ne_bytes())?;
-        writer.write_all(&[0, 0])?; //complete the stride
-
-        // top left
-        writer.write_all(transmute_slice(&[
-            -cos * width - sin * height + x,
-            -sin * width + cos * height + y,
-            u,
-            v + h,
-        ]))?;
-        writer.write_all(&sprite.color)?;
-        writer.write_all(&texture.to_ne_bytes())?;
-        writer.write_all(&[0, 0])?; //complete the stride
-
-        // top right
-        writer.write_all(transmute_slice(&[
-            cos * width - sin * height + x,
-            sin * width + cos * height + y,
-            u + w,
-            v + h,
-        ]))?;
-        writer.write_all(&sprite.color)?;
+        let [x, y] = vertex.position;
+        let [u, v] = vertex.uv;
+        writer.write_all(transmute_slice(&[x, y, u, v]))?;
+        writer.write_all(&vertex.color)?;
         writer.write_all(&texture.to_ne_bytes())?;
         writer.write_all(&[0, 0])?; //complete the stride
         Ok(())
@@ -791,8 +1994,33 @@ void main() {{
             vao = Some(vertex_array);
         }
 
+        Self::set_sprite_attributes(shader_program, vertex_buffer, 0);
+
+        if major_version > 2 {
+            gl::BindVertexArray(0);
+        }
+
+        vao
+    }
+
+    /// Disable the vertex attributes of the sprite shader. Used when there is no VAO.
+    unsafe fn disable_sprite_attributes(shader_program: u32) {
+        for name in [
+            cstr!("position"),
+            cstr!("uv"),
+            cstr!("aColor"),
+            cstr!("aTexture"),
+        ] {
+            gl::DisableVertexAttribArray(gl::GetAttribLocation(shader_program, name) as u32);
+        }
+    }
+
+    /// Set and enable the vertex attributes of the sprite shader, in the bound VAO, if any, reading
+    /// the vertices of `vertex_buffer` from `first_vertex`.
+    unsafe fn set_sprite_attributes(shader_program: u32, vertex_buffer: u32, first_vertex: usize) {
         log::trace!("setting attributes");
         gl::BindBuffer(gl::ARRAY_BUFFER, vertex_buffer);
+        let offset = first_vertex * SPRITE_VERTEX_STRIDE;
 
         let position = gl::GetAttribLocation(shader_program, cstr!("position")) as u32;
         gl_check_error!("get position attribute location");
@@ -802,7 +2030,7 @@ void main() {{
             gl::FLOAT,
             gl::FALSE,
             SPRITE_VERTEX_STRIDE as i32,
-            ptr::null(),
+            offset as *const c_void,
         );
         gl_check_error!("position vertex attrib pointer");
         gl::EnableVertexAttribArray(position);
@@ -815,7 +2043,7 @@ void main() {{
             gl::FLOAT,
             gl::FALSE,
             SPRITE_VERTEX_STRIDE as i32,
-            (mem::size_of::<f32>() * 2) as *const c_void,
+            (offset + mem::size_of::<f32>() * 2) as *const c_void,
         );
         gl::EnableVertexAttribArray(uv);
 
@@ -826,7 +2054,7 @@ void main() {{
             gl::UNSIGNED_BYTE,
             gl::TRUE,
             SPRITE_VERTEX_STRIDE as i32,
-            (mem::size_of::<f32>() * 4) as *const c_void,
+            (offset + mem::size_of::<f32>() * 4) as *const c_void,
         );
         gl::EnableVertexAttribArray(a_color);
 
@@ -837,39 +2065,34 @@ void main() {{
             gl::UNSIGNED_SHORT,
             gl::FALSE,
             SPRITE_VERTEX_STRIDE as i32,
-            (mem::size_of::<f32>() * 5) as *const c_void,
+            (offset + mem::size_of::<f32>() * 5) as *const c_void,
         );
         gl::EnableVertexAttribArray(a_texture);
 
         gl_check_error!("set vertex attributes");
 
         gl::BindBuffer(gl::ARRAY_BUFFER, 0);
-        if major_version > 2 {
-            gl::BindVertexArray(0);
-        }
-
-        vao
     }
 
-    fn set_current_context(&mut self, window_id: WindowId) -> Result<(), glutin::error::Error> {
-        log::trace!("set current context to {:?}", window_id);
+    fn set_current_context(&mut self, surface_id: SurfaceId) -> Result<(), glutin::error::Error> {
+        log::trace!("set current context to {:?}", surface_id);
         let already_current = self
             .current_context
             .as_ref()
-            .map_or(false, |x| x.0 == window_id);
+            .is_some_and(|x| x.0 == surface_id);
         if !already_current {
             let previous_context = self.current_context.take();
             self.current_context = Some((
-                window_id,
+                surface_id,
                 self.contexts
-                    .get_mut(&window_id)
+                    .get_mut(&surface_id)
                     .unwrap()
                     .take()
                     .unwrap()
                     .make_current()?,
             ));
-            if let Some((window, context)) = previous_context {
-                *self.contexts.get_mut(&window).unwrap() = Some(context.make_not_current()?);
+            if let Some((surface, context)) = previous_context {
+                *self.contexts.get_mut(&surface).unwrap() = Some(context.make_not_current()?);
             }
         } else {
             // Make the current context current again to be sure that it is.
@@ -886,7 +2109,7 @@ fn parse_version_number(version: &CStr) -> Option<(u8, u8)> {
     let Some(start_pos) = bytes.iter().position(|x| x.is_ascii_digit()) else {
         return None;
     };
-    let Some(( dot_pos , _)) = bytes
+    let Some((dot_pos, _)) = bytes
         .iter()
         .enumerate()
         .skip(start_pos)
@@ -907,23 +2130,29 @@ fn parse_version_number(version: &CStr) -> Option<(u8, u8)> {
     Some((major.parse().ok()?, minor.parse().ok()?))
 }
 impl SpriteRender for GlSpriteRender {
-    fn add_window(&mut self, window: &Window) {
-        log::trace!("add window {:?}", window.id());
-        let window_id = window.id();
+    fn add_window(&mut self, window: &dyn RawWindow, width: u32, height: u32) -> SurfaceId {
+        let surface_id = SurfaceId(self.next_surface_id);
+        self.next_surface_id += 1;
+        log::trace!("add window {:?}", surface_id);
 
         // TODO: propagate errors
         let context = Context::new(
             window,
+            width,
+            height,
             self.vsync,
+            self.color_space,
             self.current_context.as_ref().map(|x| &x.1),
         )
         .unwrap();
 
         self.contexts
-            .insert(window_id, Some(context.make_not_current().unwrap()));
-        self.set_current_context(window_id).unwrap();
+            .insert(surface_id, Some(context.make_not_current().unwrap()));
+        self.set_current_context(surface_id).unwrap();
 
-        unsafe { Self::init_context() };
+        let context = &mut self.current_context.as_mut().unwrap().1;
+        context.srgb &= self.major_version > 2;
+        unsafe { Self::init_context(context.srgb) };
 
         self.current_context.as_mut().unwrap().1.vao = unsafe {
             let Some(res) = &self.shared_resources else {
@@ -931,13 +2160,22 @@ impl SpriteRender for GlSpriteRender {
             };
             Self::create_vao(res.shader_program, res.vertex_buffer, self.major_version)
         };
+
+        self.resize(surface_id, width, height);
+
+        surface_id
     }
 
-    fn remove_window(&mut self, window_id: WindowId) {
-        log::trace!("remove window {:?}", window_id);
-        let mut context = self.contexts.remove(&window_id).flatten();
+    fn remove_window(&mut self, surface_id: SurfaceId) {
+        log::trace!("remove window {:?}", surface_id);
+        if self.shared_resources.is_some() && self.contexts.contains_key(&surface_id) {
+            // delete the post-processing resources, which are not owned by the context.
+            self.set_post_effects(surface_id, Vec::new());
+            unsafe { self.delete_context_objects() };
+        }
+        let mut context = self.contexts.remove(&surface_id).flatten();
         if let Some((id, _)) = self.current_context.as_mut() {
-            if *id == window_id {
+            if *id == surface_id {
                 // TODO: propagate the error
                 context = Some(
                     self.current_context
@@ -958,7 +2196,8 @@ impl SpriteRender for GlSpriteRender {
             width,
             height,
             format,
-            filter,
+            mut sampler,
+            mipmaps,
             data,
         } = texture;
 
@@ -973,9 +2212,103 @@ impl SpriteRender for GlSpriteRender {
             return Ok(id);
         };
 
+        let Some(gl_format) = GlFormat::new(
+            format,
+            self.major_version,
+            &res.compressed_formats,
+            self.color_space,
+        ) else {
+            log::error!(
+                "texture format {:?} is not supported in OpenGL {}",
+                format,
+                self.major_version
+            );
+            return Err(TextureError::UnsupportedFormat);
+        };
+
+        let data_len = format
+            .data_len(width, height)
+            .ok_or(TextureError::InvalidLength)?;
+        let zeros;
+        let data_ptr = match data {
+            Some(data) => {
+                if data.len() != data_len {
+                    return Err(TextureError::InvalidLength);
+                }
+                data.as_ptr() as *const c_void
+            }
+            // compressed textures cannot be created without data.
+            None if format.is_compressed() => {
+                zeros = vec![0u8; data_len];
+                zeros.as_ptr() as *const c_void
+            }
+            None => std::ptr::null::<c_void>(),
+        };
+
+        let mut levels = match mipmaps {
+            Mipmaps::Levels(levels) => levels,
+            _ => &[],
+        };
+        let level_size = |level: usize| ((width >> level).max(1), (height >> level).max(1));
+        let full_chain = 31usize.saturating_sub(width.max(height).leading_zeros() as usize);
+        if levels.len() > full_chain {
+            log::error!("texture has more mipmap levels than its dimensions allow");
+            return Err(TextureError::InvalidLength);
+        }
+        for (i, level) in levels.iter().enumerate() {
+            let (w, h) = level_size(i + 1);
+            if Some(level.len()) != format.data_len(w, h) {
+                return Err(TextureError::InvalidLength);
+            }
+        }
+        let mut generate_mipmaps = mipmaps == Mipmaps::Generate
+            && if format.is_compressed() {
+                log::warn!("cannot generate mipmaps of compressed format {:?}", format);
+                false
+            } else if !gl::GenerateMipmap::is_loaded() {
+                log::warn!("glGenerateMipmap is not supported by this context");
+                false
+            } else {
+                true
+            };
+        if self.major_version <= 2 {
+            // there is no TEXTURE_MAX_LEVEL in OpenGL ES 2.0 to cut the mipmap chain short.
+            if !levels.is_empty() && levels.len() < full_chain {
+                log::warn!("the mipmap levels must go down to 1x1 in OpenGL ES 2.0, ignoring them");
+                levels = &[];
+            }
+            // OpenGL ES 2.0 does not support mipmaps and repeat on non power of two textures.
+            if !width.is_power_of_two() || !height.is_power_of_two() {
+                if generate_mipmaps || !levels.is_empty() {
+                    log::warn!("non power of two texture {width}x{height} cannot have mipmaps");
+                    generate_mipmaps = false;
+                    levels = &[];
+                }
+                if sampler.wrap_s != TextureWrap::ClampToEdge
+                    || sampler.wrap_t != TextureWrap::ClampToEdge
+                {
+                    log::warn!("non power of two texture {width}x{height} cannot repeat");
+                    sampler.wrap_s = TextureWrap::ClampToEdge;
+                    sampler.wrap_t = TextureWrap::ClampToEdge;
+                }
+            }
+        }
+        let has_mipmaps = generate_mipmaps || !levels.is_empty();
+
         unsafe {
-            let texture = match res.get_gl_texture(id) {
-                Some(x) => x.name,
+            let texture = match res.textures.iter_mut().find(|x| x.id == id) {
+                Some(x) => {
+                    if x.stencil != 0 && (x.width, x.height) != (width, height) {
+                        // the attachments of a framebuffer must have the same size.
+                        Self::resize_stencil_buffer(x.stencil, width, height, self.major_version);
+                    }
+                    x.width = width;
+                    x.height = height;
+                    x.format = format;
+                    x.gl_format = gl_format;
+                    x.generate_mipmaps = generate_mipmaps;
+                    x.name
+                }
                 None => {
                     let mut texture = 0;
                     gl::ActiveTexture(gl::TEXTURE0 + res.texture_unit_map.len() as u32);
@@ -985,49 +2318,86 @@ impl SpriteRender for GlSpriteRender {
                         name: texture,
                         width,
                         height,
+                        format,
+                        gl_format,
+                        render_texture: false,
+                        stencil: 0,
+                        generate_mipmaps,
                     });
                     texture
                 }
             };
 
             gl::BindTexture(gl::TEXTURE_2D, texture);
-            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as i32);
-            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as i32);
-            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as i32);
+            gl::TexParameteri(
+                gl::TEXTURE_2D,
+                gl::TEXTURE_WRAP_S,
+                wrap(sampler.wrap_s) as i32,
+            );
+            gl::TexParameteri(
+                gl::TEXTURE_2D,
+                gl::TEXTURE_WRAP_T,
+                wrap(sampler.wrap_t) as i32,
+            );
+            gl::TexParameteri(
+                gl::TEXTURE_2D,
+                gl::TEXTURE_MIN_FILTER,
+                min_filter(&sampler, has_mipmaps) as i32,
+            );
             gl::TexParameteri(
                 gl::TEXTURE_2D,
                 gl::TEXTURE_MAG_FILTER,
-                match filter {
-                    TextureFilter::Nearest => gl::NEAREST,
-                    TextureFilter::Linear => gl::LINEAR,
-                } as i32,
+                filter(sampler.mag_filter) as i32,
             );
+            if self.major_version > 2 {
+                // with user supplied levels, the mipmap chain may not go down to 1x1.
+                let max_level = if generate_mipmaps { 1000 } else { levels.len() };
+                gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAX_LEVEL, max_level as i32);
+            }
+            if res.max_anisotropy > 1.0 {
+                gl::TexParameterf(
+                    gl::TEXTURE_2D,
+                    gl::TEXTURE_MAX_ANISOTROPY,
+                    sampler.max_anisotropy.clamp(1.0, res.max_anisotropy),
+                );
+            }
 
-            let data_ptr = match data {
-                Some(data) => {
-                    if data.len() as u32 != width * height * 4 {
-                        return Err(TextureError::InvalidLength);
-                    }
-                    data.as_ptr() as *const c_void
+            let upload = |level: usize, data_ptr: *const c_void| {
+                let (width, height) = level_size(level);
+                if format.is_compressed() {
+                    gl::CompressedTexImage2D(
+                        gl::TEXTURE_2D,
+                        level as i32,
+                        gl_format.format,
+                        width as i32,
+                        height as i32,
+                        0,
+                        // the levels are not larger than the base level, whose length was checked.
+                        format.data_len(width, height).unwrap_or(data_len) as i32,
+                        data_ptr,
+                    );
+                } else {
+                    gl::TexImage2D(
+                        gl::TEXTURE_2D,
+                        level as i32,
+                        gl_format.internal_format,
+                        width as i32,
+                        height as i32,
+                        0,
+                        gl_format.format,
+                        gl_format.type_,
+                        data_ptr,
+                    );
                 }
-                None => std::ptr::null::<c_void>(),
-            };
-
-            let (internalformat, format, type_) = match format {
-                crate::TextureFormat::Rgba8888 => (gl::RGBA as i32, gl::RGBA, gl::UNSIGNED_BYTE),
             };
-
-            gl::TexImage2D(
-                gl::TEXTURE_2D,
-                0,
-                internalformat,
-                width as i32,
-                height as i32,
-                0,
-                format,
-                type_,
-                data_ptr,
-            );
+            upload(0, data_ptr);
+            for (i, level) in levels.iter().enumerate() {
+                upload(i + 1, level.as_ptr() as *const c_void);
+            }
+            if generate_mipmaps {
+                gl::GenerateMipmap(gl::TEXTURE_2D);
+            }
+            gl_check_error!("new texture");
             Ok(id)
         }
     }
@@ -1044,18 +2414,31 @@ impl SpriteRender for GlSpriteRender {
             return Ok(());
         };
 
-        let t = res.get_gl_texture(texture).unwrap();
+        let Some(t) = res.get_gl_texture(texture) else {
+            log::error!("texture {} not found", texture);
+            return Err(TextureError::NotFound);
+        };
 
         let rect = sub_rect.unwrap_or([0, 0, t.width, t.height]);
-        let expected_len = (rect[2] * rect[3] * 4) as usize;
+        if rect[0] as u64 + rect[2] as u64 > t.width as u64
+            || rect[1] as u64 + rect[3] as u64 > t.height as u64
+        {
+            log::error!("sub rect {:?} is out of the texture bounds", rect);
+            return Err(TextureError::OutOfBounds);
+        }
+        let expected_len = t
+            .format
+            .data_len(rect[2], rect[3])
+            .ok_or(TextureError::InvalidLength)?;
 
         let data_ptr = match data {
             Some(data) => {
                 if data.len() != expected_len {
                     log::error!(
-                        "expected data length was {}x{}x4={}, but receive a data of length {}",
+                        "expected data length of {}x{} {:?} was {}, but receive a data of length {}",
                         rect[2],
                         rect[3],
+                        t.format,
                         expected_len,
                         data.len()
                     );
@@ -1070,44 +2453,409 @@ impl SpriteRender for GlSpriteRender {
 
         unsafe {
             gl::BindTexture(gl::TEXTURE_2D, texture);
-            gl::TexSubImage2D(
+            if t.format.is_compressed() {
+                // the sub_rect must be aligned to the blocks of the format.
+                if !data_ptr.is_null() {
+                    gl::CompressedTexSubImage2D(
+                        gl::TEXTURE_2D,
+                        0,
+                        rect[0] as i32,
+                        rect[1] as i32,
+                        rect[2] as i32,
+                        rect[3] as i32,
+                        t.gl_format.format,
+                        expected_len as i32,
+                        data_ptr,
+                    );
+                }
+            } else {
+                gl::TexSubImage2D(
+                    gl::TEXTURE_2D,
+                    0,
+                    rect[0] as i32,
+                    rect[1] as i32,
+                    rect[2] as i32,
+                    rect[3] as i32,
+                    t.gl_format.format,
+                    t.gl_format.type_,
+                    data_ptr,
+                );
+            }
+            if t.generate_mipmaps {
+                gl::GenerateMipmap(gl::TEXTURE_2D);
+            }
+            gl_check_error!("update texture");
+        }
+
+        Ok(())
+    }
+
+    fn read_texture(
+        &mut self,
+        texture: TextureId,
+        sub_rect: Option<[u32; 4]>,
+    ) -> Result<Vec<u8>, TextureError> {
+        log::trace!("read texture {texture}");
+        let (Some(res), Some(_)) = (&self.shared_resources, &self.current_context) else {
+            log::warn!("OpenGL context don't exist.");
+            return Err(TextureError::RendererContextDontExist);
+        };
+        let Some(t) = res.get_gl_texture(texture) else {
+            log::error!("texture {} not found", texture);
+            return Err(TextureError::NotFound);
+        };
+        if t.format.is_compressed() {
+            return Err(TextureError::UnsupportedFormat);
+        }
+
+        let [x, y, width, height] = sub_rect.unwrap_or([0, 0, t.width, t.height]);
+        if x as u64 + width as u64 > t.width as u64 || y as u64 + height as u64 > t.height as u64 {
+            return Err(TextureError::OutOfBounds);
+        }
+
+        let mut data = vec![0u8; width as usize * height as usize * 4];
+        unsafe {
+            // OpenGL ES has no glGetTexImage, so the texture is read through a framebuffer.
+            let mut framebuffer = 0;
+            gl::GenFramebuffers(1, &mut framebuffer);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, framebuffer);
+            gl::FramebufferTexture2D(
+                gl::FRAMEBUFFER,
+                gl::COLOR_ATTACHMENT0,
                 gl::TEXTURE_2D,
+                t.name,
                 0,
-                rect[0] as i32,
-                rect[1] as i32,
-                rect[2] as i32,
-                rect[3] as i32,
-                gl::RGBA,
-                gl::UNSIGNED_BYTE,
-                data_ptr,
             );
+            let status = gl::CheckFramebufferStatus(gl::FRAMEBUFFER);
+            if status == gl::FRAMEBUFFER_COMPLETE {
+                gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+                gl::ReadPixels(
+                    x as i32,
+                    y as i32,
+                    width as i32,
+                    height as i32,
+                    gl::RGBA,
+                    gl::UNSIGNED_BYTE,
+                    data.as_mut_ptr() as *mut c_void,
+                );
+            }
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+            gl::DeleteFramebuffers(1, &framebuffer);
+            gl_check_error!("read texture");
+            if status != gl::FRAMEBUFFER_COMPLETE {
+                log::error!("texture format {:?} cannot be read", t.format);
+                return Err(TextureError::UnsupportedFormat);
+            }
         }
 
-        Ok(())
+        material::swizzle_pixels(t.gl_format.swizzle, &mut data);
+        Ok(data)
+    }
+
+    fn set_fallback_texture(&mut self, fallback: FallbackTexture) {
+        self.fallback = fallback;
+        let Some(res) = &mut self.shared_resources else {
+            return;
+        };
+        if let Some(i) = res.textures.iter().position(|x| x.id == FALLBACK_TEXTURE) {
+            let texture = res.textures.remove(i);
+            unsafe { gl::DeleteTextures(1, &texture.name) };
+        }
+        fallback.create(self);
     }
 
-    fn render<'a>(&'a mut self, window_id: WindowId) -> Box<dyn Renderer + 'a> {
-        log::trace!("render {:?}", window_id);
+    fn render<'a>(&'a mut self, surface_id: SurfaceId) -> Box<dyn Renderer + 'a> {
+        log::trace!("render {:?}", surface_id);
         if self.shared_resources.is_none() {
             log::warn!("OpenGL context don't exist.");
             return Box::new(crate::NoopRenderer);
         }
-        self.set_current_context(window_id).unwrap();
-        Box::new(GlRenderer { render: self })
+        self.set_current_context(surface_id).unwrap();
+        #[cfg(feature = "hot-reload")]
+        self.reload_shaders();
+        unsafe { self.begin_post_process() };
+        Box::new(GlRenderer {
+            render: self,
+            target: None,
+            clip: ClipStack::default(),
+        })
+    }
+
+    fn supports_texture_format(&self, format: TextureFormat) -> bool {
+        let Some(res) = &self.shared_resources else {
+            return false;
+        };
+        GlFormat::new(
+            format,
+            self.major_version,
+            &res.compressed_formats,
+            self.color_space,
+        )
+        .is_some()
+    }
+
+    fn color_space(&self) -> ColorSpace {
+        self.color_space
+    }
+
+    fn new_material(&mut self, material: Material) -> Result<MaterialId, ShaderError> {
+        let Some(res) = &mut self.shared_resources else {
+            return Err(ShaderError::RendererContextDontExist);
+        };
+
+        // at least one texture unit must be left for the sprite textures.
+        let texture_count = material.texture_count() as i32;
+        if texture_count >= res.max_texture_units {
+            return Err(ShaderError::TooManyTextures);
+        }
+        let mut texture_units = res.max_texture_units - texture_count;
+        if material.normal_maps {
+            // each sprite texture also uses a unit for its normal map.
+            texture_units /= 2;
+            if texture_units == 0 {
+                return Err(ShaderError::TooManyTextures);
+            }
+        }
+
+        let program = unsafe {
+            Self::create_sprite_program(
+                &material.fragment_source,
+                texture_units,
+                material.normal_maps,
+                self.color_space,
+            )?
+        };
+
+        let id = MaterialId(res.next_material_id);
+        res.next_material_id += 1;
+        log::trace!("new material {}", id);
+        #[cfg(feature = "hot-reload")]
+        if let Some(path) = material.path {
+            let modified = hot_reload::modified_time(&path);
+            self.shader_watcher.watch(id, path, modified);
+        }
+        res.materials.insert(
+            id,
+            GlMaterial {
+                program,
+                texture_units,
+                normal_maps: material.normal_maps,
+                uniforms: material.uniforms,
+                blend: material.blend,
+            },
+        );
+        Ok(id)
+    }
+
+    fn set_material_uniform(&mut self, material: MaterialId, name: &str, value: Uniform) {
+        let Some(res) = &mut self.shared_resources else {
+            log::error!("OpenGL context don't exist");
+            return;
+        };
+        let Some(material) = res.materials.get_mut(&material) else {
+            log::error!("material {} not found", material);
+            return;
+        };
+        if !material::can_set_uniform(&material.uniforms, name, &value) {
+            log::error!(
+                "texture uniform '{}' was not given on material creation",
+                name
+            );
+            return;
+        }
+        material::set_uniform(&mut material.uniforms, name, value);
+    }
+
+    fn set_normal_map(&mut self, texture: TextureId, normal_map: Option<TextureId>) {
+        let Some(res) = &mut self.shared_resources else {
+            log::error!("OpenGL context don't exist");
+            return;
+        };
+        match normal_map {
+            Some(normal_map) => res.normal_maps.insert(texture, normal_map),
+            None => res.normal_maps.remove(&texture),
+        };
+    }
+
+    fn new_render_texture(&mut self, width: u32, height: u32) -> Result<TextureId, TextureError> {
+        let id = self.new_texture(Texture::new(width, height))?;
+        if let Some(res) = &mut self.shared_resources {
+            if let Some(texture) = res.textures.iter_mut().find(|x| x.id == id) {
+                texture.render_texture = true;
+            }
+        }
+        Ok(id)
+    }
+
+    fn render_to_texture<'a>(&'a mut self, texture: TextureId) -> Box<dyn Renderer + 'a> {
+        log::trace!("render to texture {}", texture);
+        let Some(res) = &self.shared_resources else {
+            log::warn!("OpenGL context don't exist.");
+            return Box::new(crate::NoopRenderer);
+        };
+        let Some(t) = res.get_gl_texture(texture).filter(|x| x.render_texture) else {
+            log::error!("texture {} is not a render texture", texture);
+            return Box::new(crate::NoopRenderer);
+        };
+        let Some((_, context)) = &mut self.current_context else {
+            log::warn!("there is no current OpenGL context.");
+            return Box::new(crate::NoopRenderer);
+        };
+
+        unsafe {
+            match context.framebuffers.get(&texture) {
+                Some(&framebuffer) => gl::BindFramebuffer(gl::FRAMEBUFFER, framebuffer),
+                None => {
+                    log::trace!("create framebuffer for texture {}", texture);
+                    let framebuffer = Self::create_framebuffer(t.name);
+                    context.framebuffers.insert(texture, framebuffer);
+                    // the renderbuffer is shared between contexts, like the texture.
+                    let stencil = match t.stencil {
+                        0 => Self::create_stencil_buffer(t.width, t.height, self.major_version),
+                        stencil => stencil,
+                    };
+                    if Self::attach_stencil_buffer(stencil, self.major_version) {
+                        let res = self.shared_resources.as_mut().unwrap();
+                        let t = res.textures.iter_mut().find(|x| x.id == texture).unwrap();
+                        t.stencil = stencil;
+                    } else if t.stencil == 0 {
+                        gl::DeleteRenderbuffers(1, &stencil);
+                    }
+                }
+            }
+            gl::Viewport(0, 0, t.width as i32, t.height as i32);
+        }
+
+        Box::new(GlRenderer {
+            render: self,
+            target: Some(texture),
+            clip: ClipStack::default(),
+        })
+    }
+
+    fn new_static_batch(&mut self, sprites: &[SpriteInstance]) -> Result<BatchId, BatchError> {
+        let Some(res) = &mut self.shared_resources else {
+            return Err(BatchError::RendererContextDontExist);
+        };
+        let mut buffer = 0;
+        unsafe {
+            gl::GenBuffers(1, &mut buffer);
+            gl_check_error!("gen static batch buffer");
+        }
+        let groups = res.write_static_batch(buffer, sprites);
+        let id = BatchId(res.next_batch_id);
+        res.next_batch_id += 1;
+        log::trace!("new static batch {} with {} groups", id, groups.len());
+        res.static_batches.insert(
+            id,
+            GlStaticBatch {
+                buffer,
+                groups,
+                sprites: sprites.to_vec(),
+            },
+        );
+        Ok(id)
+    }
+
+    fn update_static_batch(
+        &mut self,
+        batch: BatchId,
+        sprites: &[SpriteInstance],
+    ) -> Result<(), BatchError> {
+        let Some(res) = &mut self.shared_resources else {
+            return Err(BatchError::RendererContextDontExist);
+        };
+        let Some(buffer) = res.static_batches.get(&batch).map(|x| x.buffer) else {
+            return Err(BatchError::NotFound);
+        };
+        log::trace!("update static batch {}", batch);
+        let groups = res.write_static_batch(buffer, sprites);
+        let static_batch = res.static_batches.get_mut(&batch).unwrap();
+        static_batch.groups = groups;
+        static_batch.sprites = sprites.to_vec();
+        Ok(())
+    }
+
+    fn update_static_batch_range(
+        &mut self,
+        batch: BatchId,
+        start: usize,
+        sprites: &[SpriteInstance],
+    ) -> Result<(), BatchError> {
+        let Some(res) = &mut self.shared_resources else {
+            return Err(BatchError::RendererContextDontExist);
+        };
+        let Some(static_batch) = res.static_batches.get(&batch) else {
+            return Err(BatchError::NotFound);
+        };
+        let end = start.saturating_add(sprites.len());
+        if end > static_batch.sprites.len() {
+            return Err(BatchError::OutOfBounds);
+        }
+        log::trace!("update static batch {} from {} to {}", batch, start, end);
+
+        let quads = sprites.iter().map(Quad::from).collect::<Vec<_>>();
+        let indices = static_batch::indices_in_place(&static_batch.groups, start, &quads, |x| {
+            res.get_gl_texture(x).map_or(0, |x| x.gl_format.swizzle)
+        });
+        let buffer = static_batch.buffer;
+        let static_batch = res.static_batches.get_mut(&batch).unwrap();
+        static_batch.sprites[start..end].clone_from_slice(sprites);
+
+        let Some(indices) = indices else {
+            log::trace!("static batch {} groups changed", batch);
+            let sprites = mem::take(&mut static_batch.sprites);
+            let groups = res.write_static_batch(buffer, &sprites);
+            let static_batch = res.static_batches.get_mut(&batch).unwrap();
+            static_batch.groups = groups;
+            static_batch.sprites = sprites;
+            return Ok(());
+        };
+        let mut data = Vec::with_capacity(quads.len() * SPRITE_VERTEX_STRIDE * 4);
+        for (quad, texture) in quads.iter().zip(indices) {
+            unsafe { Self::write_quad(&mut data, quad, texture).unwrap() };
+        }
+        unsafe {
+            gl::BindBuffer(gl::ARRAY_BUFFER, buffer);
+            gl::BufferSubData(
+                gl::ARRAY_BUFFER,
+                (start * SPRITE_VERTEX_STRIDE * 4) as GLintptr,
+                data.len() as GLsizeiptr,
+                data.as_ptr() as *const c_void,
+            );
+            gl_check_error!("static batch buffer subdata");
+            gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+        }
+        Ok(())
+    }
+
+    fn remove_static_batch(&mut self, batch: BatchId) {
+        let Some(res) = &mut self.shared_resources else {
+            log::warn!("OpenGL context don't exist.");
+            return;
+        };
+        match res.static_batches.remove(&batch) {
+            Some(static_batch) => unsafe {
+                gl::DeleteBuffers(1, &static_batch.buffer);
+                gl_check_error!("delete static batch buffer");
+            },
+            None => log::warn!("static batch {} not found", batch),
+        }
     }
 
-    fn resize(&mut self, window_id: WindowId, width: u32, height: u32) {
-        log::trace!("resize {:?}", window_id);
+    fn resize(&mut self, surface_id: SurfaceId, width: u32, height: u32) {
+        log::trace!("resize {:?}", surface_id);
         if self.shared_resources.is_none() {
             log::warn!("OpenGL context don't exist.");
             return;
         };
-        self.set_current_context(window_id).unwrap();
+        self.set_current_context(surface_id).unwrap();
 
         if width == 0 || height == 0 {
             log::debug!("width or height are 0: {}x{}", width, height);
         } else {
-            let ctx = &self.current_context.as_ref().unwrap().1;
+            let ctx = &mut self.current_context.as_mut().unwrap().1;
+            ctx.size = (width, height);
 
             ctx.surface.resize(
                 &ctx.context,
@@ -1121,8 +2869,38 @@ impl SpriteRender for GlSpriteRender {
         }
     }
 
-    fn resume(&mut self, window: &Window) {
-        self.create_context_and_resources(window).unwrap();
+    fn set_post_effects(&mut self, surface_id: SurfaceId, effects: Vec<PostEffect>) {
+        log::trace!("set {} post effects to {:?}", effects.len(), surface_id);
+        if self.shared_resources.is_none() {
+            log::warn!("OpenGL context don't exist.");
+            return;
+        };
+        self.set_current_context(surface_id).unwrap();
+
+        let context = &mut self.current_context.as_mut().unwrap().1;
+        if effects.is_empty() {
+            if let Some(post) = context.post_process.take() {
+                unsafe { Self::delete_post_process(post) };
+            }
+            return;
+        }
+        match &mut context.post_process {
+            Some(post) => post.effects = effects,
+            None => {
+                context.post_process = Some(PostProcess {
+                    effects,
+                    targets: Vec::new(),
+                    stencil: 0,
+                    size: (0, 0),
+                    vao: None,
+                })
+            }
+        }
+    }
+
+    fn resume(&mut self, window: &dyn RawWindow, width: u32, height: u32) {
+        self.create_context_and_resources(window, width, height)
+            .unwrap();
     }
 
     fn suspend(&mut self) {
@@ -1130,5 +2908,7 @@ impl SpriteRender for GlSpriteRender {
         self.current_context.take();
         self.major_version = 0;
         self.shared_resources = None;
+        #[cfg(feature = "hot-reload")]
+        self.shader_watcher.reset();
     }
 }
@@ -4,19 +4,48 @@ use std::{
     mem, str,
 };
 
+use raw_window_handle::RawWindowHandle;
 use wasm_bindgen::JsCast;
 use web_sys::{
-    console, WebGlBuffer, WebGlProgram, WebGlRenderingContext, WebGlShader, WebGlTexture,
-};
-use winit::{
-    platform::web::WindowExtWebSys,
-    window::{Window, WindowId},
+    console, WebGlBuffer, WebGlFramebuffer, WebGlProgram, WebGlRenderbuffer, WebGlRenderingContext,
+    WebGlShader, WebGlTexture,
 };
 
-use crate::{common::*, Renderer, SpriteRender, Texture, TextureError, TextureFilter, TextureId};
+use crate::{
+    clip::{ClipRect, ClipStack},
+    common::*,
+    fallback::{FeedbackLoops, MissingTextures, FALLBACK_TEXTURE},
+    material::{
+        self, BlendMode, Material, NormalMapFlags, Uniform, SRGB_DECODE, SWIZZLE_ALPHA,
+        SWIZZLE_NONE,
+    },
+    mesh::{self, Mesh, Vertex},
+    post_process::{self, PostEffect},
+    shape::{self, Quad, WHITE_TEXTURE},
+    static_batch::{self, BatchGroup},
+    BatchError, BatchId, ColorSpace, FallbackTexture, MaterialId, Mipmaps, RawWindow, Renderer,
+    Sampler, ShaderError, Shapes, SpriteRender, SurfaceId, Texture, TextureError, TextureFilter,
+    TextureFormat, TextureId, TextureWrap, Transform,
+};
 
 const SPRITE_VERTEX_STRIDE: usize = mem::size_of::<f32>() * 6;
 
+// From the EXT_texture_filter_anisotropic extension.
+const TEXTURE_MAX_ANISOTROPY_EXT: u32 = 0x84FE;
+const MAX_TEXTURE_MAX_ANISOTROPY_EXT: u32 = 0x84FF;
+// From the OES_texture_half_float extension.
+const HALF_FLOAT_OES: u32 = 0x8D61;
+// From the WEBGL_compressed_texture_s3tc extension.
+const COMPRESSED_RGBA_S3TC_DXT1_EXT: u32 = 0x83F1;
+const COMPRESSED_RGBA_S3TC_DXT3_EXT: u32 = 0x83F2;
+const COMPRESSED_RGBA_S3TC_DXT5_EXT: u32 = 0x83F3;
+// From the WEBGL_compressed_texture_etc extension.
+const COMPRESSED_RGB8_ETC2: u32 = 0x9274;
+const COMPRESSED_RGBA8_ETC2_EAC: u32 = 0x9278;
+// From the WEBGL_compressed_texture_astc extension.
+const COMPRESSED_RGBA_ASTC_4X4_KHR: u32 = 0x93B0;
+
+/// The vertex shader of the sprites, without the color space prelude.
 const VERTEX_SHADER_SOURCE: &str = r#"
 attribute vec2 position;
 attribute vec2 uv;
@@ -27,37 +56,21 @@ uniform mat3 view;
 
 varying vec4 color;
 varying vec2 TexCoord;
+varying vec2 WorldPos;
 varying float textureIndex;
+varying float textureSwizzle;
 
 void main() {
     gl_Position = vec4((vec3(position, 1.0) * view).xy, 0.0, 1.0);
     gl_Position.y *= -1.0;
     color = aColor;
+#ifdef LINEAR_COLOR
+    color.rgb = srgbToLinear(aColor.rgb);
+#endif
     TexCoord = uv;
-    textureIndex = aTexture;
-}
-"#;
-
-const FRAGMENT_SHADER_SOURCE: &str = r#"
-precision mediump float;
-
-uniform sampler2D text[MAX_TEXTURE_IMAGE_UNITS];
-
-varying vec4 color;
-varying vec2 TexCoord;
-varying float textureIndex;
-
-void main() {
-    int t = int(textureIndex);
-    vec4 textureColor;
-    for (int i = 0; i < MAX_TEXTURE_IMAGE_UNITS; i++ ) {
-        if (i == t) textureColor = texture2D(text[i], TexCoord);
-    }
-    
-    if (textureColor.a == 0.0 || color.a == 0.0) {
-        discard;
-    }
-    gl_FragColor = textureColor*color;
+    WorldPos = position;
+    textureIndex = mod(aTexture, 256.0);
+    textureSwizzle = floor(aTexture / 256.0);
 }
 "#;
 
@@ -101,129 +114,651 @@ macro_rules! gl_check_error {
     )
 }
 
+/// The vertices drawn by [WebGLRenderer::draw_batch].
+#[derive(Clone, Copy)]
+enum Vertices<'a> {
+    /// Vertices uploaded to the shared vertex buffer.
+    Data(&'a [u8]),
+    /// Vertices already in a buffer, starting from `first_vertex`.
+    Buffer {
+        buffer: &'a WebGlBuffer,
+        first_vertex: usize,
+    },
+}
+
 pub struct WebGLRenderer<'a> {
     render: &'a mut WebGLSpriteRender,
+    /// The render texture being rendered to, or None if rendering to the canvas.
+    target: Option<TextureId>,
+    clip: ClipStack,
 }
-impl<'a> Renderer for WebGLRenderer<'a> {
-    fn clear_screen(&mut self, color: &[f32; 4]) -> &mut dyn Renderer {
-        self.render
-            .context
-            .clear_color(color[0], color[1], color[2], color[3]);
-        self.render
-            .context
-            .clear(WebGlRenderingContext::COLOR_BUFFER_BIT);
-        self
-    }
-
-    fn draw_sprites(
-        &mut self,
-        camera: &mut Camera,
-        sprites: &[SpriteInstance],
-    ) -> &mut dyn Renderer {
-        if sprites.is_empty() {
-            return self;
+impl<'a> WebGLRenderer<'a> {
+    /// Draw `len` quads, split in draw calls by material and by the number of texture units.
+    fn draw_quads(&mut self, camera: &mut Camera, len: usize, quads: impl Iterator<Item = Quad>) {
+        if len == 0 {
+            return;
         }
 
-        if sprites.len() > self.render.buffer_size as usize {
-            self.render.reallocate_instance_buffer(sprites.len());
+        if len > self.render.buffer_size as usize {
+            self.render.reallocate_instance_buffer(len);
         }
 
-        self.render.texture_unit_map.clear();
-        unsafe {
-            let mut data: Vec<u8> = Vec::with_capacity(sprites.len() * SPRITE_VERTEX_STRIDE * 4);
-            for sprite in sprites {
-                let texture_unit = if let Some(t) =
-                    self.render.texture_unit_map.get(&sprite.texture)
-                {
-                    *t
-                } else {
-                    if self.render.texture_unit_map.len() == self.render.max_texture_units as usize
-                    {
-                        unimplemented!("Splitting rendering in multiples draw calls when the number of textures is greater than MAX_TEXTURE_IMAGE_UNITS is unimplemented.");
-                    }
-
-                    let Some(texture) = self.render.get_gl_texture(sprite.texture) else {
-                        log::debug!("{:?}", self.render.textures);
-                        log::error!("texture {:} not found", sprite.texture.0);
-                        continue;
+        let view = self.view(camera);
+
+        let mut data: Vec<u8> = Vec::with_capacity(len * SPRITE_VERTEX_STRIDE * 4);
+        let mut quads = quads.peekable();
+        while let Some(first) = quads.peek() {
+            let render = &mut *self.render;
+            // split rendering by material
+            let material = first.material;
+            let texture_units = render.program(material).1;
+            let normal_maps = render.uses_normal_maps(material);
+            render.texture_unit_map.clear();
+            render.clear_normal_maps(texture_units);
+            data.clear();
+            let mut count = 0;
+            unsafe {
+                while let Some(quad) = quads.next_if(|x| x.material == material) {
+                    let id = render.feedback_loops.texture(quad.texture, self.target);
+                    let texture_unit = if let Some(t) = render.texture_unit_map.get(&id) {
+                        *t
+                    } else if let Some(t) = render.missing_texture_unit(id) {
+                        t
+                    } else {
+                        let texture = match render.get_gl_texture(id) {
+                            Some(x) => x,
+                            None => {
+                                render.missing_textures.warn(id);
+                                match render.get_gl_texture(FALLBACK_TEXTURE) {
+                                    Some(x) => x,
+                                    None => continue,
+                                }
+                            }
+                        };
+
+                        let unit = render.texture_unit_map.len() as u32;
+                        log::trace!("active texture {}, and bind to {}", unit, id);
+
+                        render
+                            .context
+                            .active_texture(WebGlRenderingContext::TEXTURE0 + unit);
+                        render
+                            .context
+                            .bind_texture(WebGlRenderingContext::TEXTURE_2D, Some(&texture.handle));
+                        if normal_maps {
+                            render.bind_normal_map(texture.id, unit, texture_units);
+                        }
+
+                        let index = unit | (texture.gl_format.swizzle as u32) << 8;
+                        render.texture_unit_map.insert(texture.id, index);
+
+                        index
                     };
+                    WebGLSpriteRender::write_quad(&mut data, &quad, texture_unit as u16).unwrap();
 
-                    let unit = self.render.texture_unit_map.len() as u32;
-                    log::trace!("active texture {}, and bind to {}", unit, sprite.texture);
+                    count += 1;
 
-                    self.render
-                        .context
-                        .active_texture(WebGlRenderingContext::TEXTURE0 + unit);
-                    self.render
-                        .context
-                        .bind_texture(WebGlRenderingContext::TEXTURE_2D, Some(&texture.handle));
+                    // split rendering in multiple draw calls if necessary
+                    if render.texture_unit_map.len() == texture_units as usize {
+                        break;
+                    }
+                }
+            }
 
-                    self.render.texture_unit_map.insert(sprite.texture, unit);
+            let index_buffer = render.indice_buffer.clone();
+            self.draw_batch(
+                &view,
+                material,
+                Vertices::Data(&data),
+                &index_buffer,
+                count * 6,
+            );
+        }
+    }
 
-                    unit
-                };
-                WebGLSpriteRender::write_sprite(&mut data, sprite, texture_unit as u16).unwrap();
+    /// Draw the groups of a static batch with the given view matrix.
+    fn draw_static(&mut self, view: &[f32; 9], batch: BatchId) {
+        let Some(static_batch) = self.render.static_batches.get(&batch) else {
+            log::error!("static batch {} not found", batch);
+            return;
+        };
+        let buffer = static_batch.buffer.clone();
+        let groups = static_batch.groups.clone();
+        for group in groups {
+            let render = &mut *self.render;
+            if group.len > render.buffer_size as usize {
+                // the index buffer must have indices for all sprites in the group.
+                render.reallocate_instance_buffer(group.len);
+            }
+            let texture_units = render.program(group.material).1;
+            let normal_maps = render.uses_normal_maps(group.material);
+            render.clear_normal_maps(texture_units);
+            for (unit, &id) in group.textures.iter().enumerate() {
+                let id = render.feedback_loops.texture(id, self.target);
+                let texture = render.get_gl_texture(id).or_else(|| {
+                    render.missing_textures.warn(id);
+                    render.get_gl_texture(FALLBACK_TEXTURE)
+                });
+                render
+                    .context
+                    .active_texture(WebGlRenderingContext::TEXTURE0 + unit as u32);
+                render.context.bind_texture(
+                    WebGlRenderingContext::TEXTURE_2D,
+                    texture.as_ref().map(|x| &x.handle),
+                );
+                if normal_maps {
+                    render.bind_normal_map(id, unit as u32, texture_units);
+                }
             }
+            let vertices = Vertices::Buffer {
+                buffer: &buffer,
+                first_vertex: group.start * 4,
+            };
+            let (index_buffer, count) = (render.indice_buffer.clone(), group.len as i32 * 6);
+            self.draw_batch(view, group.material, vertices, &index_buffer, count);
+        }
+    }
 
-            self.render.context.bind_buffer(
-                WebGlRenderingContext::ARRAY_BUFFER,
-                Some(&self.render.buffer),
-            );
-            self.render.context.buffer_sub_data_with_i32_and_u8_array(
-                WebGlRenderingContext::ARRAY_BUFFER,
-                0,
-                &data,
-            );
+    /// The view matrix of the camera, flipped when rendering to a texture.
+    fn view(&self, camera: &mut Camera) -> [f32; 9] {
+        let mut view = *camera.view();
+        if self.target.is_some() {
+            // The vertex shader flips the y axis to match the window coordinates, but the rows of a
+            // texture go upwards, so flip it back.
+            view[3..6].iter_mut().for_each(|x| *x = -*x);
         }
+        view
+    }
 
-        // self.render.context.enable_vertex_attrib_array(0);
-        // self.render.context.vertex_attrib_pointer_with_i32(0, 2, WebGlRenderingContext::FLOAT, false, SPRITE_VERTEX_STRIDE as i32, 0);
-        // self.render.context.enable_vertex_attrib_array(1);
-        // self.render.context.vertex_attrib_pointer_with_i32(1, 3, WebGlRenderingContext::FLOAT, false, SPRITE_VERTEX_STRIDE as i32, mem::size_of::<f32>() as i32 * 2);
+    /// Draw `index_count` u16 indices of `index_buffer` with the program of `material`. The
+    /// textures must be already bound.
+    fn draw_batch(
+        &mut self,
+        view: &[f32; 9],
+        material: MaterialId,
+        vertices: Vertices,
+        index_buffer: &WebGlBuffer,
+        index_count: i32,
+    ) {
+        let render = &mut *self.render;
+        match vertices {
+            Vertices::Data(data) => {
+                render
+                    .context
+                    .bind_buffer(WebGlRenderingContext::ARRAY_BUFFER, Some(&render.buffer));
+                render.context.buffer_sub_data_with_i32_and_u8_array(
+                    WebGlRenderingContext::ARRAY_BUFFER,
+                    0,
+                    data,
+                );
+            }
+            Vertices::Buffer {
+                buffer,
+                first_vertex,
+            } => render.set_sprite_attributes(buffer, first_vertex),
+        }
 
-        gl_check_error!(&self.render.context, "after write");
-        let view = camera.view();
-        self.render.context.uniform_matrix3fv_with_f32_array(
-            self.render
+        gl_check_error!(&render.context, "after write");
+        if material != MaterialId::default() && !render.materials.contains_key(&material) {
+            log::error!("material {} not found", material);
+        }
+        let (shader_program, texture_units) = render.program(material);
+        render.context.use_program(Some(&shader_program));
+        render.context.uniform_matrix3fv_with_f32_array(
+            render
                 .context
-                .get_uniform_location(&self.render.shader_program, "view")
+                .get_uniform_location(&shader_program, "view")
                 .as_ref(),
             false,
             view,
         );
-        let text_units = (0..self.render.max_texture_units).collect::<Vec<i32>>();
-        self.render.context.uniform1iv_with_i32_array(
-            self.render
+        // WebGL 1.0 has no sRGB framebuffer, so the shader always converts the output.
+        render.context.uniform1i(
+            render
+                .context
+                .get_uniform_location(&shader_program, "encodeSrgb")
+                .as_ref(),
+            (render.color_space == ColorSpace::Linear) as i32,
+        );
+        let text_units = (0..texture_units).collect::<Vec<i32>>();
+        render.context.uniform1iv_with_i32_array(
+            render
                 .context
-                .get_uniform_location(&self.render.shader_program, "text")
+                .get_uniform_location(&shader_program, "text")
                 .as_ref(),
             &text_units,
         );
+        if render.uses_normal_maps(material) {
+            let normal_units = (texture_units..2 * texture_units).collect::<Vec<i32>>();
+            render.context.uniform1iv_with_i32_array(
+                render
+                    .context
+                    .get_uniform_location(&shader_program, "normalMaps")
+                    .as_ref(),
+                &normal_units,
+            );
+            render.context.uniform1iv_with_i32_array(
+                render
+                    .context
+                    .get_uniform_location(&shader_program, "hasNormalMap")
+                    .as_ref(),
+                render.normal_map_flags.uniform(texture_units),
+            );
+        }
+        let blend = render
+            .materials
+            .get(&material)
+            .map_or(BlendMode::Alpha, |x| x.blend);
+        let (src, dst) = blend_factors(blend);
+        render.context.blend_func(src, dst);
+        if let Some(material) = render.materials.get(&material) {
+            render.set_material_uniforms(material);
+        }
 
-        self.render.context.bind_buffer(
+        render.context.bind_buffer(
             WebGlRenderingContext::ELEMENT_ARRAY_BUFFER,
-            Some(&self.render.indice_buffer),
+            Some(index_buffer),
         );
 
-        gl_check_error!(&self.render.context, "pre draw");
+        gl_check_error!(&render.context, "pre draw");
 
-        self.render.context.draw_elements_with_i32(
+        render.context.draw_elements_with_i32(
             WebGlRenderingContext::TRIANGLES,
-            sprites.len() as i32 * 6,
+            index_count,
             WebGlRenderingContext::UNSIGNED_SHORT,
             0,
         );
-        self.render
+        if let Vertices::Buffer { .. } = vertices {
+            render.set_sprite_attributes(&render.buffer, 0);
+        }
+        render
             .context
             .bind_buffer(WebGlRenderingContext::ARRAY_BUFFER, None);
-        self.render
+        render
             .context
             .bind_buffer(WebGlRenderingContext::ELEMENT_ARRAY_BUFFER, None);
-        gl_check_error!(&self.render.context, "end frame");
+        gl_check_error!(&render.context, "end frame");
+    }
+
+    /// The size of the render target, in pixels.
+    fn target_size(&self) -> (u32, u32) {
+        match self.target {
+            Some(texture) => self
+                .render
+                .get_gl_texture(texture)
+                .map_or((0, 0), |x| (x.width, x.height)),
+            None => self.render.size,
+        }
+    }
+
+    /// Set the scissor to the current clip rectangle, or disable it if there is none.
+    fn apply_clip_rect(&self) {
+        let context = &self.render.context;
+        let Some([x, y, width, height]) = self.clip.current() else {
+            context.disable(WebGlRenderingContext::SCISSOR_TEST);
+            return;
+        };
+        // The scissor is from the bottom left corner of the framebuffer, but the rows of a render
+        // texture are already flipped by the view.
+        let y = match self.target {
+            Some(_) => y,
+            None => self.target_size().1 as i32 - y - height,
+        };
+        context.enable(WebGlRenderingContext::SCISSOR_TEST);
+        context.scissor(x, y, width, height);
+    }
+
+    /// Set the stencil test to draw only inside all the pushed masks.
+    fn apply_masks(&self) {
+        let context = &self.render.context;
+        context.color_mask(true, true, true, true);
+        context.stencil_op(
+            WebGlRenderingContext::KEEP,
+            WebGlRenderingContext::KEEP,
+            WebGlRenderingContext::KEEP,
+        );
+        match self.clip.masks() {
+            0 => context.disable(WebGlRenderingContext::STENCIL_TEST),
+            masks => context.stencil_func(WebGlRenderingContext::EQUAL, masks as i32, 0xFF),
+        }
+    }
+
+    /// Draw a quad that covers all the render target, with the current stencil state. Used to
+    /// update the stencil buffer without drawing the shapes of a mask again.
+    fn fill_target(&mut self) {
+        let render = &mut *self.render;
+        if render.buffer_size == 0 {
+            render.reallocate_instance_buffer(1);
+        }
+        let Some(white) = render.get_gl_texture(WHITE_TEXTURE) else {
+            return;
+        };
+        render
+            .context
+            .active_texture(WebGlRenderingContext::TEXTURE0);
+        render
+            .context
+            .bind_texture(WebGlRenderingContext::TEXTURE_2D, Some(&white.handle));
+        render.texture_unit_map.clear();
+        // the positions are already in normalized device coordinates.
+        let quad = Quad {
+            positions: [[-1.0, -1.0], [1.0, -1.0], [-1.0, 1.0], [1.0, 1.0]],
+            uvs: [[0.0, 0.0], [1.0, 0.0], [0.0, 1.0], [1.0, 1.0]],
+            color: [255; 4],
+            texture: WHITE_TEXTURE,
+            material: MaterialId::default(),
+        };
+        let mut data = Vec::with_capacity(SPRITE_VERTEX_STRIDE * 4);
+        let texture_unit = white.gl_format.swizzle << 8;
+        unsafe { WebGLSpriteRender::write_quad(&mut data, &quad, texture_unit).unwrap() };
+        let index_buffer = render.indice_buffer.clone();
+        let identity = [1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0];
+        let vertices = Vertices::Data(&data);
+        self.draw_batch(&identity, MaterialId::default(), vertices, &index_buffer, 6);
+    }
+}
+impl<'a> Renderer for WebGLRenderer<'a> {
+    fn clear_screen(&mut self, color: &[f32; 4]) -> &mut dyn Renderer {
+        self.render
+            .context
+            .clear_color(color[0], color[1], color[2], color[3]);
+        self.render
+            .context
+            .clear(WebGlRenderingContext::COLOR_BUFFER_BIT);
+        self
+    }
+
+    fn draw_sprites(
+        &mut self,
+        camera: &mut Camera,
+        sprites: &[SpriteInstance],
+    ) -> &mut dyn Renderer {
+        self.draw_quads(camera, sprites.len(), sprites.iter().map(Quad::from));
+        self
+    }
+
+    fn draw_shapes(&mut self, camera: &mut Camera, shapes: &Shapes) -> &mut dyn Renderer {
+        self.draw_quads(camera, shapes.quads.len(), shapes.quads.iter().cloned());
+        self
+    }
+
+    fn draw_mesh(&mut self, camera: &mut Camera, mesh: &Mesh) -> &mut dyn Renderer {
+        let chunks = mesh::chunks(mesh);
+        if chunks.is_empty() {
+            return self;
+        }
+        let view = self.view(camera);
+
+        let render = &mut *self.render;
+        let id = render.feedback_loops.texture(mesh.texture, self.target);
+        let texture = match render.get_gl_texture(id) {
+            Some(x) => x,
+            None => {
+                render.missing_textures.warn(id);
+                match render.get_gl_texture(FALLBACK_TEXTURE) {
+                    Some(x) => x,
+                    None => return self,
+                }
+            }
+        };
+        let texture_units = render.program(mesh.material).1;
+        render.texture_unit_map.clear();
+        render.clear_normal_maps(texture_units);
+        if render.uses_normal_maps(mesh.material) {
+            render.bind_normal_map(texture.id, 0, texture_units);
+        }
+        let texture_unit = texture.gl_format.swizzle << 8;
+
+        let mut data = Vec::new();
+        for chunk in chunks {
+            let render = &mut *self.render;
+            let sprites_need = chunk.vertices.len().div_ceil(4);
+            if sprites_need > render.buffer_size as usize {
+                render.reallocate_instance_buffer(sprites_need);
+            }
+            data.clear();
+            for vertex in chunk.vertices.iter() {
+                unsafe {
+                    WebGLSpriteRender::write_vertex(&mut data, vertex, texture_unit).unwrap()
+                };
+            }
+
+            render
+                .context
+                .active_texture(WebGlRenderingContext::TEXTURE0);
+            render
+                .context
+                .bind_texture(WebGlRenderingContext::TEXTURE_2D, Some(&texture.handle));
+
+            let index_buffer = render.mesh_indice_buffer.clone();
+            render.context.bind_buffer(
+                WebGlRenderingContext::ELEMENT_ARRAY_BUFFER,
+                Some(&index_buffer),
+            );
+            render.context.buffer_data_with_u8_array(
+                WebGlRenderingContext::ELEMENT_ARRAY_BUFFER,
+                unsafe { transmute_slice(&chunk.indices) },
+                WebGlRenderingContext::STREAM_DRAW,
+            );
+            render
+                .context
+                .bind_buffer(WebGlRenderingContext::ELEMENT_ARRAY_BUFFER, None);
+
+            let count = chunk.indices.len() as i32;
+            self.draw_batch(
+                &view,
+                mesh.material,
+                Vertices::Data(&data),
+                &index_buffer,
+                count,
+            );
+        }
+        self
+    }
+
+    fn draw_static_batch(&mut self, camera: &mut Camera, batch: BatchId) -> &mut dyn Renderer {
+        let view = self.view(camera);
+        self.draw_static(&view, batch);
+        self
+    }
+
+    fn draw_static_batch_transformed(
+        &mut self,
+        camera: &mut Camera,
+        batch: BatchId,
+        transform: &Transform,
+    ) -> &mut dyn Renderer {
+        let view = transform.apply(&self.view(camera));
+        self.draw_static(&view, batch);
+        self
+    }
+
+    fn push_clip_rect(&mut self, rect: ClipRect) -> &mut dyn Renderer {
+        let (width, height) = self.target_size();
+        self.clip.push(rect.to_pixels(width, height));
+        self.apply_clip_rect();
+        self
+    }
+
+    fn pop_clip_rect(&mut self) -> &mut dyn Renderer {
+        if !self.clip.pop() {
+            log::warn!("pop_clip_rect called without a pushed clip rect");
+            return self;
+        }
+        self.apply_clip_rect();
+        self
+    }
+
+    fn begin_mask(&mut self) -> &mut dyn Renderer {
+        if !self.clip.begin_mask() {
+            return self;
+        }
+        let masks = self.clip.masks();
+        let context = &self.render.context;
+        if masks == 0 {
+            // the scissor also limits the clear.
+            context.disable(WebGlRenderingContext::SCISSOR_TEST);
+            context.clear_stencil(0);
+            context.clear(WebGlRenderingContext::STENCIL_BUFFER_BIT);
+            self.apply_clip_rect();
+            context.enable(WebGlRenderingContext::STENCIL_TEST);
+        }
+        // increment the pixels inside all the pushed masks, without drawing colors.
+        context.color_mask(false, false, false, false);
+        context.stencil_func(WebGlRenderingContext::EQUAL, masks as i32, 0xFF);
+        context.stencil_op(
+            WebGlRenderingContext::KEEP,
+            WebGlRenderingContext::KEEP,
+            WebGlRenderingContext::INCR,
+        );
         self
     }
 
-    fn finish(&mut self) {}
+    fn end_mask(&mut self) -> &mut dyn Renderer {
+        if self.clip.end_mask() {
+            self.apply_masks();
+        }
+        self
+    }
+
+    fn pop_mask(&mut self) -> &mut dyn Renderer {
+        if !self.clip.pop_mask() {
+            return self;
+        }
+        let masks = self.clip.masks();
+        if masks > 0 {
+            // decrement the pixels inside the popped mask, including the ones outside the scissor.
+            let context = &self.render.context;
+            context.disable(WebGlRenderingContext::SCISSOR_TEST);
+            context.color_mask(false, false, false, false);
+            context.stencil_func(WebGlRenderingContext::EQUAL, masks as i32 + 1, 0xFF);
+            context.stencil_op(
+                WebGlRenderingContext::KEEP,
+                WebGlRenderingContext::KEEP,
+                WebGlRenderingContext::DECR,
+            );
+            self.fill_target();
+            self.apply_clip_rect();
+        }
+        self.apply_masks();
+        self
+    }
+
+    fn finish(&mut self) {
+        if !self.clip.is_empty() {
+            log::warn!("finish called with clip rects or masks still pushed");
+            self.clip = ClipStack::default();
+        }
+        self.apply_clip_rect();
+        self.apply_masks();
+        if self.target.is_none() {
+            self.render.apply_post_effects();
+        } else {
+            let (width, height) = self.render.size;
+            self.render
+                .context
+                .bind_framebuffer(WebGlRenderingContext::FRAMEBUFFER, None);
+            self.render
+                .context
+                .viewport(0, 0, width as i32, height as i32);
+        }
+    }
+}
+
+/// How a `TextureFormat` is stored in WebGL.
+#[derive(Clone, Copy, Debug)]
+struct GlFormat {
+    format: u32,
+    type_: u32,
+    /// How the sampled color is remapped in the shader.
+    swizzle: u16,
+}
+impl GlFormat {
+    /// Returns None if the format is not supported, or if it is a compressed format that is not in
+    /// `compressed_formats`.
+    ///
+    /// In the linear color space, the colors are stored in sRGB and converted to linear by the
+    /// shader, because WebGL 1.0 has no sRGB formats without extensions.
+    fn new(
+        format: TextureFormat,
+        half_float: bool,
+        compressed_formats: &[u32],
+        color_space: ColorSpace,
+    ) -> Option<Self> {
+        let srgb_swizzle = match (color_space, format) {
+            (ColorSpace::Gamma, _)
+            | (_, TextureFormat::R8 | TextureFormat::Rg8 | TextureFormat::Rgba16F) => 0,
+            _ => SRGB_DECODE,
+        };
+        let compressed = |format: u32| {
+            compressed_formats
+                .contains(&format)
+                .then_some((format, 0, SWIZZLE_NONE))
+        };
+        let (format, type_, swizzle) = match format {
+            TextureFormat::Rgba8888 => (
+                WebGlRenderingContext::RGBA,
+                WebGlRenderingContext::UNSIGNED_BYTE,
+                SWIZZLE_NONE,
+            ),
+            // LUMINANCE is sampled as (l, l, l, 1), so it is swizzled to (1, 1, 1, l).
+            TextureFormat::R8 => (
+                WebGlRenderingContext::LUMINANCE,
+                WebGlRenderingContext::UNSIGNED_BYTE,
+                SWIZZLE_ALPHA,
+            ),
+            TextureFormat::Rg8 => return None,
+            TextureFormat::Rgb888 => (
+                WebGlRenderingContext::RGB,
+                WebGlRenderingContext::UNSIGNED_BYTE,
+                SWIZZLE_NONE,
+            ),
+            TextureFormat::Rgb565 => (
+                WebGlRenderingContext::RGB,
+                WebGlRenderingContext::UNSIGNED_SHORT_5_6_5,
+                SWIZZLE_NONE,
+            ),
+            TextureFormat::Rgba4444 => (
+                WebGlRenderingContext::RGBA,
+                WebGlRenderingContext::UNSIGNED_SHORT_4_4_4_4,
+                SWIZZLE_NONE,
+            ),
+            TextureFormat::Rgba16F if half_float => {
+                (WebGlRenderingContext::RGBA, HALF_FLOAT_OES, SWIZZLE_NONE)
+            }
+            TextureFormat::Rgba16F => return None,
+            TextureFormat::LuminanceAlpha => (
+                WebGlRenderingContext::LUMINANCE_ALPHA,
+                WebGlRenderingContext::UNSIGNED_BYTE,
+                SWIZZLE_NONE,
+            ),
+            TextureFormat::Bc1 => compressed(COMPRESSED_RGBA_S3TC_DXT1_EXT)?,
+            TextureFormat::Bc2 => compressed(COMPRESSED_RGBA_S3TC_DXT3_EXT)?,
+            TextureFormat::Bc3 => compressed(COMPRESSED_RGBA_S3TC_DXT5_EXT)?,
+            TextureFormat::Etc2Rgb8 => compressed(COMPRESSED_RGB8_ETC2)?,
+            TextureFormat::Etc2Rgba8 => compressed(COMPRESSED_RGBA8_ETC2_EAC)?,
+            TextureFormat::Astc4x4 => compressed(COMPRESSED_RGBA_ASTC_4X4_KHR)?,
+        };
+        Some(Self {
+            format,
+            type_,
+            swizzle: swizzle | srgb_swizzle,
+        })
+    }
+
+    /// Convert `data` to the typed array that WebGL expects for this format.
+    fn pixels(&self, data: &[u8]) -> js_sys::Object {
+        if self.type_ == WebGlRenderingContext::UNSIGNED_BYTE {
+            js_sys::Uint8Array::from(data).into()
+        } else {
+            // packed and half-float pixels must be given in a Uint16Array.
+            let data = data
+                .chunks_exact(2)
+                .map(|x| u16::from_ne_bytes([x[0], x[1]]))
+                .collect::<Vec<u16>>();
+            js_sys::Uint16Array::from(&data[..]).into()
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -232,30 +767,182 @@ struct GlTexture {
     handle: WebGlTexture,
     width: u32,
     height: u32,
+    format: TextureFormat,
+    gl_format: GlFormat,
+    /// The framebuffer of the texture, if it was created with `new_render_texture`.
+    framebuffer: Option<WebGlFramebuffer>,
+    /// The stencil renderbuffer attached to the framebuffer, if it could be attached.
+    stencil: Option<WebGlRenderbuffer>,
+    /// If the mipmaps of this texture are generated, and must be regenerated on update.
+    generate_mipmaps: bool,
+}
+
+/// The `TEXTURE_MIN_FILTER` of the given sampler.
+fn min_filter(sampler: &Sampler, has_mipmaps: bool) -> u32 {
+    use TextureFilter::*;
+    match (
+        sampler.min_filter,
+        has_mipmaps.then_some(sampler.mip_filter),
+    ) {
+        (Nearest, None) => WebGlRenderingContext::NEAREST,
+        (Linear, None) => WebGlRenderingContext::LINEAR,
+        (Nearest, Some(Nearest)) => WebGlRenderingContext::NEAREST_MIPMAP_NEAREST,
+        (Linear, Some(Nearest)) => WebGlRenderingContext::LINEAR_MIPMAP_NEAREST,
+        (Nearest, Some(Linear)) => WebGlRenderingContext::NEAREST_MIPMAP_LINEAR,
+        (Linear, Some(Linear)) => WebGlRenderingContext::LINEAR_MIPMAP_LINEAR,
+    }
+}
+
+fn filter(filter: TextureFilter) -> u32 {
+    match filter {
+        TextureFilter::Nearest => WebGlRenderingContext::NEAREST,
+        TextureFilter::Linear => WebGlRenderingContext::LINEAR,
+    }
+}
+
+/// The source and destination factors of a blend mode.
+fn blend_factors(blend: BlendMode) -> (u32, u32) {
+    match blend {
+        BlendMode::Alpha => (
+            WebGlRenderingContext::SRC_ALPHA,
+            WebGlRenderingContext::ONE_MINUS_SRC_ALPHA,
+        ),
+        BlendMode::Additive => (WebGlRenderingContext::SRC_ALPHA, WebGlRenderingContext::ONE),
+        BlendMode::Multiply => (
+            WebGlRenderingContext::DST_COLOR,
+            WebGlRenderingContext::ZERO,
+        ),
+        BlendMode::Premultiplied => (
+            WebGlRenderingContext::ONE,
+            WebGlRenderingContext::ONE_MINUS_SRC_ALPHA,
+        ),
+    }
+}
+
+fn wrap(wrap: TextureWrap) -> u32 {
+    match wrap {
+        TextureWrap::ClampToEdge => WebGlRenderingContext::CLAMP_TO_EDGE,
+        TextureWrap::Repeat => WebGlRenderingContext::REPEAT,
+        TextureWrap::MirroredRepeat => WebGlRenderingContext::MIRRORED_REPEAT,
+    }
+}
+
+/// A material compiled by `new_material`.
+struct GlMaterial {
+    program: WebGlProgram,
+    /// The number of texture units used by the sprite textures. If `normal_maps` is true, their
+    /// normal maps use the same number of units after these. The texture uniforms of the material
+    /// use the units after all of them.
+    texture_units: i32,
+    normal_maps: bool,
+    uniforms: Vec<(String, Uniform)>,
+    blend: BlendMode,
+}
+
+/// A static batch created by `new_static_batch`.
+struct WebGLStaticBatch {
+    /// The Vertex Buffer with the vertices of all sprites of the batch.
+    buffer: WebGlBuffer,
+    groups: Vec<BatchGroup>,
+    /// The sprites of the batch, to group them again when a range update changes the groups.
+    sprites: Vec<SpriteInstance>,
 }
 
 pub struct WebGLSpriteRender {
     context: WebGlRenderingContext,
+    color_space: ColorSpace,
+    /// The shader program of the default material.
     shader_program: WebGlProgram,
+    /// The materials created by the user.
+    materials: HashMap<MaterialId, GlMaterial>,
+    /// The id that will be given to the next created material.
+    next_material_id: u32,
     textures: Vec<GlTexture>,
     buffer: WebGlBuffer,
     indice_buffer: WebGlBuffer,
+    /// The index buffer of the meshes, rewritten for each mesh.
+    mesh_indice_buffer: WebGlBuffer,
+    /// The static batches created by the user.
+    static_batches: HashMap<BatchId, WebGLStaticBatch>,
+    /// The id that will be given to the next created static batch.
+    next_batch_id: u32,
     /// Buffer size in number of sprites
     buffer_size: u32,
-    /// maps a texture to a texture unit
+    /// maps a texture to its index in the vertices, `unit | swizzle << 8`.
     texture_unit_map: HashMap<TextureId, u32>,
+    /// maps a texture to its normal map, set with `set_normal_map`.
+    normal_maps: HashMap<TextureId, TextureId>,
+    /// If a normal map is bound for each texture unit of the current batch, for the
+    /// `hasNormalMap` uniform.
+    normal_map_flags: NormalMapFlags,
     max_texture_units: i32,
+    /// If the OES_texture_half_float extension is available.
+    half_float: bool,
+    /// The compressed texture formats enabled by the available extensions.
+    compressed_formats: Vec<u32>,
+    /// The texture drawn in place of the textures that do not exist.
+    fallback: FallbackTexture,
+    /// The textures that were not found when drawing.
+    missing_textures: MissingTextures,
+    /// The render textures that were drawn to themselves.
+    feedback_loops: FeedbackLoops,
+    /// The maximum anisotropy supported, or 1.0 if EXT_texture_filter_anisotropic is not
+    /// available.
+    max_anisotropy: f32,
+    /// The size of the canvas, in pixels.
+    size: (u32, u32),
+
+    /// The Vertex Buffer of the full-screen quad, used by post effects.
+    post_quad_buffer: WebGlBuffer,
+    /// The shader programs of the post effects, indexed by `PostEffect::kind`.
+    post_programs: HashMap<usize, WebGlProgram>,
+    post_effects: Vec<PostEffect>,
+    /// The two render targets that the post effects ping-pong between. The scene is rendered to
+    /// the first one.
+    post_targets: Vec<(WebGlTexture, WebGlFramebuffer)>,
+    /// The stencil renderbuffer of the first post-processing render target.
+    post_stencil: Option<WebGlRenderbuffer>,
+    /// The size of the post-processing render targets, in pixels.
+    post_size: (u32, u32),
 }
 impl WebGLSpriteRender {
-    /// Get a WindowBuilder and a event_loop (for opengl support), and return a window and Self.
-    pub fn new(window: &Window) -> Self {
-        let canvas = window.canvas();
+    /// Create a new WebGLSpriteRender that renders to the canvas of the given window, whose size
+    /// in pixels is `width` x `height`.
+    ///
+    /// The canvas is found by its `data-raw-handle` attribute, which must match the id of the
+    /// window's raw handle.
+    pub fn new(window: &dyn RawWindow, width: u32, height: u32) -> Self {
+        Self::new_with_color_space(window, width, height, ColorSpace::Gamma)
+    }
+
+    /// Same as [WebGLSpriteRender::new], but blending the colors in the given [ColorSpace].
+    ///
+    /// WebGL 1.0 has no sRGB framebuffer, so in [ColorSpace::Linear] the conversions are done in
+    /// the shaders, and the colors are still blended in sRGB.
+    pub fn new_with_color_space(
+        window: &dyn RawWindow,
+        width: u32,
+        height: u32,
+        color_space: ColorSpace,
+    ) -> Self {
+        let id = match window.raw_window_handle() {
+            RawWindowHandle::Web(handle) => handle.id,
+            handle => panic!("expected a web window handle, found {:?}", handle),
+        };
 
-        let canvas: web_sys::HtmlCanvasElement =
-            canvas.dyn_into::<web_sys::HtmlCanvasElement>().unwrap();
+        let canvas: web_sys::HtmlCanvasElement = web_sys::window()
+            .and_then(|x| x.document())
+            .unwrap()
+            .query_selector(&format!("canvas[data-raw-handle=\"{}\"]", id))
+            .unwrap()
+            .expect("the canvas of the window was not found")
+            .dyn_into::<web_sys::HtmlCanvasElement>()
+            .unwrap();
 
         let context_options = js_sys::Object::new();
         js_sys::Reflect::set(&context_options, &"alpha".into(), &false.into()).unwrap();
+        // used by the masks of `Renderer::begin_mask`.
+        js_sys::Reflect::set(&context_options, &"stencil".into(), &true.into()).unwrap();
         js_sys::Reflect::set(
             &context_options,
             &"premultipliedAlpha".into(),
@@ -275,6 +962,39 @@ impl WebGLSpriteRender {
             WebGlRenderingContext::ONE_MINUS_SRC_ALPHA,
         );
         context.enable(WebGlRenderingContext::BLEND);
+        // the rows of formats with less than 4 bytes per pixel are not aligned.
+        context.pixel_storei(WebGlRenderingContext::UNPACK_ALIGNMENT, 1);
+
+        // the extensions are only enabled after being queried.
+        let has_extension = |name: &str| matches!(context.get_extension(name), Ok(Some(_)));
+        let half_float = has_extension("OES_texture_half_float");
+        let mut compressed_formats = Vec::new();
+        if has_extension("WEBGL_compressed_texture_s3tc")
+            || has_extension("WEBKIT_WEBGL_compressed_texture_s3tc")
+        {
+            compressed_formats.extend([
+                COMPRESSED_RGBA_S3TC_DXT1_EXT,
+                COMPRESSED_RGBA_S3TC_DXT3_EXT,
+                COMPRESSED_RGBA_S3TC_DXT5_EXT,
+            ]);
+        }
+        if has_extension("WEBGL_compressed_texture_etc") {
+            compressed_formats.extend([COMPRESSED_RGB8_ETC2, COMPRESSED_RGBA8_ETC2_EAC]);
+        }
+        if has_extension("WEBGL_compressed_texture_astc") {
+            compressed_formats.push(COMPRESSED_RGBA_ASTC_4X4_KHR);
+        }
+        let mut max_anisotropy = 1.0;
+        if has_extension("EXT_texture_filter_anisotropic")
+            || has_extension("WEBKIT_EXT_texture_filter_anisotropic")
+            || has_extension("MOZ_EXT_texture_filter_anisotropic")
+        {
+            max_anisotropy = context
+                .get_parameter(MAX_TEXTURE_MAX_ANISOTROPY_EXT)
+                .ok()
+                .and_then(|x| x.as_f64())
+                .unwrap_or(1.0) as f32;
+        }
 
         let max_texture_units = context
             .get_parameter(WebGlRenderingContext::MAX_TEXTURE_IMAGE_UNITS)
@@ -283,90 +1003,77 @@ impl WebGLSpriteRender {
             .unwrap() as i32;
         console::log_1(&format!("MAX_TEXTURE_IMAGE_UNITS: {}", max_texture_units).into());
 
-        let vert_shader = Self::compile_shader(
-            &context,
-            WebGlRenderingContext::VERTEX_SHADER,
-            VERTEX_SHADER_SOURCE,
-        )
-        .unwrap();
-        let frag_shader = Self::compile_shader(
+        let shader_program = Self::create_sprite_program(
             &context,
-            WebGlRenderingContext::FRAGMENT_SHADER,
-            &(format!("#define MAX_TEXTURE_IMAGE_UNITS {}\n", max_texture_units)
-                + FRAGMENT_SHADER_SOURCE),
+            material::DEFAULT_FRAGMENT_SOURCE,
+            max_texture_units,
+            false,
+            color_space,
         )
         .unwrap();
-        let shader_program = Self::link_program(&context, &vert_shader, &frag_shader).unwrap();
         context.use_program(Some(&shader_program));
 
         let indice_buffer = context
             .create_buffer()
             .ok_or("failed to create buffer")
             .unwrap();
+        let mesh_indice_buffer = context
+            .create_buffer()
+            .ok_or("failed to create buffer")
+            .unwrap();
         let buffer = context
             .create_buffer()
             .ok_or("failed to create buffer")
             .unwrap();
-        context.bind_buffer(WebGlRenderingContext::ARRAY_BUFFER, Some(&buffer));
-
-        let position = context.get_attrib_location(&shader_program, "position") as u32;
-        context.vertex_attrib_pointer_with_i32(
-            position,
-            2,
-            WebGlRenderingContext::FLOAT,
-            false,
-            SPRITE_VERTEX_STRIDE as i32,
-            0,
-        );
-        context.enable_vertex_attrib_array(position);
-
-        let uv = context.get_attrib_location(&shader_program, "uv") as u32;
-        context.vertex_attrib_pointer_with_i32(
-            uv,
-            2,
-            WebGlRenderingContext::FLOAT,
-            false,
-            SPRITE_VERTEX_STRIDE as i32,
-            mem::size_of::<f32>() as i32 * 2,
-        );
-        context.enable_vertex_attrib_array(uv);
-
-        let a_color = context.get_attrib_location(&shader_program, "aColor") as u32;
-        context.vertex_attrib_pointer_with_i32(
-            a_color,
-            4,
-            WebGlRenderingContext::UNSIGNED_BYTE,
-            true,
-            SPRITE_VERTEX_STRIDE as i32,
-            mem::size_of::<f32>() as i32 * 4,
-        );
-        context.enable_vertex_attrib_array(a_color);
-
-        let a_texture = context.get_attrib_location(&shader_program, "aTexture") as u32;
-        context.vertex_attrib_pointer_with_i32(
-            a_texture,
-            1,
-            WebGlRenderingContext::UNSIGNED_SHORT,
-            false,
-            SPRITE_VERTEX_STRIDE as i32,
-            mem::size_of::<f32>() as i32 * 5,
-        );
-        context.enable_vertex_attrib_array(a_texture);
-
-        context.bind_buffer(WebGlRenderingContext::ARRAY_BUFFER, None);
+        let post_quad_buffer = context
+            .create_buffer()
+            .ok_or("failed to create buffer")
+            .unwrap();
+        context.bind_buffer(WebGlRenderingContext::ARRAY_BUFFER, Some(&post_quad_buffer));
+        context.buffer_data_with_u8_array(
+            WebGlRenderingContext::ARRAY_BUFFER,
+            unsafe { transmute_slice(&post_process::POST_QUAD_VERTICES) },
+            WebGlRenderingContext::STATIC_DRAW,
+        );
+        context.bind_buffer(WebGlRenderingContext::ARRAY_BUFFER, None);
+        context.bind_buffer(WebGlRenderingContext::ARRAY_BUFFER, None);
 
         let mut sprite_render = Self {
             shader_program,
+            materials: HashMap::new(),
+            next_material_id: 1,
             context,
+            color_space,
             buffer,
             indice_buffer,
+            mesh_indice_buffer,
+            static_batches: HashMap::new(),
+            next_batch_id: 1,
             buffer_size: 0,
             textures: Vec::new(),
             texture_unit_map: HashMap::new(),
+            normal_maps: HashMap::new(),
+            normal_map_flags: NormalMapFlags::default(),
             max_texture_units,
+            half_float,
+            compressed_formats,
+            fallback: FallbackTexture::default(),
+            missing_textures: MissingTextures::default(),
+            feedback_loops: FeedbackLoops::default(),
+            max_anisotropy,
+            size: (width, height),
+            post_quad_buffer,
+            post_programs: HashMap::new(),
+            post_effects: Vec::new(),
+            post_targets: Vec::new(),
+            post_stencil: None,
+            post_size: (0, 0),
         };
-        let size = window.inner_size();
-        sprite_render.resize(window.id(), size.width, size.height);
+        sprite_render.set_sprite_attributes(&sprite_render.buffer, 0);
+        sprite_render.resize(SurfaceId::default(), width, height);
+        let fallback = sprite_render.fallback;
+        fallback.create(&mut sprite_render);
+        shape::create_white_texture(&mut sprite_render);
 
         sprite_render
     }
@@ -407,6 +1114,10 @@ impl WebGLSpriteRender {
 
         context.attach_shader(&program, vert_shader);
         context.attach_shader(&program, frag_shader);
+        // All programs use the same attribute locations, so they can share the same attributes.
+        for (location, name) in ["position", "uv", "aColor", "aTexture"].iter().enumerate() {
+            context.bind_attrib_location(&program, location as u32, name);
+        }
         context.link_program(&program);
 
         if context
@@ -422,63 +1133,89 @@ impl WebGLSpriteRender {
         }
     }
 
-    unsafe fn write_sprite<W: Write>(
-        writer: &mut W,
-        sprite: &SpriteInstance,
-        texture: u16,
-    ) -> io::Result<()> {
-        let cos = sprite.angle.cos();
-        let sin = sprite.angle.sin();
-        let width = sprite.get_width() / 2.0;
-        let height = sprite.get_height() / 2.0;
-        let x = sprite.get_x();
-        let y = sprite.get_y();
-        let u = sprite.uv_rect[0];
-        let v = sprite.uv_rect[1];
-        let w = sprite.uv_rect[2];
-        let h = sprite.uv_rect[3];
-
-        // bottom left
-        writer.write_all(transmute_slice(&[
-            -cos * width + sin * height + x,
-            -sin * width - cos * height + y,
-            u,
-            v,
-        ]))?;
-        writer.write_all(&sprite.color)?;
-        writer.write_all(&texture.to_ne_bytes())?;
-        writer.write_all(&[0, 0])?; //complete the stride
+    /// Create a shader program for drawing sprites, with the given fragment shader source of a
+    /// material, where `texture_units` is the number of texture units used by the sprite textures,
+    /// and `normal_maps` is if their normal maps are sampled.
+    fn create_sprite_program(
+        context: &WebGlRenderingContext,
+        source: &str,
+        texture_units: i32,
+        normal_maps: bool,
+        color_space: ColorSpace,
+    ) -> Result<WebGlProgram, ShaderError> {
+        let vert_shader = Self::compile_shader(
+            context,
+            WebGlRenderingContext::VERTEX_SHADER,
+            &format!(
+                "{}{}",
+                material::color_space_prelude(color_space),
+                VERTEX_SHADER_SOURCE
+            ),
+        )
+        .map_err(ShaderError::Compile)?;
+        let frag_shader = match Self::compile_shader(
+            context,
+            WebGlRenderingContext::FRAGMENT_SHADER,
+            &material::fragment_source(source, texture_units, normal_maps, color_space),
+        ) {
+            Ok(x) => x,
+            Err(err) => {
+                context.delete_shader(Some(&vert_shader));
+                return Err(ShaderError::Compile(err));
+            }
+        };
+        let program =
+            Self::link_program(context, &vert_shader, &frag_shader).map_err(ShaderError::Link);
+        context.delete_shader(Some(&vert_shader));
+        context.delete_shader(Some(&frag_shader));
+        program
+    }
 
-        // bottom right
-        writer.write_all(transmute_slice(&[
-            cos * width + sin * height + x,
-            sin * width - cos * height + y,
-            u + w,
-            v,
-        ]))?;
-        writer.write_all(&sprite.color)?;
-        writer.write_all(&texture.to_ne_bytes())?;
-        writer.write_all(&[0, 0])?; //complete the stride
+    /// Set the uniforms of the given material in its program, which must be in use. The texture
+    /// uniforms are bound to the texture units after the ones used by the sprite textures.
+    fn set_material_uniforms(&self, material: &GlMaterial) {
+        let mut unit = material.texture_units * if material.normal_maps { 2 } else { 1 };
+        for (name, value) in &material.uniforms {
+            let location = self.context.get_uniform_location(&material.program, name);
+            let location = location.as_ref();
+            match *value {
+                Uniform::Float(x) => self.context.uniform1f(location, x),
+                Uniform::Vec2(x) => self.context.uniform2fv_with_f32_array(location, &x),
+                Uniform::Vec3(x) => self.context.uniform3fv_with_f32_array(location, &x),
+                Uniform::Vec4(x) => self.context.uniform4fv_with_f32_array(location, &x),
+                Uniform::Texture(texture) => {
+                    self.context
+                        .active_texture(WebGlRenderingContext::TEXTURE0 + unit as u32);
+                    let handle = self.get_gl_texture(texture).map(|x| x.handle);
+                    if handle.is_none() {
+                        log::error!("texture {} of uniform '{}' not found", texture, name);
+                    }
+                    self.context
+                        .bind_texture(WebGlRenderingContext::TEXTURE_2D, handle.as_ref());
+                    self.context.uniform1i(location, unit);
+                    unit += 1;
+                }
+            }
+        }
+        gl_check_error!(&self.context, "set material uniforms");
+    }
 
-        // top left
-        writer.write_all(transmute_slice(&[
-            -cos * width - sin * height + x,
-            -sin * width + cos * height + y,
-            u,
-            v + h,
-        ]))?;
-        writer.write_all(&sprite.color)?;
-        writer.write_all(&texture.to_ne_bytes())?;
-        writer.write_all(&[0, 0])?; //complete the stride
+    unsafe fn write_quad<W: Write>(writer: &mut W, quad: &Quad, texture: u16) -> io::Result<()> {
+        for (&position, &uv) in quad.positions.iter().zip(&quad.uvs) {
+            Self::write_vertex(writer, &Vertex::new(position, uv, quad.color), texture)?;
+        }
+        Ok(())
+    }
 
-        // top right
-        writer.write_all(transmute_slice(&[
-            cos * width - sin * height + x,
-            sin * width + cos * height + y,
-            u + w,
-            v + h,
-        ]))?;
-        writer.write_all(&sprite.color)?;
+    unsafe fn write_vertex<W: Write>(
+        writer: &mut W,
+        vertex: &Vertex,
+        texture: u16,
+    ) -> io::Result<()> {
+        let [x, y] = vertex.position;
+        let [u, v] = vertex.uv;
+        writer.write_all(transmute_slice(&[x, y, u, v]))?;
+        writer.write_all(&vertex.color)?;
         writer.write_all(&texture.to_ne_bytes())?;
         writer.write_all(&[0, 0])?; //complete the stride
         Ok(())
@@ -523,15 +1260,422 @@ impl WebGLSpriteRender {
         self.buffer_size = new_size as u32;
     }
 
+    /// Set and enable the vertex attributes of the sprite shader, reading the vertices of
+    /// `buffer` from `first_vertex`.
+    ///
+    /// WebGL 1.0 has no VAOs, so this must be called again after drawing with other attributes.
+    fn set_sprite_attributes(&self, buffer: &WebGlBuffer, first_vertex: usize) {
+        self.context
+            .bind_buffer(WebGlRenderingContext::ARRAY_BUFFER, Some(buffer));
+        let offset = (first_vertex * SPRITE_VERTEX_STRIDE) as i32;
+
+        let position = self
+            .context
+            .get_attrib_location(&self.shader_program, "position") as u32;
+        self.context.vertex_attrib_pointer_with_i32(
+            position,
+            2,
+            WebGlRenderingContext::FLOAT,
+            false,
+            SPRITE_VERTEX_STRIDE as i32,
+            offset,
+        );
+        self.context.enable_vertex_attrib_array(position);
+
+        let uv = self.context.get_attrib_location(&self.shader_program, "uv") as u32;
+        self.context.vertex_attrib_pointer_with_i32(
+            uv,
+            2,
+            WebGlRenderingContext::FLOAT,
+            false,
+            SPRITE_VERTEX_STRIDE as i32,
+            offset + mem::size_of::<f32>() as i32 * 2,
+        );
+        self.context.enable_vertex_attrib_array(uv);
+
+        let a_color = self
+            .context
+            .get_attrib_location(&self.shader_program, "aColor") as u32;
+        self.context.vertex_attrib_pointer_with_i32(
+            a_color,
+            4,
+            WebGlRenderingContext::UNSIGNED_BYTE,
+            true,
+            SPRITE_VERTEX_STRIDE as i32,
+            offset + mem::size_of::<f32>() as i32 * 4,
+        );
+        self.context.enable_vertex_attrib_array(a_color);
+
+        let a_texture = self
+            .context
+            .get_attrib_location(&self.shader_program, "aTexture") as u32;
+        self.context.vertex_attrib_pointer_with_i32(
+            a_texture,
+            1,
+            WebGlRenderingContext::UNSIGNED_SHORT,
+            false,
+            SPRITE_VERTEX_STRIDE as i32,
+            offset + mem::size_of::<f32>() as i32 * 5,
+        );
+        self.context.enable_vertex_attrib_array(a_texture);
+
+        self.context
+            .bind_buffer(WebGlRenderingContext::ARRAY_BUFFER, None);
+    }
+
+    /// Disable the vertex attributes of the sprite shader.
+    fn disable_sprite_attributes(&self) {
+        for name in ["position", "uv", "aColor", "aTexture"] {
+            let location = self.context.get_attrib_location(&self.shader_program, name);
+            self.context.disable_vertex_attrib_array(location as u32);
+        }
+    }
+
+    /// Create the shader program of the given post effect, or of a effect that only copies the
+    /// image if `effect` is None.
+    fn create_post_program(
+        context: &WebGlRenderingContext,
+        effect: Option<&PostEffect>,
+    ) -> Result<WebGlProgram, String> {
+        let vert_shader = Self::compile_shader(
+            context,
+            WebGlRenderingContext::VERTEX_SHADER,
+            post_process::POST_VERTEX_SHADER_SOURCE,
+        )?;
+        let fragment_source = match effect {
+            Some(effect) => effect.fragment_source(),
+            None => post_process::copy_fragment_source(),
+        };
+        let frag_shader = Self::compile_shader(
+            context,
+            WebGlRenderingContext::FRAGMENT_SHADER,
+            &fragment_source,
+        )?;
+        Self::link_program(context, &vert_shader, &frag_shader)
+    }
+
+    /// If there are post effects, bind the render target that the scene will be rendered to,
+    /// recreating the render targets if the canvas was resized.
+    fn begin_post_process(&mut self) {
+        if self.post_effects.is_empty() {
+            return;
+        }
+        if self.post_size != self.size || self.post_targets.is_empty() {
+            self.delete_post_targets();
+            let (width, height) = self.size;
+            for _ in 0..2 {
+                let texture = self.context.create_texture().unwrap();
+                self.context
+                    .bind_texture(WebGlRenderingContext::TEXTURE_2D, Some(&texture));
+                for (pname, param) in [
+                    (
+                        WebGlRenderingContext::TEXTURE_WRAP_S,
+                        WebGlRenderingContext::CLAMP_TO_EDGE,
+                    ),
+                    (
+                        WebGlRenderingContext::TEXTURE_WRAP_T,
+                        WebGlRenderingContext::CLAMP_TO_EDGE,
+                    ),
+                    (
+                        WebGlRenderingContext::TEXTURE_MIN_FILTER,
+                        WebGlRenderingContext::LINEAR,
+                    ),
+                    (
+                        WebGlRenderingContext::TEXTURE_MAG_FILTER,
+                        WebGlRenderingContext::LINEAR,
+                    ),
+                ] {
+                    self.context.tex_parameteri(
+                        WebGlRenderingContext::TEXTURE_2D,
+                        pname,
+                        param as i32,
+                    );
+                }
+                self.context
+                    .tex_image_2d_with_i32_and_i32_and_i32_and_format_and_type_and_opt_u8_array(
+                        WebGlRenderingContext::TEXTURE_2D,
+                        0,
+                        WebGlRenderingContext::RGBA as i32,
+                        width as i32,
+                        height as i32,
+                        0,
+                        WebGlRenderingContext::RGBA,
+                        WebGlRenderingContext::UNSIGNED_BYTE,
+                        None,
+                    )
+                    .unwrap();
+                let framebuffer = self.create_framebuffer(&texture);
+                self.post_targets.push((texture, framebuffer));
+            }
+            self.post_size = self.size;
+            // the scene, and its masks, are rendered to the first target.
+            self.context.bind_framebuffer(
+                WebGlRenderingContext::FRAMEBUFFER,
+                Some(&self.post_targets[0].1),
+            );
+            self.post_stencil = self.create_stencil_buffer(width, height);
+        }
+        self.context.bind_framebuffer(
+            WebGlRenderingContext::FRAMEBUFFER,
+            Some(&self.post_targets[0].1),
+        );
+    }
+
+    /// Delete the post-processing render targets, and their stencil renderbuffer.
+    fn delete_post_targets(&mut self) {
+        for (texture, framebuffer) in self.post_targets.drain(..) {
+            self.context.delete_framebuffer(Some(&framebuffer));
+            self.context.delete_texture(Some(&texture));
+        }
+        if let Some(stencil) = self.post_stencil.take() {
+            self.context.delete_renderbuffer(Some(&stencil));
+        }
+    }
+
+    /// Apply the post effects to the scene rendered in the first render target, and draw the
+    /// result to the canvas.
+    fn apply_post_effects(&mut self) {
+        if self.post_effects.is_empty() {
+            return;
+        }
+        self.context.disable(WebGlRenderingContext::BLEND);
+        self.disable_sprite_attributes();
+        self.context.bind_buffer(
+            WebGlRenderingContext::ARRAY_BUFFER,
+            Some(&self.post_quad_buffer),
+        );
+
+        let (width, height) = self.post_size;
+        let count = self.post_effects.len();
+        for (i, effect) in self.post_effects.iter().enumerate() {
+            let context = &self.context;
+            let program = self.post_programs.entry(effect.kind()).or_insert_with(|| {
+                Self::create_post_program(context, Some(effect))
+                    .or_else(|err| {
+                        log::error!("failed to create the program of {:?}: {}", effect, err);
+                        Self::create_post_program(context, None)
+                    })
+                    .unwrap()
+            });
+
+            let framebuffer = if i + 1 == count {
+                None
+            } else {
+                Some(&self.post_targets[(i + 1) % 2].1)
+            };
+            context.bind_framebuffer(WebGlRenderingContext::FRAMEBUFFER, framebuffer);
+            context.use_program(Some(program));
+
+            let position = context.get_attrib_location(program, "position") as u32;
+            context.vertex_attrib_pointer_with_i32(
+                position,
+                2,
+                WebGlRenderingContext::FLOAT,
+                false,
+                0,
+                0,
+            );
+            context.enable_vertex_attrib_array(position);
+
+            context.active_texture(WebGlRenderingContext::TEXTURE0);
+            context.bind_texture(
+                WebGlRenderingContext::TEXTURE_2D,
+                Some(&self.post_targets[i % 2].0),
+            );
+            if let Some(lut) = effect.texture() {
+                match self.textures.iter().find(|x| x.id == lut) {
+                    Some(t) => {
+                        context.active_texture(WebGlRenderingContext::TEXTURE1);
+                        context.bind_texture(WebGlRenderingContext::TEXTURE_2D, Some(&t.handle));
+                    }
+                    None => log::error!("texture {} not found", lut),
+                }
+            }
+
+            context.uniform1i(
+                context.get_uniform_location(program, "u_texture").as_ref(),
+                0,
+            );
+            context.uniform1i(context.get_uniform_location(program, "u_lut").as_ref(), 1);
+            context.uniform2f(
+                context
+                    .get_uniform_location(program, "u_resolution")
+                    .as_ref(),
+                width as f32,
+                height as f32,
+            );
+            context.uniform4fv_with_f32_array(
+                context.get_uniform_location(program, "u_params").as_ref(),
+                &effect.params(),
+            );
+            context.draw_arrays(WebGlRenderingContext::TRIANGLE_STRIP, 0, 4);
+            context.disable_vertex_attrib_array(position);
+            gl_check_error!(context, "apply post effect {:?}", effect);
+        }
+
+        self.context
+            .bind_buffer(WebGlRenderingContext::ARRAY_BUFFER, None);
+        self.context.use_program(Some(&self.shader_program));
+        self.set_sprite_attributes(&self.buffer, 0);
+        self.context.enable(WebGlRenderingContext::BLEND);
+    }
+
+    /// Create a framebuffer with the given texture as its color attachment.
+    fn create_framebuffer(&self, texture: &WebGlTexture) -> WebGlFramebuffer {
+        let framebuffer = self.context.create_framebuffer().unwrap();
+        self.context
+            .bind_framebuffer(WebGlRenderingContext::FRAMEBUFFER, Some(&framebuffer));
+        self.context.framebuffer_texture_2d(
+            WebGlRenderingContext::FRAMEBUFFER,
+            WebGlRenderingContext::COLOR_ATTACHMENT0,
+            WebGlRenderingContext::TEXTURE_2D,
+            Some(texture),
+            0,
+        );
+        let status = self
+            .context
+            .check_framebuffer_status(WebGlRenderingContext::FRAMEBUFFER);
+        if status != WebGlRenderingContext::FRAMEBUFFER_COMPLETE {
+            log::error!("framebuffer is incomplete: {:#x}", status);
+        }
+        gl_check_error!(&self.context, "create framebuffer");
+        framebuffer
+    }
+
+    /// Create a stencil renderbuffer of the given size, for the masks of [Renderer::begin_mask],
+    /// and attach it to the bound framebuffer. Returns None, and the masks don't clip the draws in
+    /// that framebuffer, if the framebuffer is incomplete with it.
+    fn create_stencil_buffer(&self, width: u32, height: u32) -> Option<WebGlRenderbuffer> {
+        let renderbuffer = self.context.create_renderbuffer().unwrap();
+        Self::resize_stencil_buffer(&self.context, &renderbuffer, width, height);
+        // DEPTH_STENCIL is the only stencil format that WebGL 1.0 guarantees to be renderable.
+        self.context.framebuffer_renderbuffer(
+            WebGlRenderingContext::FRAMEBUFFER,
+            WebGlRenderingContext::DEPTH_STENCIL_ATTACHMENT,
+            WebGlRenderingContext::RENDERBUFFER,
+            Some(&renderbuffer),
+        );
+        let status = self
+            .context
+            .check_framebuffer_status(WebGlRenderingContext::FRAMEBUFFER);
+        if status != WebGlRenderingContext::FRAMEBUFFER_COMPLETE {
+            log::warn!(
+                "framebuffer is incomplete with a stencil buffer ({:#x}), masks will not clip",
+                status
+            );
+            self.context.framebuffer_renderbuffer(
+                WebGlRenderingContext::FRAMEBUFFER,
+                WebGlRenderingContext::DEPTH_STENCIL_ATTACHMENT,
+                WebGlRenderingContext::RENDERBUFFER,
+                None,
+            );
+            self.context.delete_renderbuffer(Some(&renderbuffer));
+            return None;
+        }
+        gl_check_error!(&self.context, "create stencil renderbuffer");
+        Some(renderbuffer)
+    }
+
+    /// Reallocate the storage of a stencil renderbuffer with the given size.
+    fn resize_stencil_buffer(
+        context: &WebGlRenderingContext,
+        renderbuffer: &WebGlRenderbuffer,
+        width: u32,
+        height: u32,
+    ) {
+        context.bind_renderbuffer(WebGlRenderingContext::RENDERBUFFER, Some(renderbuffer));
+        context.renderbuffer_storage(
+            WebGlRenderingContext::RENDERBUFFER,
+            WebGlRenderingContext::DEPTH_STENCIL,
+            width as i32,
+            height as i32,
+        );
+        context.bind_renderbuffer(WebGlRenderingContext::RENDERBUFFER, None);
+    }
+
     fn get_gl_texture(&self, id: TextureId) -> Option<GlTexture> {
         self.textures.iter().find(|x| x.id == id).cloned()
     }
+
+    /// The texture unit of the fallback texture, if it is already bound in the current batch and
+    /// `id` does not exist.
+    fn missing_texture_unit(&self, id: TextureId) -> Option<u32> {
+        let unit = *self.texture_unit_map.get(&FALLBACK_TEXTURE)?;
+        (!self.textures.iter().any(|x| x.id == id)).then_some(unit)
+    }
+
+    /// The shader program of a material, and the number of texture units it uses for the sprite
+    /// textures. Unknown materials use the default program.
+    fn program(&self, material: MaterialId) -> (WebGlProgram, i32) {
+        match self.materials.get(&material) {
+            Some(x) => (x.program.clone(), x.texture_units),
+            None => (self.shader_program.clone(), self.max_texture_units),
+        }
+    }
+
+    /// If the program of a material samples the normal maps of the sprite textures.
+    fn uses_normal_maps(&self, material: MaterialId) -> bool {
+        self.materials.get(&material).is_some_and(|x| x.normal_maps)
+    }
+
+    /// Mark all `texture_units` of a new batch as not having a normal map.
+    fn clear_normal_maps(&mut self, texture_units: i32) {
+        self.normal_map_flags.clear(texture_units);
+    }
+
+    /// Bind the normal map of `texture`, if it has one, to the unit `texture_units` after the
+    /// unit of the texture.
+    fn bind_normal_map(&mut self, texture: TextureId, unit: u32, texture_units: i32) {
+        let Some(&normal_map) = self.normal_maps.get(&texture) else {
+            return;
+        };
+        let Some(normal_map) = self.get_gl_texture(normal_map) else {
+            self.missing_textures.warn(normal_map);
+            return;
+        };
+        self.context
+            .active_texture(WebGlRenderingContext::TEXTURE0 + texture_units as u32 + unit);
+        self.context
+            .bind_texture(WebGlRenderingContext::TEXTURE_2D, Some(&normal_map.handle));
+        self.normal_map_flags.set(unit);
+    }
+
+    /// Write the vertices of `sprites` to `buffer`, returning the groups they are drawn in.
+    fn write_static_batch(
+        &self,
+        buffer: &WebGlBuffer,
+        sprites: &[SpriteInstance],
+    ) -> Vec<BatchGroup> {
+        let mut data = Vec::with_capacity(sprites.len() * SPRITE_VERTEX_STRIDE * 4);
+        let groups = static_batch::group_quads(
+            sprites.iter().map(Quad::from),
+            |material| self.program(material).1 as usize,
+            |texture| {
+                self.get_gl_texture(texture)
+                    .map_or(0, |x| x.gl_format.swizzle)
+            },
+            |quad, texture| unsafe {
+                Self::write_quad(&mut data, quad, texture).unwrap();
+            },
+        );
+        self.context
+            .bind_buffer(WebGlRenderingContext::ARRAY_BUFFER, Some(buffer));
+        self.context.buffer_data_with_u8_array(
+            WebGlRenderingContext::ARRAY_BUFFER,
+            &data,
+            WebGlRenderingContext::STATIC_DRAW,
+        );
+        self.context
+            .bind_buffer(WebGlRenderingContext::ARRAY_BUFFER, None);
+        gl_check_error!(&self.context, "static batch buffer data");
+        groups
+    }
 }
 impl SpriteRender for WebGLSpriteRender {
-    fn add_window(&mut self, _: &Window) {
+    fn add_window(&mut self, _: &dyn RawWindow, _: u32, _: u32) -> SurfaceId {
         unimplemented!("Multi window is not implemented in WebGL");
     }
-    fn remove_window(&mut self, _: WindowId) {
+    fn remove_window(&mut self, _: SurfaceId) {
         unimplemented!("Multi window is not implemented in WebGL");
     }
 
@@ -541,7 +1685,8 @@ impl SpriteRender for WebGLSpriteRender {
             width,
             height,
             format,
-            filter,
+            mut sampler,
+            mipmaps,
             data,
         } = texture;
 
@@ -550,8 +1695,81 @@ impl SpriteRender for WebGLSpriteRender {
             id.0 = COUNTER.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
         }
 
-        let texture = match self.get_gl_texture(id) {
-            Some(x) => x.handle,
+        let Some(gl_format) = GlFormat::new(
+            format,
+            self.half_float,
+            &self.compressed_formats,
+            self.color_space,
+        ) else {
+            log::error!("texture format {:?} is not supported in WebGL", format);
+            return Err(TextureError::UnsupportedFormat);
+        };
+
+        let data_len = format
+            .data_len(width, height)
+            .ok_or(TextureError::InvalidLength)?;
+        match data {
+            Some(data) if data.len() != data_len => return Err(TextureError::InvalidLength),
+            _ => {}
+        };
+
+        let mut levels = match mipmaps {
+            Mipmaps::Levels(levels) => levels,
+            _ => &[],
+        };
+        let level_size = |level: usize| ((width >> level).max(1), (height >> level).max(1));
+        let full_chain = 31usize.saturating_sub(width.max(height).leading_zeros() as usize);
+        if levels.len() > full_chain {
+            log::error!("texture has more mipmap levels than its dimensions allow");
+            return Err(TextureError::InvalidLength);
+        }
+        for (i, level) in levels.iter().enumerate() {
+            let (w, h) = level_size(i + 1);
+            if Some(level.len()) != format.data_len(w, h) {
+                return Err(TextureError::InvalidLength);
+            }
+        }
+        let mut generate_mipmaps = mipmaps == Mipmaps::Generate;
+        if generate_mipmaps && format.is_compressed() {
+            log::warn!("cannot generate mipmaps of compressed format {:?}", format);
+            generate_mipmaps = false;
+        }
+        if !levels.is_empty() && levels.len() < full_chain {
+            log::warn!("the mipmap levels must go down to 1x1 in WebGL, ignoring them");
+            levels = &[];
+        }
+        // WebGL 1.0 does not support mipmaps and repeat on non power of two textures.
+        if !width.is_power_of_two() || !height.is_power_of_two() {
+            if generate_mipmaps || !levels.is_empty() {
+                log::warn!("non power of two texture {width}x{height} cannot have mipmaps");
+                generate_mipmaps = false;
+                levels = &[];
+            }
+            if sampler.wrap_s != TextureWrap::ClampToEdge
+                || sampler.wrap_t != TextureWrap::ClampToEdge
+            {
+                log::warn!("non power of two texture {width}x{height} cannot repeat");
+                sampler.wrap_s = TextureWrap::ClampToEdge;
+                sampler.wrap_t = TextureWrap::ClampToEdge;
+            }
+        }
+        let has_mipmaps = generate_mipmaps || !levels.is_empty();
+
+        let texture = match self.textures.iter_mut().find(|x| x.id == id) {
+            Some(x) => {
+                if let Some(stencil) = &x.stencil {
+                    if (x.width, x.height) != (width, height) {
+                        // the attachments of a framebuffer must have the same size.
+                        Self::resize_stencil_buffer(&self.context, stencil, width, height);
+                    }
+                }
+                x.width = width;
+                x.height = height;
+                x.format = format;
+                x.gl_format = gl_format;
+                x.generate_mipmaps = generate_mipmaps;
+                x.handle.clone()
+            }
             None => {
                 self.context.active_texture(
                     WebGlRenderingContext::TEXTURE0 + self.texture_unit_map.len() as u32,
@@ -563,6 +1781,11 @@ impl SpriteRender for WebGLSpriteRender {
                     handle: texture.clone(),
                     width,
                     height,
+                    format,
+                    gl_format,
+                    framebuffer: None,
+                    stencil: None,
+                    generate_mipmaps,
                 });
 
                 texture
@@ -574,55 +1797,78 @@ impl SpriteRender for WebGLSpriteRender {
         self.context.tex_parameteri(
             WebGlRenderingContext::TEXTURE_2D,
             WebGlRenderingContext::TEXTURE_WRAP_S,
-            WebGlRenderingContext::CLAMP_TO_EDGE as i32,
+            wrap(sampler.wrap_s) as i32,
         );
         self.context.tex_parameteri(
             WebGlRenderingContext::TEXTURE_2D,
             WebGlRenderingContext::TEXTURE_WRAP_T,
-            WebGlRenderingContext::CLAMP_TO_EDGE as i32,
+            wrap(sampler.wrap_t) as i32,
         );
         self.context.tex_parameteri(
             WebGlRenderingContext::TEXTURE_2D,
             WebGlRenderingContext::TEXTURE_MIN_FILTER,
-            WebGlRenderingContext::LINEAR as i32,
+            min_filter(&sampler, has_mipmaps) as i32,
         );
         self.context.tex_parameteri(
             WebGlRenderingContext::TEXTURE_2D,
             WebGlRenderingContext::TEXTURE_MAG_FILTER,
-            match filter {
-                TextureFilter::Nearest => WebGlRenderingContext::NEAREST,
-                TextureFilter::Linear => WebGlRenderingContext::LINEAR,
-            } as i32,
+            filter(sampler.mag_filter) as i32,
         );
+        if self.max_anisotropy > 1.0 {
+            self.context.tex_parameterf(
+                WebGlRenderingContext::TEXTURE_2D,
+                TEXTURE_MAX_ANISOTROPY_EXT,
+                sampler.max_anisotropy.clamp(1.0, self.max_anisotropy),
+            );
+        }
 
-        match data {
-            Some(data) if data.len() as u32 != width * height * 4 => {
-                return Err(TextureError::InvalidLength)
+        let upload = |level: usize, data: Option<&[u8]>| {
+            let (width, height) = level_size(level);
+            if format.is_compressed() {
+                // compressed textures cannot be created without data.
+                let zeros;
+                let data = match data {
+                    Some(data) => data,
+                    None => {
+                        // the levels are not larger than the base level, whose length was checked.
+                        zeros = vec![0u8; format.data_len(width, height).unwrap_or(data_len)];
+                        &zeros
+                    }
+                };
+                self.context.compressed_tex_image_2d_with_u8_array(
+                    WebGlRenderingContext::TEXTURE_2D,
+                    level as i32,
+                    gl_format.format,
+                    width as i32,
+                    height as i32,
+                    0,
+                    data,
+                );
+            } else {
+                let pixels = data.map(|x| gl_format.pixels(x));
+                self.context
+                    .tex_image_2d_with_i32_and_i32_and_i32_and_format_and_type_and_opt_array_buffer_view(
+                        WebGlRenderingContext::TEXTURE_2D,
+                        level as i32,
+                        gl_format.format as i32,
+                        width as i32,
+                        height as i32,
+                        0,
+                        gl_format.format,
+                        gl_format.type_,
+                        pixels.as_ref(),
+                    )
+                    .unwrap();
             }
-            _ => {}
         };
-
-        let (internalformat, format, type_) = match format {
-            crate::TextureFormat::Rgba8888 => (
-                WebGlRenderingContext::RGBA as i32,
-                WebGlRenderingContext::RGBA,
-                WebGlRenderingContext::UNSIGNED_BYTE,
-            ),
-        };
-
-        self.context
-            .tex_image_2d_with_i32_and_i32_and_i32_and_format_and_type_and_opt_u8_array(
-                WebGlRenderingContext::TEXTURE_2D,
-                0,
-                internalformat,
-                width as i32,
-                height as i32,
-                0,
-                format,
-                type_,
-                data,
-            )
-            .unwrap();
+        upload(0, data);
+        for (i, level) in levels.iter().enumerate() {
+            upload(i + 1, Some(*level));
+        }
+        if generate_mipmaps {
+            self.context
+                .generate_mipmap(WebGlRenderingContext::TEXTURE_2D);
+        }
         gl_check_error!(&self.context, "new_texture",);
 
         Ok(id)
@@ -634,16 +1880,29 @@ impl SpriteRender for WebGLSpriteRender {
         data: Option<&[u8]>,
         sub_rect: Option<[u32; 4]>,
     ) -> Result<(), TextureError> {
-        let t = self.get_gl_texture(texture).unwrap();
+        let Some(t) = self.get_gl_texture(texture) else {
+            log::error!("texture {} not found", texture);
+            return Err(TextureError::NotFound);
+        };
         let rect = sub_rect.unwrap_or([0, 0, t.width, t.height]);
-        let expected_len = (rect[2] * rect[3] * 4) as usize;
+        if rect[0] as u64 + rect[2] as u64 > t.width as u64
+            || rect[1] as u64 + rect[3] as u64 > t.height as u64
+        {
+            log::error!("sub rect {:?} is out of the texture bounds", rect);
+            return Err(TextureError::OutOfBounds);
+        }
+        let expected_len = t
+            .format
+            .data_len(rect[2], rect[3])
+            .ok_or(TextureError::InvalidLength)?;
 
         match data {
             Some(data) if data.len() != expected_len => {
                 log::error!(
-                    "expected data length was {}x{}x4={}, but receive a data of length {}",
+                    "expected data length of {}x{} {:?} was {}, but receive a data of length {}",
                     rect[2],
                     rect[3],
+                    t.format,
                     expected_len,
                     data.len()
                 );
@@ -654,33 +1913,329 @@ impl SpriteRender for WebGLSpriteRender {
 
         self.context
             .bind_texture(WebGlRenderingContext::TEXTURE_2D, Some(&t.handle));
-        self.context
-            .tex_sub_image_2d_with_i32_and_i32_and_u32_and_type_and_opt_u8_array(
-                WebGlRenderingContext::TEXTURE_2D,
-                0,
-                rect[0] as i32,
-                rect[1] as i32,
-                rect[2] as i32,
-                rect[3] as i32,
-                WebGlRenderingContext::RGBA,
-                WebGlRenderingContext::UNSIGNED_BYTE,
-                data,
-            )
-            .unwrap();
+        if t.format.is_compressed() {
+            // the sub_rect must be aligned to the blocks of the format.
+            if let Some(data) = data {
+                self.context
+                    .compressed_tex_sub_image_2d_with_array_buffer_view(
+                        WebGlRenderingContext::TEXTURE_2D,
+                        0,
+                        rect[0] as i32,
+                        rect[1] as i32,
+                        rect[2] as i32,
+                        rect[3] as i32,
+                        t.gl_format.format,
+                        &js_sys::Uint8Array::from(data),
+                    );
+            }
+        } else {
+            let pixels = data.map(|x| t.gl_format.pixels(x));
+            self.context
+                .tex_sub_image_2d_with_i32_and_i32_and_u32_and_type_and_opt_array_buffer_view(
+                    WebGlRenderingContext::TEXTURE_2D,
+                    0,
+                    rect[0] as i32,
+                    rect[1] as i32,
+                    rect[2] as i32,
+                    rect[3] as i32,
+                    t.gl_format.format,
+                    t.gl_format.type_,
+                    pixels.as_ref(),
+                )
+                .unwrap();
+        }
+        if t.generate_mipmaps {
+            self.context
+                .generate_mipmap(WebGlRenderingContext::TEXTURE_2D);
+        }
         gl_check_error!(&self.context, "update_texture",);
 
         Ok(())
     }
 
-    fn render<'a>(&'a mut self, _: WindowId) -> Box<dyn Renderer + 'a> {
-        Box::new(WebGLRenderer { render: self })
+    fn read_texture(
+        &mut self,
+        texture: TextureId,
+        sub_rect: Option<[u32; 4]>,
+    ) -> Result<Vec<u8>, TextureError> {
+        let Some(t) = self.get_gl_texture(texture) else {
+            log::error!("texture {} not found", texture);
+            return Err(TextureError::NotFound);
+        };
+        if t.format.is_compressed() {
+            return Err(TextureError::UnsupportedFormat);
+        }
+
+        let [x, y, width, height] = sub_rect.unwrap_or([0, 0, t.width, t.height]);
+        if x as u64 + width as u64 > t.width as u64 || y as u64 + height as u64 > t.height as u64 {
+            return Err(TextureError::OutOfBounds);
+        }
+
+        // WebGL has no getTexImage, so the texture is read through a framebuffer.
+        let context = &self.context;
+        let framebuffer = context.create_framebuffer();
+        context.bind_framebuffer(WebGlRenderingContext::FRAMEBUFFER, framebuffer.as_ref());
+        context.framebuffer_texture_2d(
+            WebGlRenderingContext::FRAMEBUFFER,
+            WebGlRenderingContext::COLOR_ATTACHMENT0,
+            WebGlRenderingContext::TEXTURE_2D,
+            Some(&t.handle),
+            0,
+        );
+        let status = context.check_framebuffer_status(WebGlRenderingContext::FRAMEBUFFER);
+        let mut data = vec![0u8; width as usize * height as usize * 4];
+        if status == WebGlRenderingContext::FRAMEBUFFER_COMPLETE {
+            context.pixel_storei(WebGlRenderingContext::PACK_ALIGNMENT, 1);
+            context
+                .read_pixels_with_opt_u8_array(
+                    x as i32,
+                    y as i32,
+                    width as i32,
+                    height as i32,
+                    WebGlRenderingContext::RGBA,
+                    WebGlRenderingContext::UNSIGNED_BYTE,
+                    Some(&mut data),
+                )
+                .unwrap();
+        }
+        context.bind_framebuffer(WebGlRenderingContext::FRAMEBUFFER, None);
+        context.delete_framebuffer(framebuffer.as_ref());
+        gl_check_error!(&self.context, "read_texture",);
+        if status != WebGlRenderingContext::FRAMEBUFFER_COMPLETE {
+            log::error!("texture format {:?} cannot be read", t.format);
+            return Err(TextureError::UnsupportedFormat);
+        }
+
+        material::swizzle_pixels(t.gl_format.swizzle, &mut data);
+        Ok(data)
+    }
+
+    fn set_fallback_texture(&mut self, fallback: FallbackTexture) {
+        self.fallback = fallback;
+        if let Some(i) = self.textures.iter().position(|x| x.id == FALLBACK_TEXTURE) {
+            let texture = self.textures.remove(i);
+            self.context.delete_texture(Some(&texture.handle));
+        }
+        fallback.create(self);
     }
 
-    fn resize(&mut self, _window_id: WindowId, width: u32, height: u32) {
+    fn render<'a>(&'a mut self, _: SurfaceId) -> Box<dyn Renderer + 'a> {
+        self.begin_post_process();
+        Box::new(WebGLRenderer {
+            render: self,
+            target: None,
+            clip: ClipStack::default(),
+        })
+    }
+
+    fn supports_texture_format(&self, format: TextureFormat) -> bool {
+        GlFormat::new(
+            format,
+            self.half_float,
+            &self.compressed_formats,
+            self.color_space,
+        )
+        .is_some()
+    }
+
+    fn color_space(&self) -> ColorSpace {
+        self.color_space
+    }
+
+    fn new_material(&mut self, material: Material) -> Result<MaterialId, ShaderError> {
+        // at least one texture unit must be left for the sprite textures.
+        let texture_count = material.texture_count() as i32;
+        if texture_count >= self.max_texture_units {
+            return Err(ShaderError::TooManyTextures);
+        }
+        let mut texture_units = self.max_texture_units - texture_count;
+        if material.normal_maps {
+            // each sprite texture also uses a unit for its normal map.
+            texture_units /= 2;
+            if texture_units == 0 {
+                return Err(ShaderError::TooManyTextures);
+            }
+        }
+
+        let program = Self::create_sprite_program(
+            &self.context,
+            &material.fragment_source,
+            texture_units,
+            material.normal_maps,
+            self.color_space,
+        )?;
+
+        let id = MaterialId(self.next_material_id);
+        self.next_material_id += 1;
+        self.materials.insert(
+            id,
+            GlMaterial {
+                program,
+                texture_units,
+                normal_maps: material.normal_maps,
+                uniforms: material.uniforms,
+                blend: material.blend,
+            },
+        );
+        Ok(id)
+    }
+
+    fn set_material_uniform(&mut self, material: MaterialId, name: &str, value: Uniform) {
+        let Some(material) = self.materials.get_mut(&material) else {
+            log::error!("material {} not found", material);
+            return;
+        };
+        if !material::can_set_uniform(&material.uniforms, name, &value) {
+            log::error!(
+                "texture uniform '{}' was not given on material creation",
+                name
+            );
+            return;
+        }
+        material::set_uniform(&mut material.uniforms, name, value);
+    }
+
+    fn set_normal_map(&mut self, texture: TextureId, normal_map: Option<TextureId>) {
+        match normal_map {
+            Some(normal_map) => self.normal_maps.insert(texture, normal_map),
+            None => self.normal_maps.remove(&texture),
+        };
+    }
+
+    fn new_render_texture(&mut self, width: u32, height: u32) -> Result<TextureId, TextureError> {
+        let id = self.new_texture(Texture::new(width, height))?;
+        let texture = self.get_gl_texture(id).unwrap();
+        if texture.framebuffer.is_none() {
+            let framebuffer = self.create_framebuffer(&texture.handle);
+            let stencil = self.create_stencil_buffer(texture.width, texture.height);
+            self.context
+                .bind_framebuffer(WebGlRenderingContext::FRAMEBUFFER, None);
+            let texture = self.textures.iter_mut().find(|x| x.id == id).unwrap();
+            texture.framebuffer = Some(framebuffer);
+            texture.stencil = stencil;
+        }
+        Ok(id)
+    }
+
+    fn render_to_texture<'a>(&'a mut self, texture: TextureId) -> Box<dyn Renderer + 'a> {
+        let Some(t) = self.get_gl_texture(texture) else {
+            log::error!("texture {} not found", texture);
+            return Box::new(crate::NoopRenderer);
+        };
+        let Some(framebuffer) = &t.framebuffer else {
+            log::error!("texture {} is not a render texture", texture);
+            return Box::new(crate::NoopRenderer);
+        };
+        self.context
+            .bind_framebuffer(WebGlRenderingContext::FRAMEBUFFER, Some(framebuffer));
+        self.context.viewport(0, 0, t.width as i32, t.height as i32);
+        Box::new(WebGLRenderer {
+            render: self,
+            target: Some(texture),
+            clip: ClipStack::default(),
+        })
+    }
+
+    fn new_static_batch(&mut self, sprites: &[SpriteInstance]) -> Result<BatchId, BatchError> {
+        let Some(buffer) = self.context.create_buffer() else {
+            return Err(BatchError::RendererContextDontExist);
+        };
+        let groups = self.write_static_batch(&buffer, sprites);
+        let id = BatchId(self.next_batch_id);
+        self.next_batch_id += 1;
+        self.static_batches.insert(
+            id,
+            WebGLStaticBatch {
+                buffer,
+                groups,
+                sprites: sprites.to_vec(),
+            },
+        );
+        Ok(id)
+    }
+
+    fn update_static_batch(
+        &mut self,
+        batch: BatchId,
+        sprites: &[SpriteInstance],
+    ) -> Result<(), BatchError> {
+        let Some(buffer) = self.static_batches.get(&batch).map(|x| x.buffer.clone()) else {
+            return Err(BatchError::NotFound);
+        };
+        let groups = self.write_static_batch(&buffer, sprites);
+        let static_batch = self.static_batches.get_mut(&batch).unwrap();
+        static_batch.groups = groups;
+        static_batch.sprites = sprites.to_vec();
+        Ok(())
+    }
+
+    fn update_static_batch_range(
+        &mut self,
+        batch: BatchId,
+        start: usize,
+        sprites: &[SpriteInstance],
+    ) -> Result<(), BatchError> {
+        let Some(static_batch) = self.static_batches.get(&batch) else {
+            return Err(BatchError::NotFound);
+        };
+        let end = start.saturating_add(sprites.len());
+        if end > static_batch.sprites.len() {
+            return Err(BatchError::OutOfBounds);
+        }
+
+        let quads = sprites.iter().map(Quad::from).collect::<Vec<_>>();
+        let indices = static_batch::indices_in_place(&static_batch.groups, start, &quads, |x| {
+            self.get_gl_texture(x).map_or(0, |x| x.gl_format.swizzle)
+        });
+        let buffer = static_batch.buffer.clone();
+        let static_batch = self.static_batches.get_mut(&batch).unwrap();
+        static_batch.sprites[start..end].clone_from_slice(sprites);
+
+        let Some(indices) = indices else {
+            let sprites = mem::take(&mut static_batch.sprites);
+            let groups = self.write_static_batch(&buffer, &sprites);
+            let static_batch = self.static_batches.get_mut(&batch).unwrap();
+            static_batch.groups = groups;
+            static_batch.sprites = sprites;
+            return Ok(());
+        };
+        let mut data = Vec::with_capacity(quads.len() * SPRITE_VERTEX_STRIDE * 4);
+        for (quad, texture) in quads.iter().zip(indices) {
+            unsafe { Self::write_quad(&mut data, quad, texture).unwrap() };
+        }
+        self.context
+            .bind_buffer(WebGlRenderingContext::ARRAY_BUFFER, Some(&buffer));
+        self.context.buffer_sub_data_with_i32_and_u8_array(
+            WebGlRenderingContext::ARRAY_BUFFER,
+            (start * SPRITE_VERTEX_STRIDE * 4) as i32,
+            &data,
+        );
+        self.context
+            .bind_buffer(WebGlRenderingContext::ARRAY_BUFFER, None);
+        gl_check_error!(&self.context, "static batch buffer subdata");
+        Ok(())
+    }
+
+    fn remove_static_batch(&mut self, batch: BatchId) {
+        match self.static_batches.remove(&batch) {
+            Some(static_batch) => self.context.delete_buffer(Some(&static_batch.buffer)),
+            None => log::warn!("static batch {} not found", batch),
+        }
+    }
+
+    fn resize(&mut self, _surface_id: SurfaceId, width: u32, height: u32) {
+        self.size = (width, height);
         self.context.viewport(0, 0, width as i32, height as i32);
     }
 
-    fn resume(&mut self, _: &Window) {}
+    fn set_post_effects(&mut self, _: SurfaceId, effects: Vec<PostEffect>) {
+        if effects.is_empty() {
+            self.delete_post_targets();
+        }
+        self.post_effects = effects;
+    }
+
+    fn resume(&mut self, _: &dyn RawWindow, _: u32, _: u32) {}
 
     fn suspend(&mut self) {}
 }
@@ -0,0 +1,271 @@
+use crate::Camera;
+
+/// A rectangle that limits where a [Renderer](crate::Renderer) draws, pushed with
+/// [Renderer::push_clip_rect](crate::Renderer::push_clip_rect).
+///
+/// The rectangles are given as `[x, y, width, height]`, from the top left corner of the render
+/// target, with y growing downwards.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ClipRect {
+    /// A rectangle in pixels of the render target.
+    Pixels([f32; 4]),
+    /// A rectangle in fractions of the size of the render target, where `[0.0, 0.0, 1.0, 1.0]`
+    /// covers all of it.
+    Relative([f32; 4]),
+}
+impl ClipRect {
+    /// The smallest [ClipRect::Relative] that contains the rectangle `[x, y, width, height]` in
+    /// world space, as seen by the given camera. If the camera is rotated, the clipped area is the
+    /// bounding box of the rotated rectangle.
+    pub fn world(camera: &mut Camera, rect: [f32; 4]) -> Self {
+        let [x, y, width, height] = rect;
+        let view = *camera.view();
+        let corners = [
+            [x, y],
+            [x + width, y],
+            [x, y + height],
+            [x + width, y + height],
+        ]
+        .map(|[x, y]| {
+            // the view maps to normalized device coordinates, with y downwards.
+            let ndc_x = view[0] * x + view[1] * y + view[2];
+            let ndc_y = view[3] * x + view[4] * y + view[5];
+            [(ndc_x + 1.0) / 2.0, (ndc_y + 1.0) / 2.0]
+        });
+        let mut min = corners[0];
+        let mut max = corners[0];
+        for [x, y] in corners {
+            min = [min[0].min(x), min[1].min(y)];
+            max = [max[0].max(x), max[1].max(y)];
+        }
+        ClipRect::Relative([min[0], min[1], max[0] - min[0], max[1] - min[1]])
+    }
+
+    /// The rectangle `[x, y, width, height]` in whole pixels of a render target of the given size,
+    /// rounded outwards and clamped to the target.
+    #[cfg(any(test, feature = "opengl", feature = "webgl"))]
+    pub(crate) fn to_pixels(self, width: u32, height: u32) -> [i32; 4] {
+        let [x, y, w, h] = match self {
+            ClipRect::Pixels(rect) => rect,
+            ClipRect::Relative([x, y, w, h]) => {
+                let (width, height) = (width as f32, height as f32);
+                [x * width, y * height, w * width, h * height]
+            }
+        };
+        let left = x.floor().clamp(0.0, width as f32) as i32;
+        let top = y.floor().clamp(0.0, height as f32) as i32;
+        let right = (x + w).ceil().clamp(0.0, width as f32) as i32;
+        let bottom = (y + h).ceil().clamp(0.0, height as f32) as i32;
+        [left, top, (right - left).max(0), (bottom - top).max(0)]
+    }
+}
+
+/// The clip rectangles and masks pushed in a Renderer.
+#[cfg(any(test, feature = "opengl", feature = "webgl"))]
+#[derive(Debug, Default)]
+pub(crate) struct ClipStack {
+    /// The pushed clip rectangles, in pixels, each one intersected with the previous ones.
+    rects: Vec<[i32; 4]>,
+    /// The number of masks pushed. The pixels inside all of them have this value in the stencil
+    /// buffer.
+    masks: u8,
+    /// If the shapes of a mask are being drawn, between `begin_mask` and `end_mask`.
+    drawing_mask: bool,
+}
+#[cfg(any(test, feature = "opengl", feature = "webgl"))]
+impl ClipStack {
+    /// Push the intersection of `rect` with the current clip rectangle, and return it.
+    pub fn push(&mut self, rect: [i32; 4]) -> [i32; 4] {
+        let rect = match self.current() {
+            Some([x, y, w, h]) => {
+                let left = rect[0].max(x);
+                let top = rect[1].max(y);
+                let right = (rect[0] + rect[2]).min(x + w);
+                let bottom = (rect[1] + rect[3]).min(y + h);
+                [left, top, (right - left).max(0), (bottom - top).max(0)]
+            }
+            None => rect,
+        };
+        self.rects.push(rect);
+        rect
+    }
+
+    /// Pop the last clip rectangle. Returns false if there was none.
+    pub fn pop(&mut self) -> bool {
+        self.rects.pop().is_some()
+    }
+
+    /// The current clip rectangle, or None if the drawing is not clipped by a rectangle.
+    pub fn current(&self) -> Option<[i32; 4]> {
+        self.rects.last().copied()
+    }
+
+    /// The number of masks pushed.
+    pub fn masks(&self) -> u8 {
+        self.masks
+    }
+
+    /// Start drawing a mask. Returns false, and logs why, if a mask cannot be started.
+    pub fn begin_mask(&mut self) -> bool {
+        if self.drawing_mask {
+            log::warn!("begin_mask called while drawing a mask");
+            return false;
+        }
+        if self.masks == u8::MAX {
+            log::warn!("too many masks pushed, the maximum is {}", u8::MAX);
+            return false;
+        }
+        self.drawing_mask = true;
+        true
+    }
+
+    /// Finish drawing a mask, and push it. Returns false, and logs why, if no mask was started.
+    pub fn end_mask(&mut self) -> bool {
+        if !self.drawing_mask {
+            log::warn!("end_mask called without begin_mask");
+            return false;
+        }
+        self.drawing_mask = false;
+        self.masks += 1;
+        true
+    }
+
+    /// Pop the last mask. Returns false, and logs why, if there is no mask to pop.
+    pub fn pop_mask(&mut self) -> bool {
+        if self.drawing_mask {
+            log::warn!("pop_mask called while drawing a mask");
+            return false;
+        }
+        if self.masks == 0 {
+            log::warn!("pop_mask called without a pushed mask");
+            return false;
+        }
+        self.masks -= 1;
+        true
+    }
+
+    /// If no clip rectangle or mask is pushed, and no mask is being drawn.
+    pub fn is_empty(&self) -> bool {
+        self.rects.is_empty() && self.masks == 0 && !self.drawing_mask
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_rect_close(a: ClipRect, b: [f32; 4]) {
+        let ClipRect::Relative(a) = a else {
+            panic!("{:?} is not relative", a);
+        };
+        assert!(
+            (0..4).all(|i| (a[i] - b[i]).abs() < 1e-5),
+            "{:?} != {:?}",
+            a,
+            b
+        );
+    }
+
+    #[test]
+    fn world() {
+        let mut camera = Camera::new(800, 600, 6.0);
+        assert_rect_close(
+            ClipRect::world(&mut camera, [-4.0, -3.0, 8.0, 6.0]),
+            [0.0, 0.0, 1.0, 1.0],
+        );
+        assert_rect_close(
+            ClipRect::world(&mut camera, [0.0, 0.0, 2.0, 1.5]),
+            [0.5, 0.5, 0.25, 0.25],
+        );
+        camera.set_position(4.0, 3.0);
+        assert_rect_close(
+            ClipRect::world(&mut camera, [0.0, 0.0, 2.0, 1.5]),
+            [0.0, 0.0, 0.25, 0.25],
+        );
+    }
+
+    #[test]
+    fn to_pixels() {
+        assert_eq!(
+            ClipRect::Pixels([10.0, 20.0, 30.0, 40.0]).to_pixels(800, 600),
+            [10, 20, 30, 40]
+        );
+        assert_eq!(
+            ClipRect::Relative([0.25, 0.5, 0.5, 0.25]).to_pixels(800, 600),
+            [200, 300, 400, 150]
+        );
+        // rounded outwards.
+        assert_eq!(
+            ClipRect::Pixels([10.5, 20.25, 9.0, 9.5]).to_pixels(800, 600),
+            [10, 20, 10, 10]
+        );
+        // clamped to the target.
+        assert_eq!(
+            ClipRect::Pixels([-10.0, 590.0, 100.0, 100.0]).to_pixels(800, 600),
+            [0, 590, 90, 10]
+        );
+        assert_eq!(
+            ClipRect::Pixels([900.0, 10.0, 100.0, 100.0]).to_pixels(800, 600),
+            [800, 10, 0, 100]
+        );
+        assert_eq!(
+            ClipRect::Pixels([10.0, 10.0, -5.0, 10.0]).to_pixels(800, 600),
+            [10, 10, 0, 10]
+        );
+    }
+
+    #[test]
+    fn push_intersects() {
+        let mut stack = ClipStack::default();
+        assert!(stack.is_empty());
+        assert_eq!(stack.current(), None);
+        assert_eq!(stack.push([10, 10, 100, 100]), [10, 10, 100, 100]);
+        assert_eq!(stack.push([50, 0, 100, 50]), [50, 10, 60, 40]);
+        // disjoint rectangles intersect in a empty one.
+        assert_eq!(stack.push([0, 0, 20, 20]), [50, 10, 0, 10]);
+        assert_eq!(stack.push([200, 200, 10, 10]), [200, 200, 0, 0]);
+
+        assert!(stack.pop());
+        assert!(stack.pop());
+        assert_eq!(stack.current(), Some([50, 10, 60, 40]));
+        assert!(stack.pop());
+        assert!(stack.pop());
+        assert!(!stack.pop());
+        assert!(stack.is_empty());
+    }
+
+    #[test]
+    fn masks() {
+        let mut stack = ClipStack::default();
+        assert!(!stack.end_mask());
+        assert!(!stack.pop_mask());
+
+        assert!(stack.begin_mask());
+        assert!(!stack.is_empty());
+        assert!(!stack.begin_mask());
+        assert!(!stack.pop_mask());
+        assert!(stack.end_mask());
+        assert_eq!(stack.masks(), 1);
+
+        assert!(stack.begin_mask());
+        assert!(stack.end_mask());
+        assert_eq!(stack.masks(), 2);
+        assert!(stack.pop_mask());
+        assert!(stack.pop_mask());
+        assert_eq!(stack.masks(), 0);
+        assert!(stack.is_empty());
+    }
+
+    #[test]
+    fn mask_limit() {
+        let mut stack = ClipStack::default();
+        for _ in 0..u8::MAX {
+            assert!(stack.begin_mask());
+            assert!(stack.end_mask());
+        }
+        assert_eq!(stack.masks(), u8::MAX);
+        assert!(!stack.begin_mask());
+        assert!(stack.pop_mask());
+        assert!(stack.begin_mask());
+    }
+}
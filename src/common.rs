@@ -1,6 +1,6 @@
 use std::f32::consts::PI;
 
-use crate::TextureId;
+use crate::{MaterialId, TextureId};
 
 #[derive(Clone, Debug)]
 pub struct SpriteInstance {
@@ -10,6 +10,8 @@ pub struct SpriteInstance {
     pub color: [u8; 4],
     pub pos: [f32; 2],
     pub texture: TextureId,
+    /// The material used to draw the sprite.
+    pub material: MaterialId,
 }
 impl Default for SpriteInstance {
     fn default() -> Self {
@@ -20,6 +22,7 @@ impl Default for SpriteInstance {
             color: [255; 4],
             pos: [0.0; 2],
             texture: TextureId::default(),
+            material: MaterialId::default(),
         }
     }
 }
@@ -41,6 +44,7 @@ impl SpriteInstance {
             color: [0xff; 4],
             pos: [x, y],
             texture,
+            material: MaterialId::default(),
         }
     }
 
@@ -62,6 +66,7 @@ impl SpriteInstance {
             color: [0xff; 4],
             pos: [x, y],
             texture,
+            material: MaterialId::default(),
         }
     }
 
@@ -157,6 +162,19 @@ impl SpriteInstance {
         self.uv_rect = rect;
         self
     }
+
+    /// set the material used to draw the sprite.
+    #[inline]
+    pub fn set_material(&mut self, material: MaterialId) {
+        self.material = material;
+    }
+
+    /// set the material of the sprite, in a functional way (get owership of the value, and return it modified).
+    #[inline]
+    pub fn with_material(mut self, material: MaterialId) -> Self {
+        self.material = material;
+        self
+    }
 }
 
 /// The camera encapsulates the view matrix, providing methods to move,
@@ -188,7 +206,6 @@ impl Camera {
         }
     }
 
-    #[allow(dead_code)]
     pub(crate) fn view(&mut self) -> &[f32; 9] {
         if self.dirty {
             self.dirty = false;
@@ -317,6 +334,12 @@ impl Camera {
         self.dirty = true;
     }
 
+    #[inline]
+    /// get the angle of rotation of the view, in counterclockwise radians.
+    pub fn view_rotation(&self) -> f32 {
+        self.rotation
+    }
+
     #[inline]
     /// Set the angle of rotation of the view, in counterclockwise radians.
     pub fn set_view_rotation(&mut self, radians: f32) {
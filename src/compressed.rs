@@ -0,0 +1,163 @@
+//! Loading of compressed textures from KTX2 and DDS containers.
+
+mod container;
+mod decode;
+
+use crate::{SpriteRender, Texture, TextureError, TextureFilter, TextureFormat, TextureId};
+
+/// Error generated when parsing a KTX2 or DDS container.
+#[derive(Debug)]
+pub enum ContainerError {
+    /// The data does not start with the identifier of the container.
+    InvalidIdentifier,
+    /// The header is invalid, or the data is shorter than the header describes.
+    InvalidHeader,
+    /// The pixel format of the container has no equivalent [TextureFormat].
+    UnsupportedFormat,
+    /// The container is a array, cubemap or 3D texture.
+    UnsupportedDimension,
+    /// The KTX2 container uses supercompression, like Basis Universal or Zstandard.
+    Supercompressed,
+}
+impl std::fmt::Display for ContainerError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ContainerError::InvalidIdentifier => write!(f, "invalid file identifier"),
+            ContainerError::InvalidHeader => write!(f, "invalid or truncated header"),
+            ContainerError::UnsupportedFormat => write!(f, "unsupported pixel format"),
+            ContainerError::UnsupportedDimension => {
+                write!(f, "array, cubemap and 3D textures are not supported")
+            }
+            ContainerError::Supercompressed => write!(f, "supercompression is not supported"),
+        }
+    }
+}
+impl std::error::Error for ContainerError {}
+
+/// The image of a compressed texture, loaded from a container.
+///
+/// Only the base level of the image is loaded, the other mipmap levels are ignored.
+#[derive(Clone, Debug)]
+pub struct CompressedImage {
+    /// The width of the image, in pixels.
+    pub width: u32,
+    /// The height of the image, in pixels.
+    pub height: u32,
+    /// The format of `data`.
+    pub format: TextureFormat,
+    /// The blocks of the image, in the given `format`.
+    pub data: Vec<u8>,
+}
+impl CompressedImage {
+    /// Load the base level of a KTX2 container.
+    pub fn from_ktx2(bytes: &[u8]) -> Result<Self, ContainerError> {
+        container::parse_ktx2(bytes)
+    }
+
+    /// Load the base level of a DDS container.
+    pub fn from_dds(bytes: &[u8]) -> Result<Self, ContainerError> {
+        container::parse_dds(bytes)
+    }
+
+    /// Load the base level of a KTX2 or DDS container, detecting it by its identifier.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ContainerError> {
+        if bytes.starts_with(&container::KTX2_IDENTIFIER) {
+            Self::from_ktx2(bytes)
+        } else if bytes.starts_with(container::DDS_MAGIC) {
+            Self::from_dds(bytes)
+        } else {
+            Err(ContainerError::InvalidIdentifier)
+        }
+    }
+
+    /// Decode the image to RGBA8888 pixels in the CPU.
+    ///
+    /// Returns None if the format cannot be decoded. BC1, BC2, BC3 and ETC2 are supported, ASTC is
+    /// not.
+    pub fn decode_rgba(&self) -> Option<Vec<u8>> {
+        let (width, height) = (self.width, self.height);
+        if Some(self.data.len()) != self.format.data_len(width, height) {
+            return None;
+        }
+        if !self.format.is_compressed() {
+            return (self.format == TextureFormat::Rgba8888).then(|| self.data.clone());
+        }
+        let decode_block: fn(&[u8], &mut [[u8; 4]; 16]) = match self.format {
+            TextureFormat::Bc1 => decode::decode_bc1,
+            TextureFormat::Bc2 => decode::decode_bc2,
+            TextureFormat::Bc3 => decode::decode_bc3,
+            TextureFormat::Etc2Rgb8 => decode::decode_etc2_rgb8,
+            TextureFormat::Etc2Rgba8 => decode::decode_etc2_rgba8,
+            _ => return None,
+        };
+        let (_, _, block_bytes) = self.format.block_size();
+        let blocks_x = width.div_ceil(4);
+
+        let mut rgba = vec![0u8; TextureFormat::Rgba8888.data_len(width, height)?];
+        let mut pixels = [[0u8; 4]; 16];
+        for (i, block) in self.data.chunks_exact(block_bytes as usize).enumerate() {
+            decode_block(block, &mut pixels);
+            let bx = (i as u32 % blocks_x) * 4;
+            let by = (i as u32 / blocks_x) * 4;
+            for (j, pixel) in pixels.iter().enumerate() {
+                let x = bx + j as u32 % 4;
+                let y = by + j as u32 / 4;
+                if x < width && y < height {
+                    let k = (y * width + x) as usize * 4;
+                    rgba[k..k + 4].copy_from_slice(pixel);
+                }
+            }
+        }
+        Some(rgba)
+    }
+
+    /// Create a texture from this image in the given [SpriteRender].
+    ///
+    /// If the format of the image is not supported, the image is decoded to RGBA8888 in the CPU,
+    /// see [CompressedImage::decode_rgba]. Returns [TextureError::UnsupportedFormat] if it cannot
+    /// be decoded.
+    pub fn create(
+        &self,
+        sprite_render: &mut dyn SpriteRender,
+        filter: TextureFilter,
+    ) -> Result<TextureId, TextureError> {
+        Self::create_best(std::slice::from_ref(self), sprite_render, filter)
+    }
+
+    /// Create a texture from the first of the given images whose format is supported by the
+    /// [SpriteRender].
+    ///
+    /// The images are usually the same texture compressed in different formats, in order of
+    /// preference. If none is supported, the first image that can be decoded in the CPU is used,
+    /// as RGBA8888.
+    pub fn create_best(
+        images: &[CompressedImage],
+        sprite_render: &mut dyn SpriteRender,
+        filter: TextureFilter,
+    ) -> Result<TextureId, TextureError> {
+        if let Some(image) = images
+            .iter()
+            .find(|x| sprite_render.supports_texture_format(x.format))
+        {
+            return Texture::new(image.width, image.height)
+                .format(image.format)
+                .filter(filter)
+                .data(&image.data)
+                .create(sprite_render);
+        }
+        for image in images {
+            if let Some(rgba) = image.decode_rgba() {
+                log::info!(
+                    "texture format {:?} is not supported, decoding it in the CPU",
+                    image.format
+                );
+                return Texture::new(image.width, image.height)
+                    .format(TextureFormat::Rgba8888)
+                    .filter(filter)
+                    .data(&rgba)
+                    .create(sprite_render);
+            }
+        }
+        Err(TextureError::UnsupportedFormat)
+    }
+}
@@ -0,0 +1,338 @@
+use std::convert::TryFrom;
+
+use super::{CompressedImage, ContainerError};
+use crate::TextureFormat;
+
+pub(super) const KTX2_IDENTIFIER: [u8; 12] = [
+    0xAB, 0x4B, 0x54, 0x58, 0x20, 0x32, 0x30, 0xBB, 0x0D, 0x0A, 0x1A, 0x0A,
+];
+pub(super) const DDS_MAGIC: &[u8] = b"DDS ";
+
+fn read_u32(bytes: &[u8], offset: usize) -> Result<u32, ContainerError> {
+    bytes
+        .get(offset..offset + 4)
+        .map(|x| u32::from_le_bytes([x[0], x[1], x[2], x[3]]))
+        .ok_or(ContainerError::InvalidHeader)
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> Result<u64, ContainerError> {
+    let low = read_u32(bytes, offset)? as u64;
+    let high = read_u32(bytes, offset + 4)? as u64;
+    Ok(low | high << 32)
+}
+
+/// Get `len` bytes of `bytes`, starting at `offset`.
+fn read_data(bytes: &[u8], offset: u64, len: usize) -> Result<Vec<u8>, ContainerError> {
+    let offset = usize::try_from(offset).map_err(|_| ContainerError::InvalidHeader)?;
+    let end = offset
+        .checked_add(len)
+        .ok_or(ContainerError::InvalidHeader)?;
+    bytes
+        .get(offset..end)
+        .map(|x| x.to_vec())
+        .ok_or(ContainerError::InvalidHeader)
+}
+
+/// Map a `VkFormat` to a [TextureFormat]. The sRGB formats are mapped to their UNORM equivalent.
+fn format_from_vk(vk_format: u32) -> Option<TextureFormat> {
+    Some(match vk_format {
+        // VK_FORMAT_R8G8B8A8_UNORM, VK_FORMAT_R8G8B8A8_SRGB
+        37 | 43 => TextureFormat::Rgba8888,
+        // VK_FORMAT_BC1_RGB(A)_UNORM_BLOCK, VK_FORMAT_BC1_RGB(A)_SRGB_BLOCK
+        131..=134 => TextureFormat::Bc1,
+        // VK_FORMAT_BC2_UNORM_BLOCK, VK_FORMAT_BC2_SRGB_BLOCK
+        135 | 136 => TextureFormat::Bc2,
+        // VK_FORMAT_BC3_UNORM_BLOCK, VK_FORMAT_BC3_SRGB_BLOCK
+        137 | 138 => TextureFormat::Bc3,
+        // VK_FORMAT_ETC2_R8G8B8_UNORM_BLOCK, VK_FORMAT_ETC2_R8G8B8_SRGB_BLOCK
+        147 | 148 => TextureFormat::Etc2Rgb8,
+        // VK_FORMAT_ETC2_R8G8B8A8_UNORM_BLOCK, VK_FORMAT_ETC2_R8G8B8A8_SRGB_BLOCK
+        151 | 152 => TextureFormat::Etc2Rgba8,
+        // VK_FORMAT_ASTC_4x4_UNORM_BLOCK, VK_FORMAT_ASTC_4x4_SRGB_BLOCK
+        157 | 158 => TextureFormat::Astc4x4,
+        _ => return None,
+    })
+}
+
+/// Parse a KTX2 container.
+///
+/// See <https://registry.khronos.org/KTX/specs/2.0/ktxspec.v2.html>.
+pub(super) fn parse_ktx2(bytes: &[u8]) -> Result<CompressedImage, ContainerError> {
+    if !bytes.starts_with(&KTX2_IDENTIFIER) {
+        return Err(ContainerError::InvalidIdentifier);
+    }
+
+    let vk_format = read_u32(bytes, 12)?;
+    let width = read_u32(bytes, 20)?;
+    let height = read_u32(bytes, 24)?;
+    let depth = read_u32(bytes, 28)?;
+    let layer_count = read_u32(bytes, 32)?;
+    let face_count = read_u32(bytes, 36)?;
+    let supercompression_scheme = read_u32(bytes, 44)?;
+
+    if supercompression_scheme != 0 {
+        return Err(ContainerError::Supercompressed);
+    }
+    if depth > 1 || layer_count > 1 || face_count != 1 || height == 0 {
+        return Err(ContainerError::UnsupportedDimension);
+    }
+    if width == 0 {
+        return Err(ContainerError::InvalidHeader);
+    }
+    // VK_FORMAT_UNDEFINED is used by Basis Universal.
+    let format = format_from_vk(vk_format).ok_or(ContainerError::UnsupportedFormat)?;
+
+    // The level index starts after the header and the index of the data format descriptor,
+    // key/value data and supercompression global data. Level 0 is the first one.
+    let level_offset = read_u64(bytes, 80)?;
+    let level_len = read_u64(bytes, 88)?;
+    let data_len = format
+        .data_len(width, height)
+        .ok_or(ContainerError::InvalidHeader)?;
+    if level_len != data_len as u64 {
+        return Err(ContainerError::InvalidHeader);
+    }
+
+    Ok(CompressedImage {
+        width,
+        height,
+        format,
+        data: read_data(bytes, level_offset, data_len)?,
+    })
+}
+
+/// Parse a DDS container.
+///
+/// See <https://learn.microsoft.com/en-us/windows/win32/direct3ddds/dx-graphics-dds-pguide>.
+pub(super) fn parse_dds(bytes: &[u8]) -> Result<CompressedImage, ContainerError> {
+    if !bytes.starts_with(DDS_MAGIC) {
+        return Err(ContainerError::InvalidIdentifier);
+    }
+
+    const DDSD_DEPTH: u32 = 0x800000;
+    const DDPF_FOURCC: u32 = 0x4;
+    const DDPF_RGB: u32 = 0x40;
+    const DDSCAPS2_CUBEMAP: u32 = 0x200;
+
+    // the header starts after the magic number.
+    let header_size = read_u32(bytes, 4)?;
+    let flags = read_u32(bytes, 8)?;
+    let height = read_u32(bytes, 12)?;
+    let width = read_u32(bytes, 16)?;
+    let pixel_format_flags = read_u32(bytes, 80)?;
+    let four_cc = read_u32(bytes, 84)?;
+    let rgb_bit_count = read_u32(bytes, 88)?;
+    let masks = [
+        read_u32(bytes, 92)?,
+        read_u32(bytes, 96)?,
+        read_u32(bytes, 100)?,
+        read_u32(bytes, 104)?,
+    ];
+    let caps2 = read_u32(bytes, 112)?;
+
+    if header_size != 124 || width == 0 || height == 0 {
+        return Err(ContainerError::InvalidHeader);
+    }
+    if flags & DDSD_DEPTH != 0 || caps2 & DDSCAPS2_CUBEMAP != 0 {
+        return Err(ContainerError::UnsupportedDimension);
+    }
+
+    let mut data_offset = 128;
+    let format = if pixel_format_flags & DDPF_FOURCC != 0 {
+        match &four_cc.to_le_bytes() {
+            b"DXT1" => TextureFormat::Bc1,
+            b"DXT2" | b"DXT3" => TextureFormat::Bc2,
+            b"DXT4" | b"DXT5" => TextureFormat::Bc3,
+            b"DX10" => {
+                let dxgi_format = read_u32(bytes, 128)?;
+                let resource_dimension = read_u32(bytes, 132)?;
+                let array_size = read_u32(bytes, 140)?;
+                // D3D10_RESOURCE_DIMENSION_TEXTURE2D
+                if resource_dimension != 3 || array_size > 1 {
+                    return Err(ContainerError::UnsupportedDimension);
+                }
+                data_offset += 20;
+                match dxgi_format {
+                    // DXGI_FORMAT_R8G8B8A8_UNORM(_SRGB)
+                    28 | 29 => TextureFormat::Rgba8888,
+                    // DXGI_FORMAT_BC1_UNORM(_SRGB)
+                    71 | 72 => TextureFormat::Bc1,
+                    // DXGI_FORMAT_BC2_UNORM(_SRGB)
+                    74 | 75 => TextureFormat::Bc2,
+                    // DXGI_FORMAT_BC3_UNORM(_SRGB)
+                    77 | 78 => TextureFormat::Bc3,
+                    _ => return Err(ContainerError::UnsupportedFormat),
+                }
+            }
+            _ => return Err(ContainerError::UnsupportedFormat),
+        }
+    } else if pixel_format_flags & DDPF_RGB != 0
+        && rgb_bit_count == 32
+        && masks == [0xFF, 0xFF00, 0xFF0000, 0xFF000000]
+    {
+        TextureFormat::Rgba8888
+    } else {
+        return Err(ContainerError::UnsupportedFormat);
+    };
+
+    let data_len = format
+        .data_len(width, height)
+        .ok_or(ContainerError::InvalidHeader)?;
+    Ok(CompressedImage {
+        width,
+        height,
+        format,
+        data: read_data(bytes, data_offset, data_len)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BC1_BLOCK: [u8; 8] = [0x00, 0xF8, 0x1F, 0x00, 0xE4, 0, 0, 0];
+
+    fn put_u32(bytes: &mut [u8], offset: usize, value: u32) {
+        bytes[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+    }
+
+    /// A KTX2 container with a single level, right after the level index.
+    fn ktx2(vk_format: u32, width: u32, height: u32, data: &[u8]) -> Vec<u8> {
+        let mut bytes = vec![0; 104];
+        bytes[..12].copy_from_slice(&KTX2_IDENTIFIER);
+        put_u32(&mut bytes, 12, vk_format);
+        put_u32(&mut bytes, 20, width);
+        put_u32(&mut bytes, 24, height);
+        put_u32(&mut bytes, 36, 1);
+        put_u32(&mut bytes, 40, 1);
+        bytes[80..88].copy_from_slice(&104u64.to_le_bytes());
+        bytes[88..96].copy_from_slice(&(data.len() as u64).to_le_bytes());
+        bytes.extend_from_slice(data);
+        bytes
+    }
+
+    /// A DDS container with the given four character code.
+    fn dds(four_cc: &[u8; 4], width: u32, height: u32, data: &[u8]) -> Vec<u8> {
+        let mut bytes = vec![0; 128];
+        bytes[..4].copy_from_slice(DDS_MAGIC);
+        put_u32(&mut bytes, 4, 124);
+        put_u32(&mut bytes, 12, height);
+        put_u32(&mut bytes, 16, width);
+        put_u32(&mut bytes, 76, 32);
+        put_u32(&mut bytes, 80, 0x4);
+        bytes[84..88].copy_from_slice(four_cc);
+        bytes.extend_from_slice(data);
+        bytes
+    }
+
+    #[test]
+    fn ktx2_bc1() {
+        let image = parse_ktx2(&ktx2(131, 4, 4, &BC1_BLOCK)).unwrap();
+        assert_eq!((image.width, image.height), (4, 4));
+        assert_eq!(image.format, TextureFormat::Bc1);
+        assert_eq!(image.data, BC1_BLOCK);
+        let rgba = image.decode_rgba().unwrap();
+        assert_eq!(rgba[..8], [255, 0, 0, 255, 0, 0, 255, 255]);
+    }
+
+    #[test]
+    fn ktx2_invalid() {
+        let valid = ktx2(131, 4, 4, &BC1_BLOCK);
+
+        let mut bad_magic = valid.clone();
+        bad_magic[1] = b'X';
+        assert!(matches!(
+            parse_ktx2(&bad_magic),
+            Err(ContainerError::InvalidIdentifier)
+        ));
+
+        for len in [12, 60, 100, valid.len() - 1] {
+            assert!(matches!(
+                parse_ktx2(&valid[..len]),
+                Err(ContainerError::InvalidHeader)
+            ));
+        }
+
+        let mut far_offset = valid.clone();
+        far_offset[80..88].copy_from_slice(&(u64::MAX - 2).to_le_bytes());
+        assert!(matches!(
+            parse_ktx2(&far_offset),
+            Err(ContainerError::InvalidHeader)
+        ));
+
+        let mut wrong_length = valid.clone();
+        wrong_length[88..96].copy_from_slice(&16u64.to_le_bytes());
+        assert!(matches!(
+            parse_ktx2(&wrong_length),
+            Err(ContainerError::InvalidHeader)
+        ));
+
+        let mut supercompressed = valid.clone();
+        put_u32(&mut supercompressed, 44, 2);
+        assert!(matches!(
+            parse_ktx2(&supercompressed),
+            Err(ContainerError::Supercompressed)
+        ));
+
+        assert!(matches!(
+            parse_ktx2(&ktx2(0, 4, 4, &BC1_BLOCK)),
+            Err(ContainerError::UnsupportedFormat)
+        ));
+    }
+
+    #[test]
+    fn dds_bc1_cropped() {
+        let image = parse_dds(&dds(b"DXT1", 2, 3, &BC1_BLOCK)).unwrap();
+        assert_eq!((image.width, image.height), (2, 3));
+        assert_eq!(image.format, TextureFormat::Bc1);
+        // the pixels outside of the image are dropped.
+        let rgba = image.decode_rgba().unwrap();
+        assert_eq!(rgba.len(), 2 * 3 * 4);
+        assert_eq!(rgba[..8], [255, 0, 0, 255, 0, 0, 255, 255]);
+        assert_eq!(rgba[8..12], [255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn dds_uncompressed_rgba() {
+        let mut bytes = dds(&[0; 4], 1, 1, &[1, 2, 3, 4]);
+        put_u32(&mut bytes, 80, 0x40);
+        put_u32(&mut bytes, 88, 32);
+        for (i, &mask) in [0xFF, 0xFF00, 0xFF0000, 0xFF000000].iter().enumerate() {
+            put_u32(&mut bytes, 92 + 4 * i, mask);
+        }
+        let image = parse_dds(&bytes).unwrap();
+        assert_eq!(image.format, TextureFormat::Rgba8888);
+        assert_eq!(image.decode_rgba().unwrap(), [1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn dds_invalid() {
+        let valid = dds(b"DXT5", 4, 4, &[0; 16]);
+        assert!(parse_dds(&valid).is_ok());
+
+        let mut bad_magic = valid.clone();
+        bad_magic[0] = b'X';
+        assert!(matches!(
+            parse_dds(&bad_magic),
+            Err(ContainerError::InvalidIdentifier)
+        ));
+
+        for len in [4, 64, 127, valid.len() - 1] {
+            assert!(matches!(
+                parse_dds(&valid[..len]),
+                Err(ContainerError::InvalidHeader)
+            ));
+        }
+
+        // the length of the data does not fit in a usize.
+        assert!(matches!(
+            parse_dds(&dds(b"DXT5", u32::MAX, u32::MAX, &[0; 16])),
+            Err(ContainerError::InvalidHeader)
+        ));
+
+        assert!(matches!(
+            parse_dds(&dds(b"ATI2", 4, 4, &[0; 16])),
+            Err(ContainerError::UnsupportedFormat)
+        ));
+    }
+}
@@ -0,0 +1,396 @@
+//! Decoders of compressed blocks to RGBA8888.
+//!
+//! Each function decodes a single 4x4 block, writing the pixels in row-major order.
+
+use std::convert::TryInto;
+
+/// Expand a RGB565 color to RGBA8888.
+fn rgb565(color: u16) -> [u8; 4] {
+    let r = (color >> 11) & 0x1F;
+    let g = (color >> 5) & 0x3F;
+    let b = color & 0x1F;
+    [
+        (r << 3 | r >> 2) as u8,
+        (g << 2 | g >> 4) as u8,
+        (b << 3 | b >> 2) as u8,
+        255,
+    ]
+}
+
+/// Interpolate between two colors, as `(a * wa + b * wb) / (wa + wb)`.
+fn mix(a: [u8; 4], b: [u8; 4], wa: u32, wb: u32) -> [u8; 4] {
+    let mut out = [0; 4];
+    for i in 0..4 {
+        out[i] = ((a[i] as u32 * wa + b[i] as u32 * wb) / (wa + wb)) as u8;
+    }
+    out
+}
+
+/// Decode the 8 bytes color block of BC1, BC2 and BC3. BC2 and BC3 always use the 4 colors mode.
+fn decode_bc_color(block: &[u8], pixels: &mut [[u8; 4]; 16], always_four_colors: bool) {
+    let c0 = u16::from_le_bytes([block[0], block[1]]);
+    let c1 = u16::from_le_bytes([block[2], block[3]]);
+    let (a, b) = (rgb565(c0), rgb565(c1));
+    let colors = if c0 > c1 || always_four_colors {
+        [a, b, mix(a, b, 2, 1), mix(a, b, 1, 2)]
+    } else {
+        [a, b, mix(a, b, 1, 1), [0, 0, 0, 0]]
+    };
+    let indices = u32::from_le_bytes([block[4], block[5], block[6], block[7]]);
+    for (i, pixel) in pixels.iter_mut().enumerate() {
+        *pixel = colors[(indices >> (2 * i) & 0x3) as usize];
+    }
+}
+
+pub(super) fn decode_bc1(block: &[u8], pixels: &mut [[u8; 4]; 16]) {
+    decode_bc_color(block, pixels, false);
+}
+
+pub(super) fn decode_bc2(block: &[u8], pixels: &mut [[u8; 4]; 16]) {
+    decode_bc_color(&block[8..16], pixels, true);
+    let alpha = u64::from_le_bytes(block[0..8].try_into().unwrap());
+    for (i, pixel) in pixels.iter_mut().enumerate() {
+        pixel[3] = (alpha >> (4 * i) & 0xF) as u8 * 17;
+    }
+}
+
+pub(super) fn decode_bc3(block: &[u8], pixels: &mut [[u8; 4]; 16]) {
+    decode_bc_color(&block[8..16], pixels, true);
+    let (a0, a1) = (block[0] as u32, block[1] as u32);
+    let mut alphas = [0u8; 8];
+    alphas[0] = a0 as u8;
+    alphas[1] = a1 as u8;
+    if a0 > a1 {
+        for i in 1..7 {
+            alphas[i as usize + 1] = ((a0 * (7 - i) + a1 * i) / 7) as u8;
+        }
+    } else {
+        for i in 1..5 {
+            alphas[i as usize + 1] = ((a0 * (5 - i) + a1 * i) / 5) as u8;
+        }
+        alphas[6] = 0;
+        alphas[7] = 255;
+    }
+    let mut indices = [0u8; 8];
+    indices[..6].copy_from_slice(&block[2..8]);
+    let indices = u64::from_le_bytes(indices);
+    for (i, pixel) in pixels.iter_mut().enumerate() {
+        pixel[3] = alphas[(indices >> (3 * i) & 0x7) as usize];
+    }
+}
+
+/// The modifier tables of ETC1 and ETC2, indexed by the table codeword.
+const ETC_MODIFIERS: [[i32; 2]; 8] = [
+    [2, 8],
+    [5, 17],
+    [9, 29],
+    [13, 42],
+    [18, 60],
+    [24, 80],
+    [33, 106],
+    [47, 183],
+];
+
+/// The distances of the T and H modes of ETC2.
+const ETC_DISTANCES: [i32; 8] = [3, 6, 11, 16, 23, 32, 41, 64];
+
+fn extend4(x: u64) -> i32 {
+    (x << 4 | x) as i32
+}
+
+fn extend5(x: u64) -> i32 {
+    (x << 3 | x >> 2) as i32
+}
+
+fn extend6(x: u64) -> i32 {
+    (x << 2 | x >> 4) as i32
+}
+
+fn extend7(x: u64) -> i32 {
+    (x << 1 | x >> 6) as i32
+}
+
+fn clamp(c: [i32; 3]) -> [u8; 4] {
+    [
+        c[0].clamp(0, 255) as u8,
+        c[1].clamp(0, 255) as u8,
+        c[2].clamp(0, 255) as u8,
+        255,
+    ]
+}
+
+fn add(c: [i32; 3], d: i32) -> [u8; 4] {
+    clamp([c[0] + d, c[1] + d, c[2] + d])
+}
+
+/// The 2 bits index of the pixel `(x, y)` in the ETC1 and ETC2 blocks, whose pixels are indexed
+/// in column-major order.
+fn etc_index(bits: u64, x: usize, y: usize) -> usize {
+    let i = x * 4 + y;
+    ((bits >> (16 + i) & 1) << 1 | (bits >> i & 1)) as usize
+}
+
+pub(super) fn decode_etc2_rgb8(block: &[u8], pixels: &mut [[u8; 4]; 16]) {
+    let bits = u64::from_be_bytes(block[0..8].try_into().unwrap());
+    let diff = bits >> 33 & 1 == 1;
+
+    let (base1, base2) = if diff {
+        let r = (bits >> 59 & 0x1F) as i32;
+        let g = (bits >> 51 & 0x1F) as i32;
+        let b = (bits >> 43 & 0x1F) as i32;
+        // the deltas are 3 bits signed integers.
+        let dr = ((bits >> 56 & 0x7) as i32) << 29 >> 29;
+        let dg = ((bits >> 48 & 0x7) as i32) << 29 >> 29;
+        let db = ((bits >> 40 & 0x7) as i32) << 29 >> 29;
+
+        // ETC2 encodes additional modes in a overflowing delta.
+        if !(0..32).contains(&(r + dr)) {
+            return decode_etc2_t(bits, pixels);
+        }
+        if !(0..32).contains(&(g + dg)) {
+            return decode_etc2_h(bits, pixels);
+        }
+        if !(0..32).contains(&(b + db)) {
+            return decode_etc2_planar(bits, pixels);
+        }
+
+        let base1 = [r, g, b].map(|x| extend5(x as u64));
+        let base2 = [r + dr, g + dg, b + db].map(|x| extend5(x as u64));
+        (base1, base2)
+    } else {
+        let base1 = [60, 52, 44].map(|s| extend4(bits >> s & 0xF));
+        let base2 = [56, 48, 40].map(|s| extend4(bits >> s & 0xF));
+        (base1, base2)
+    };
+
+    let table1 = ETC_MODIFIERS[(bits >> 37 & 0x7) as usize];
+    let table2 = ETC_MODIFIERS[(bits >> 34 & 0x7) as usize];
+    let flip = bits >> 32 & 1 == 1;
+
+    for y in 0..4 {
+        for x in 0..4 {
+            let second = if flip { y >= 2 } else { x >= 2 };
+            let (base, table) = if second {
+                (base2, table2)
+            } else {
+                (base1, table1)
+            };
+            let modifier = match etc_index(bits, x, y) {
+                0 => table[0],
+                1 => table[1],
+                2 => -table[0],
+                _ => -table[1],
+            };
+            pixels[y * 4 + x] = add(base, modifier);
+        }
+    }
+}
+
+/// Write the pixels of the T and H modes, which index one of 4 paint colors.
+fn etc2_paint(bits: u64, paint: [[u8; 4]; 4], pixels: &mut [[u8; 4]; 16]) {
+    for y in 0..4 {
+        for x in 0..4 {
+            pixels[y * 4 + x] = paint[etc_index(bits, x, y)];
+        }
+    }
+}
+
+fn decode_etc2_t(bits: u64, pixels: &mut [[u8; 4]; 16]) {
+    let r1 = (bits >> 59 & 0x3) << 2 | (bits >> 56 & 0x3);
+    let c1 = [r1, bits >> 52 & 0xF, bits >> 48 & 0xF].map(extend4);
+    let c2 = [bits >> 44 & 0xF, bits >> 40 & 0xF, bits >> 36 & 0xF].map(extend4);
+    let d = ETC_DISTANCES[((bits >> 34 & 0x3) << 1 | (bits >> 32 & 1)) as usize];
+    let paint = [clamp(c1), add(c2, d), clamp(c2), add(c2, -d)];
+    etc2_paint(bits, paint, pixels);
+}
+
+fn decode_etc2_h(bits: u64, pixels: &mut [[u8; 4]; 16]) {
+    let r1 = bits >> 59 & 0xF;
+    let g1 = (bits >> 56 & 0x7) << 1 | (bits >> 52 & 1);
+    let b1 = (bits >> 51 & 1) << 3 | (bits >> 47 & 0x7);
+    let r2 = bits >> 43 & 0xF;
+    let g2 = bits >> 39 & 0xF;
+    let b2 = bits >> 35 & 0xF;
+    let order = (r1 << 8 | g1 << 4 | b1) >= (r2 << 8 | g2 << 4 | b2);
+    let d = ETC_DISTANCES[((bits >> 34 & 1) << 2 | (bits >> 32 & 1) << 1 | order as u64) as usize];
+    let c1 = [r1, g1, b1].map(extend4);
+    let c2 = [r2, g2, b2].map(extend4);
+    let paint = [add(c1, d), add(c1, -d), add(c2, d), add(c2, -d)];
+    etc2_paint(bits, paint, pixels);
+}
+
+fn decode_etc2_planar(bits: u64, pixels: &mut [[u8; 4]; 16]) {
+    let o = [
+        extend6(bits >> 57 & 0x3F),
+        extend7((bits >> 56 & 1) << 6 | (bits >> 49 & 0x3F)),
+        extend6((bits >> 48 & 1) << 5 | (bits >> 43 & 0x3) << 3 | (bits >> 39 & 0x7)),
+    ];
+    let h = [
+        extend6((bits >> 34 & 0x1F) << 1 | (bits >> 32 & 1)),
+        extend7(bits >> 25 & 0x7F),
+        extend6(bits >> 19 & 0x3F),
+    ];
+    let v = [
+        extend6(bits >> 13 & 0x3F),
+        extend7(bits >> 6 & 0x7F),
+        extend6(bits & 0x3F),
+    ];
+    for y in 0..4 {
+        for x in 0..4 {
+            let (xi, yi) = (x as i32, y as i32);
+            let c =
+                [0, 1, 2].map(|i| (xi * (h[i] - o[i]) + yi * (v[i] - o[i]) + 4 * o[i] + 2) >> 2);
+            pixels[y * 4 + x] = clamp(c);
+        }
+    }
+}
+
+/// The modifier tables of EAC, indexed by the table index.
+const EAC_MODIFIERS: [[i32; 8]; 16] = [
+    [-3, -6, -9, -15, 2, 5, 8, 14],
+    [-3, -7, -10, -13, 2, 6, 9, 12],
+    [-2, -5, -8, -13, 1, 4, 7, 12],
+    [-2, -4, -6, -13, 1, 3, 5, 12],
+    [-3, -6, -8, -12, 2, 5, 7, 11],
+    [-3, -7, -9, -11, 2, 6, 8, 10],
+    [-4, -7, -8, -11, 3, 6, 7, 10],
+    [-3, -5, -8, -11, 2, 4, 7, 10],
+    [-2, -6, -8, -10, 1, 5, 7, 9],
+    [-2, -5, -8, -10, 1, 4, 7, 9],
+    [-2, -4, -8, -10, 1, 3, 7, 9],
+    [-2, -5, -7, -10, 1, 4, 6, 9],
+    [-3, -4, -7, -10, 2, 3, 6, 9],
+    [-1, -2, -3, -10, 0, 1, 2, 9],
+    [-4, -6, -8, -9, 3, 5, 7, 8],
+    [-3, -5, -7, -9, 2, 4, 6, 8],
+];
+
+pub(super) fn decode_etc2_rgba8(block: &[u8], pixels: &mut [[u8; 4]; 16]) {
+    decode_etc2_rgb8(&block[8..16], pixels);
+
+    let bits = u64::from_be_bytes(block[0..8].try_into().unwrap());
+    let base = (bits >> 56) as i32;
+    let multiplier = (bits >> 52 & 0xF) as i32;
+    let table = EAC_MODIFIERS[(bits >> 48 & 0xF) as usize];
+    for y in 0..4 {
+        for x in 0..4 {
+            // the pixels are indexed in column-major order, from the most significant bits.
+            let i = x * 4 + y;
+            let index = (bits >> (45 - 3 * i) & 0x7) as usize;
+            let alpha = base + table[index] * multiplier;
+            pixels[y * 4 + x][3] = alpha.clamp(0, 255) as u8;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode(decode_block: fn(&[u8], &mut [[u8; 4]; 16]), block: &[u8]) -> [[u8; 4]; 16] {
+        let mut pixels = [[1; 4]; 16];
+        decode_block(block, &mut pixels);
+        pixels
+    }
+
+    #[test]
+    fn bc1_four_colors() {
+        // red and blue endpoints, the first pixels use the indices 0, 1, 2 and 3.
+        let pixels = decode(decode_bc1, &[0x00, 0xF8, 0x1F, 0x00, 0xE4, 0, 0, 0]);
+        assert_eq!(pixels[0], [255, 0, 0, 255]);
+        assert_eq!(pixels[1], [0, 0, 255, 255]);
+        assert_eq!(pixels[2], [170, 0, 85, 255]);
+        assert_eq!(pixels[3], [85, 0, 170, 255]);
+        assert!(pixels[4..].iter().all(|x| *x == [255, 0, 0, 255]));
+    }
+
+    #[test]
+    fn bc1_three_colors_and_transparent() {
+        // c0 <= c1 selects the mode with a transparent black.
+        let pixels = decode(decode_bc1, &[0x1F, 0x00, 0x00, 0xF8, 0xE4, 0, 0, 0]);
+        assert_eq!(pixels[0], [0, 0, 255, 255]);
+        assert_eq!(pixels[1], [255, 0, 0, 255]);
+        assert_eq!(pixels[2], [127, 0, 127, 255]);
+        assert_eq!(pixels[3], [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn bc2_explicit_alpha() {
+        let mut block = [0u8; 16];
+        // alpha nibbles 0x0, 0xF and 0x8 for the first pixels.
+        block[0] = 0xF0;
+        block[1] = 0x08;
+        // white endpoints, always in the four colors mode.
+        block[8..12].copy_from_slice(&[0xFF, 0xFF, 0x00, 0x00]);
+        let pixels = decode(decode_bc2, &block);
+        assert_eq!(pixels[0], [255, 255, 255, 0]);
+        assert_eq!(pixels[1], [255, 255, 255, 255]);
+        assert_eq!(pixels[2], [255, 255, 255, 136]);
+        assert_eq!(pixels[3], [255, 255, 255, 0]);
+    }
+
+    #[test]
+    fn bc3_interpolated_alpha() {
+        let mut block = [0u8; 16];
+        block[8..12].copy_from_slice(&[0xFF, 0xFF, 0x00, 0x00]);
+
+        // a0 > a1: 6 interpolated alphas. Indices 0, 1, 2 and 7.
+        block[..4].copy_from_slice(&[255, 0, 0x88, 0x0E]);
+        let pixels = decode(decode_bc3, &block);
+        let alphas = pixels.map(|x| x[3]);
+        assert_eq!(alphas[..5], [255, 0, 218, 36, 255]);
+
+        // a0 <= a1: 4 interpolated alphas, plus 0 and 255. Indices 6, 7 and 2.
+        block[..4].copy_from_slice(&[0, 255, 0xBE, 0x00]);
+        let pixels = decode(decode_bc3, &block);
+        let alphas = pixels.map(|x| x[3]);
+        assert_eq!(alphas[..4], [0, 255, 51, 0]);
+    }
+
+    #[test]
+    fn etc2_individual_mode() {
+        // base colors (15, 0, 0) and (0, 0, 15) split left and right, table 0, the pixel (3, 0)
+        // uses the index 3.
+        let block = [0xF0, 0x00, 0x0F, 0x00, 0x10, 0x00, 0x10, 0x00];
+        let pixels = decode(decode_etc2_rgb8, &block);
+        assert_eq!(pixels[0], [255, 2, 2, 255]);
+        assert_eq!(pixels[1], [255, 2, 2, 255]);
+        assert_eq!(pixels[2], [2, 2, 255, 255]);
+        assert_eq!(pixels[3], [0, 0, 247, 255]);
+        assert_eq!(pixels[15], [2, 2, 255, 255]);
+    }
+
+    #[test]
+    fn etc2_differential_mode_flipped() {
+        // red 16 with a delta of +1, split top and bottom.
+        let block = [0x81, 0x00, 0x00, 0x03, 0, 0, 0, 0];
+        let pixels = decode(decode_etc2_rgb8, &block);
+        assert!(pixels[..8].iter().all(|x| *x == [134, 2, 2, 255]));
+        assert!(pixels[8..].iter().all(|x| *x == [142, 2, 2, 255]));
+    }
+
+    #[test]
+    fn etc2_t_mode() {
+        // red 0 with a delta of -4 overflows, selecting the T mode. Black and gray (8, 8, 8) with
+        // the distance 6.
+        let block = [0x04, 0x00, 0x88, 0x83, 0x00, 0x12, 0x00, 0x03];
+        let pixels = decode(decode_etc2_rgb8, &block);
+        assert_eq!(pixels[0], [142, 142, 142, 255]);
+        assert_eq!(pixels[1], [136, 136, 136, 255]);
+        assert_eq!(pixels[4], [130, 130, 130, 255]);
+        assert_eq!(pixels[5], [0, 0, 0, 255]);
+    }
+
+    #[test]
+    fn etc2_eac_alpha() {
+        // base 128, multiplier 1, table 0. The pixel (0, 0) uses the index 4, (1, 0) the index 7.
+        let mut block = [0u8; 16];
+        block[..4].copy_from_slice(&[128, 0x10, 0x80, 0x0E]);
+        block[8..].copy_from_slice(&[0xF0, 0x00, 0x0F, 0x00, 0, 0, 0, 0]);
+        let pixels = decode(decode_etc2_rgba8, &block);
+        assert_eq!(pixels[0], [255, 2, 2, 130]);
+        assert_eq!(pixels[1], [255, 2, 2, 142]);
+        assert_eq!(pixels[2][3], 125);
+        assert_eq!(pixels[15], [2, 2, 255, 125]);
+    }
+}
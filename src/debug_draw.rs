@@ -0,0 +1,358 @@
+//! Immediate-mode drawing of lines, shapes and text labels for debugging, drawn over the scene.
+
+mod font;
+
+use std::f32::consts::PI;
+
+use crate::{
+    Camera, Renderer, Shapes, SpriteInstance, SpriteRender, Texture, TextureError, TextureFilter,
+    TextureId,
+};
+
+use font::{FIRST_CHAR, GLYPHS, GLYPH_HEIGHT, GLYPH_WIDTH};
+
+/// The number of glyphs in each row of the font texture.
+const FONT_COLUMNS: u32 = 16;
+const FONT_ROWS: u32 = (GLYPHS.len() as u32).div_ceil(FONT_COLUMNS);
+/// The size of the cell of each glyph in the font texture, with a pixel of padding.
+const CELL_WIDTH: u32 = GLYPH_WIDTH + 1;
+const CELL_HEIGHT: u32 = GLYPH_HEIGHT + 1;
+/// The length of the head of arrows, in multiples of the line width.
+const ARROW_HEAD: f32 = 8.0;
+/// The maximum number of lines in each axis of a grid, to not stall when the spacing is too small.
+const MAX_GRID_LINES: f32 = 1024.0;
+
+enum Command {
+    Line {
+        a: [f32; 2],
+        b: [f32; 2],
+        color: [u8; 4],
+    },
+    Arrow {
+        from: [f32; 2],
+        to: [f32; 2],
+        color: [u8; 4],
+    },
+    Aabb {
+        min: [f32; 2],
+        max: [f32; 2],
+        color: [u8; 4],
+    },
+    Circle {
+        center: [f32; 2],
+        radius: f32,
+        color: [u8; 4],
+    },
+    Grid {
+        min: [f32; 2],
+        max: [f32; 2],
+        spacing: f32,
+        color: [u8; 4],
+    },
+    Text {
+        position: [f32; 2],
+        text: String,
+        color: [u8; 4],
+    },
+}
+
+/// Accumulates debug drawing commands during a frame, and draws them on top of the scene with
+/// [DebugDraw::flush], which should be called right before [Renderer::finish].
+///
+/// The lines and text have a constant size in pixels, independent of the camera zoom, given by
+/// [DebugDraw::set_line_width] and [DebugDraw::set_text_scale]. The world space commands are drawn
+/// with the camera given to `flush`. The screen space text is positioned in pixels, with the origin
+/// in the top left corner of the screen.
+///
+/// When disabled, the commands are discarded as they are issued, so they can be left in the code
+/// at little cost.
+pub struct DebugDraw {
+    enabled: bool,
+    font: TextureId,
+    line_width: f32,
+    text_scale: f32,
+    commands: Vec<Command>,
+    screen_text: Vec<Command>,
+    shapes: Shapes,
+    sprites: Vec<SpriteInstance>,
+}
+impl DebugDraw {
+    /// Create a new DebugDraw, enabled, creating its font texture in the given SpriteRender.
+    pub fn new(sprite_render: &mut dyn SpriteRender) -> Result<Self, TextureError> {
+        let width = FONT_COLUMNS * CELL_WIDTH;
+        let height = FONT_ROWS * CELL_HEIGHT;
+        let mut data = vec![0u8; (width * height * 4) as usize];
+        for (i, glyph) in GLYPHS.iter().enumerate() {
+            let x0 = i as u32 % FONT_COLUMNS * CELL_WIDTH;
+            let y0 = i as u32 / FONT_COLUMNS * CELL_HEIGHT;
+            for (y, row) in glyph.iter().enumerate() {
+                for x in 0..GLYPH_WIDTH {
+                    if row & (1 << (GLYPH_WIDTH - 1 - x)) != 0 {
+                        let p = (((y0 + y as u32) * width + x0 + x) * 4) as usize;
+                        data[p..p + 4].copy_from_slice(&[255; 4]);
+                    }
+                }
+            }
+        }
+        let font = Texture::new(width, height)
+            .filter(TextureFilter::Nearest)
+            .data(&data)
+            .create(sprite_render)?;
+
+        Ok(Self {
+            enabled: true,
+            font,
+            line_width: 1.0,
+            text_scale: 2.0,
+            commands: Vec::new(),
+            screen_text: Vec::new(),
+            shapes: Shapes::new(),
+            sprites: Vec::new(),
+        })
+    }
+
+    /// Enable or disable the debug drawing. Disabling it discards the pending commands.
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        if !enabled {
+            self.clear();
+        }
+    }
+
+    /// If the debug drawing is enabled.
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Enable the debug drawing if it is disabled, and disable it otherwise.
+    pub fn toggle(&mut self) {
+        self.set_enabled(!self.enabled);
+    }
+
+    /// Set the width of the lines, in pixels. The default is 1.0.
+    pub fn set_line_width(&mut self, width: f32) {
+        self.line_width = width;
+    }
+
+    /// Set the size of each pixel of the font, in pixels of the screen. The default is 2.0.
+    pub fn set_text_scale(&mut self, scale: f32) {
+        self.text_scale = scale;
+    }
+
+    /// The texture of the font, in case it needs to be deleted or recreated.
+    pub fn font_texture(&self) -> TextureId {
+        self.font
+    }
+
+    /// Discard all pending commands.
+    pub fn clear(&mut self) {
+        self.commands.clear();
+        self.screen_text.clear();
+    }
+
+    fn push(&mut self, command: Command) {
+        if self.enabled {
+            self.commands.push(command);
+        }
+    }
+
+    /// A line from `a` to `b`, in world space.
+    pub fn line(&mut self, a: [f32; 2], b: [f32; 2], color: [u8; 4]) {
+        self.push(Command::Line { a, b, color });
+    }
+
+    /// A line from `from` to `to`, with a arrow head in `to`, in world space.
+    pub fn arrow(&mut self, from: [f32; 2], to: [f32; 2], color: [u8; 4]) {
+        self.push(Command::Arrow { from, to, color });
+    }
+
+    /// The outline of a axis aligned bounding box, in world space.
+    pub fn aabb(&mut self, min: [f32; 2], max: [f32; 2], color: [u8; 4]) {
+        self.push(Command::Aabb { min, max, color });
+    }
+
+    /// The outline of a circle, in world space.
+    pub fn circle(&mut self, center: [f32; 2], radius: f32, color: [u8; 4]) {
+        self.push(Command::Circle {
+            center,
+            radius,
+            color,
+        });
+    }
+
+    /// A grid of lines spaced by `spacing`, aligned to the origin, covering the area from `min` to
+    /// `max` in world space.
+    pub fn grid(&mut self, min: [f32; 2], max: [f32; 2], spacing: f32, color: [u8; 4]) {
+        self.push(Command::Grid {
+            min,
+            max,
+            spacing,
+            color,
+        });
+    }
+
+    /// A text label with its top left corner in `position`, in world space. Only ASCII characters
+    /// are supported, the others are drawn as `?`. Lines are broken at `\n`.
+    pub fn text(&mut self, position: [f32; 2], text: impl Into<String>, color: [u8; 4]) {
+        self.push(Command::Text {
+            position,
+            text: text.into(),
+            color,
+        });
+    }
+
+    /// A text label with its top left corner in `position`, in pixels from the top left corner of
+    /// the screen. See [DebugDraw::text].
+    pub fn screen_text(&mut self, position: [f32; 2], text: impl Into<String>, color: [u8; 4]) {
+        if self.enabled {
+            self.screen_text.push(Command::Text {
+                position,
+                text: text.into(),
+                color,
+            });
+        }
+    }
+
+    /// Draw the pending commands, and clear them. The world space commands are drawn with the given
+    /// camera.
+    pub fn flush(&mut self, renderer: &mut dyn Renderer, camera: &mut Camera) {
+        if !self.enabled {
+            return;
+        }
+
+        // the size of a screen pixel in world space.
+        let (x, y) = camera.vector_to_word_space(1.0, 0.0);
+        let pixel = (x * x + y * y).sqrt();
+        let commands = std::mem::take(&mut self.commands);
+        self.draw(renderer, camera, pixel, &commands);
+        self.commands = commands;
+        self.commands.clear();
+
+        let (width, height) = camera.screen_size();
+        if width == 0 || height == 0 {
+            self.screen_text.clear();
+            return;
+        }
+        let mut screen = Camera::new(width, height, height as f32);
+        screen.set_position(width as f32 / 2.0, height as f32 / 2.0);
+        let commands = std::mem::take(&mut self.screen_text);
+        self.draw(renderer, &mut screen, 1.0, &commands);
+        self.screen_text = commands;
+        self.screen_text.clear();
+    }
+
+    fn draw(
+        &mut self,
+        renderer: &mut dyn Renderer,
+        camera: &mut Camera,
+        pixel: f32,
+        commands: &[Command],
+    ) {
+        if commands.is_empty() {
+            return;
+        }
+        self.shapes.clear();
+        self.sprites.clear();
+        let thickness = self.line_width * pixel;
+        for command in commands {
+            match *command {
+                Command::Line { a, b, color } => {
+                    self.shapes.line(a, b, thickness, color);
+                }
+                Command::Arrow { from, to, color } => {
+                    let d = [to[0] - from[0], to[1] - from[1]];
+                    let len = (d[0] * d[0] + d[1] * d[1]).sqrt();
+                    if len == 0.0 {
+                        continue;
+                    }
+                    let head = (ARROW_HEAD * thickness).min(len);
+                    let [dx, dy] = [d[0] / len * head, d[1] / len * head];
+                    let base = [to[0] - dx, to[1] - dy];
+                    self.shapes.line(from, base, thickness, color).triangle(
+                        to,
+                        [base[0] - dy / 2.0, base[1] + dx / 2.0],
+                        [base[0] + dy / 2.0, base[1] - dx / 2.0],
+                        color,
+                    );
+                }
+                Command::Aabb { min, max, color } => {
+                    let points = [min, [max[0], min[1]], max, [min[0], max[1]]];
+                    self.shapes.polygon_outline(&points, thickness, color);
+                }
+                Command::Circle {
+                    center,
+                    radius,
+                    color,
+                } => {
+                    // around 4 pixels per segment.
+                    let segments = (2.0 * PI * radius / pixel / 4.0).clamp(8.0, 64.0) as u32;
+                    self.shapes
+                        .circle_outline(center[0], center[1], radius, segments, thickness, color);
+                }
+                Command::Grid {
+                    min,
+                    max,
+                    spacing,
+                    color,
+                } => {
+                    if spacing <= 0.0
+                        || (max[0] - min[0]) / spacing > MAX_GRID_LINES
+                        || (max[1] - min[1]) / spacing > MAX_GRID_LINES
+                    {
+                        log::warn!("debug grid spacing {} is too small", spacing);
+                        continue;
+                    }
+                    let mut x = (min[0] / spacing).ceil() * spacing;
+                    while x <= max[0] {
+                        self.shapes.line([x, min[1]], [x, max[1]], thickness, color);
+                        x += spacing;
+                    }
+                    let mut y = (min[1] / spacing).ceil() * spacing;
+                    while y <= max[1] {
+                        self.shapes.line([min[0], y], [max[0], y], thickness, color);
+                        y += spacing;
+                    }
+                }
+                Command::Text {
+                    position,
+                    ref text,
+                    color,
+                } => self.write_text(position, text, color, self.text_scale * pixel),
+            }
+        }
+        renderer.draw_shapes(camera, &self.shapes);
+        renderer.draw_sprites(camera, &self.sprites);
+    }
+
+    /// Add a sprite for each character of `text`, where `scale` is the size of a font pixel.
+    fn write_text(&mut self, position: [f32; 2], text: &str, color: [u8; 4], scale: f32) {
+        let columns = FONT_COLUMNS * CELL_WIDTH;
+        let rows = FONT_ROWS * CELL_HEIGHT;
+        let (w, h) = (GLYPH_WIDTH as f32 * scale, GLYPH_HEIGHT as f32 * scale);
+        for (line, text) in text.lines().enumerate() {
+            let y = position[1] + line as f32 * CELL_HEIGHT as f32 * scale + h / 2.0;
+            for (i, c) in text.chars().enumerate() {
+                let c = if c.is_ascii_graphic() || c == ' ' {
+                    c as u8
+                } else {
+                    b'?'
+                };
+                if c == b' ' {
+                    continue;
+                }
+                let glyph = (c - FIRST_CHAR) as u32;
+                let u = (glyph % FONT_COLUMNS * CELL_WIDTH) as f32 / columns as f32;
+                let v = (glyph / FONT_COLUMNS * CELL_HEIGHT) as f32 / rows as f32;
+                let x = position[0] + i as f32 * CELL_WIDTH as f32 * scale + w / 2.0;
+                let uv_rect = [
+                    u,
+                    v,
+                    GLYPH_WIDTH as f32 / columns as f32,
+                    GLYPH_HEIGHT as f32 / rows as f32,
+                ];
+                self.sprites
+                    .push(SpriteInstance::new(x, y, w, h, self.font, uv_rect).with_color(color));
+            }
+        }
+    }
+}
@@ -0,0 +1,85 @@
+#[cfg(any(feature = "opengl", feature = "webgl"))]
+use std::collections::HashSet;
+
+#[cfg(any(feature = "opengl", feature = "webgl"))]
+use crate::{SpriteRender, Texture, TextureFilter, TextureId, TextureWrap};
+
+/// The texture drawn in place of a [TextureId](crate::TextureId) that does not exist.
+///
+/// Set with [SpriteRender::set_fallback_texture](crate::SpriteRender::set_fallback_texture).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FallbackTexture {
+    /// A magenta and black checkerboard, to make missing textures stand out.
+    #[default]
+    Checkerboard,
+    /// A white texture, so the sprite is draw with its solid color.
+    White,
+    /// Don't draw sprites whose texture does not exist.
+    None,
+}
+impl FallbackTexture {
+    /// Create the fallback texture in the given SpriteRender, with the id [FALLBACK_TEXTURE]. Does
+    /// nothing for [FallbackTexture::None].
+    #[cfg(any(feature = "opengl", feature = "webgl"))]
+    pub(crate) fn create(&self, sprite_render: &mut dyn SpriteRender) {
+        const M: [u8; 4] = [255, 0, 255, 255];
+        const B: [u8; 4] = [0, 0, 0, 255];
+        let (width, height, data) = match self {
+            FallbackTexture::Checkerboard => (2, 2, [M, B, B, M].concat()),
+            FallbackTexture::White => (1, 1, vec![255; 4]),
+            FallbackTexture::None => return,
+        };
+        let result = Texture::new(width, height)
+            .id(FALLBACK_TEXTURE)
+            .filter(TextureFilter::Nearest)
+            .wrap(TextureWrap::Repeat)
+            .data(&data)
+            .create(sprite_render);
+        if let Err(err) = result {
+            log::error!("failed to create fallback texture: {}", err);
+        }
+    }
+}
+
+/// The id of the texture created by [FallbackTexture::create].
+#[cfg(any(feature = "opengl", feature = "webgl"))]
+pub(crate) const FALLBACK_TEXTURE: TextureId = TextureId(u32::MAX - 1);
+
+/// Warn about each missing texture only once, to not flood the log every frame.
+#[cfg(any(feature = "opengl", feature = "webgl"))]
+#[derive(Default)]
+pub(crate) struct MissingTextures(HashSet<TextureId>);
+#[cfg(any(feature = "opengl", feature = "webgl"))]
+impl MissingTextures {
+    pub fn warn(&mut self, texture: TextureId) {
+        if self.0.insert(texture) {
+            log::warn!(
+                "texture {} not found. Further warnings for this texture are suppressed",
+                texture
+            );
+        }
+    }
+}
+
+/// Replace a render texture by the fallback texture in the sprites drawn to itself, because a
+/// texture cannot be sampled while it is rendered to. Warns about each render texture only once.
+#[cfg(any(feature = "opengl", feature = "webgl"))]
+#[derive(Default)]
+pub(crate) struct FeedbackLoops(HashSet<TextureId>);
+#[cfg(any(feature = "opengl", feature = "webgl"))]
+impl FeedbackLoops {
+    /// The texture sampled in place of `texture` while rendering to `target`.
+    pub fn texture(&mut self, texture: TextureId, target: Option<TextureId>) -> TextureId {
+        if target != Some(texture) {
+            return texture;
+        }
+        if self.0.insert(texture) {
+            log::warn!(
+                "render texture {} is drawn to itself, drawing the fallback texture instead. \
+                Further warnings for this texture are suppressed",
+                texture
+            );
+        }
+        FALLBACK_TEXTURE
+    }
+}
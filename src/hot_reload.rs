@@ -0,0 +1,70 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use crate::MaterialId;
+
+/// A shader source file, watched for changes.
+struct WatchedFile {
+    material: MaterialId,
+    path: PathBuf,
+    /// The modification time of the file when it was last loaded, or None if it was never loaded.
+    modified: Option<SystemTime>,
+}
+
+/// Watch the shader source files of materials, by polling their modification time.
+#[derive(Default)]
+pub(crate) struct ShaderWatcher {
+    files: Vec<WatchedFile>,
+}
+impl ShaderWatcher {
+    /// Watch the file in `path` as the fragment shader source of the given material, replacing any
+    /// previous file of the same material.
+    ///
+    /// `modified` is the modification time of the file when the material was compiled. If None,
+    /// the file is loaded in the next poll.
+    pub fn watch(&mut self, material: MaterialId, path: PathBuf, modified: Option<SystemTime>) {
+        self.files.retain(|x| x.material != material);
+        self.files.push(WatchedFile {
+            material,
+            path,
+            modified,
+        });
+    }
+
+    /// Stop watching all files, except the one of the default material, which is loaded again in
+    /// the next poll. Used when the materials are lost.
+    pub fn reset(&mut self) {
+        self.files.retain(|x| x.material == MaterialId::default());
+        for file in &mut self.files {
+            file.modified = None;
+        }
+    }
+
+    /// Return the new source of each file modified since the last poll.
+    pub fn poll(&mut self) -> Vec<(MaterialId, String)> {
+        let mut changed = Vec::new();
+        for file in &mut self.files {
+            // The file may be missing for a moment while a editor saves it, so ignore errors.
+            let Ok(modified) = fs::metadata(&file.path).and_then(|x| x.modified()) else {
+                continue;
+            };
+            if file.modified == Some(modified) {
+                continue;
+            }
+            file.modified = Some(modified);
+            match fs::read_to_string(&file.path) {
+                Ok(source) => changed.push((file.material, source)),
+                Err(err) => log::error!("failed to read {}: {}", file.path.display(), err),
+            }
+        }
+        changed
+    }
+}
+
+/// The modification time of the file in `path`, if available.
+pub(crate) fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|x| x.modified()).ok()
+}
@@ -1,9 +1,75 @@
+mod animation;
 mod backends;
+mod clip;
 mod common;
+mod compressed;
+mod debug_draw;
+mod fallback;
+#[cfg(all(
+    feature = "hot-reload",
+    feature = "opengl",
+    not(target_arch = "wasm32")
+))]
+mod hot_reload;
+mod lighting;
+mod material;
+mod mesh;
+mod nine_slice;
+mod particles;
+mod post_process;
+mod shadow;
+mod shape;
+mod sprite_sheet;
+mod static_batch;
+#[cfg(feature = "image")]
+mod texture_image;
+#[cfg(all(feature = "image", not(target_arch = "wasm32")))]
+mod texture_loader;
+mod tilemap;
 
+pub use animation::{AnimationClip, AnimationPlayer, Frame, PlayMode};
+#[cfg(any(feature = "opengl", feature = "webgl"))]
 pub use backends::*;
+pub use clip::ClipRect;
 pub use common::*;
-use winit::window::{Window, WindowId};
+pub use compressed::{CompressedImage, ContainerError};
+pub use debug_draw::DebugDraw;
+pub use fallback::FallbackTexture;
+pub use lighting::{Light, LightKind, Lighting, MAX_LIGHTS};
+pub use material::{BlendMode, Material, Uniform};
+pub use mesh::{Indices, Mesh, Vertex};
+pub use nine_slice::{NineSlice, SliceMode};
+pub use particles::{EmitterShape, ParticleEmitter};
+pub use post_process::PostEffect;
+use raw_window_handle::{HasRawDisplayHandle, HasRawWindowHandle};
+pub use shadow::{shadow_geometry, Occluder, ShadowError, ShadowMask};
+pub use shape::{Shapes, WHITE_TEXTURE};
+pub use sprite_sheet::{AtlasRegion, FrameTag, SheetError, SheetSlice, SpriteSheet};
+pub use static_batch::Transform;
+#[cfg(feature = "image")]
+pub use texture_image::{ImageError, ImageOptions, TextureImage};
+#[cfg(all(feature = "image", not(target_arch = "wasm32")))]
+pub use texture_loader::{LoadState, TextureLoader};
+pub use tilemap::{Tilemap, Tileset};
+
+/// A window that can be rendered to by a [SpriteRender].
+///
+/// It is implemented for any type that provides both a raw window handle and a raw display handle,
+/// like the windows of winit, SDL2 or glfw.
+pub trait RawWindow: HasRawWindowHandle + HasRawDisplayHandle {}
+impl<T: HasRawWindowHandle + HasRawDisplayHandle + ?Sized> RawWindow for T {}
+
+/// Identifies a window surface of a [SpriteRender].
+///
+/// The default id, `SurfaceId(0)`, is the id of the window passed when creating the
+/// [SpriteRender]. The id of the other windows are returned by [SpriteRender::add_window].
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Hash, Default)]
+pub struct SurfaceId(pub u32);
+impl std::fmt::Display for SurfaceId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
 
 #[derive(PartialEq, Eq, Clone, Copy, Debug, Hash, Default)]
 pub struct TextureId(pub u32);
@@ -18,6 +84,73 @@ impl std::fmt::Display for TextureId {
     }
 }
 
+/// Identifies a [Material] of a [SpriteRender].
+///
+/// The default id, `MaterialId(0)`, is the built-in material, that draws the sprite texture
+/// multiplied by the sprite color. The id of the other materials are returned by
+/// [SpriteRender::new_material].
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Hash, Default)]
+pub struct MaterialId(pub u32);
+impl std::fmt::Display for MaterialId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+/// Identifies a static batch of a [SpriteRender], created by [SpriteRender::new_static_batch].
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Hash, Default)]
+pub struct BatchId(pub u32);
+impl std::fmt::Display for BatchId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+/// Error generate in [SpriteRender::new_static_batch] and when updating a static batch.
+#[derive(Debug)]
+pub enum BatchError {
+    /// The underline Renderer Context does not exist.
+    RendererContextDontExist,
+    /// There is no static batch with the given `BatchId`.
+    NotFound,
+    /// The updated range goes beyond the sprites of the static batch.
+    OutOfBounds,
+}
+impl std::fmt::Display for BatchError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BatchError::RendererContextDontExist => write!(f, "renderer context don't exist"),
+            BatchError::NotFound => write!(f, "static batch not found"),
+            BatchError::OutOfBounds => write!(f, "range out of the static batch bounds"),
+        }
+    }
+}
+impl std::error::Error for BatchError {}
+
+/// Error generate in [SpriteRender::new_material].
+#[derive(Debug)]
+pub enum ShaderError {
+    /// The shader failed to compile. Contains the info log of the shader.
+    Compile(String),
+    /// The program failed to link. Contains the info log of the program.
+    Link(String),
+    /// The material has more texture uniforms than the available texture units.
+    TooManyTextures,
+    /// The underline Renderer Context does not exist.
+    RendererContextDontExist,
+}
+impl std::fmt::Display for ShaderError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ShaderError::Compile(log) => write!(f, "shader compilation failed: {}", log),
+            ShaderError::Link(log) => write!(f, "program linking failed: {}", log),
+            ShaderError::TooManyTextures => write!(f, "too many texture uniforms"),
+            ShaderError::RendererContextDontExist => write!(f, "renderer context don't exist"),
+        }
+    }
+}
+impl std::error::Error for ShaderError {}
+
 /// Error generate in [SpriteRender::new_texture].
 #[derive(Debug)]
 pub enum TextureError {
@@ -25,9 +158,28 @@ pub enum TextureError {
     InvalidLength,
     /// The underline Renderer Context does not exist.
     RendererContextDontExist,
+    /// The `TextureFormat` is not supported by the underline Renderer Context.
+    UnsupportedFormat,
+    /// There is no texture with the given `TextureId`.
+    NotFound,
+    /// The sub rect is not inside the texture.
+    OutOfBounds,
+}
+impl std::fmt::Display for TextureError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TextureError::InvalidLength => write!(f, "texture data has an invalid length"),
+            TextureError::RendererContextDontExist => write!(f, "renderer context don't exist"),
+            TextureError::UnsupportedFormat => write!(f, "texture format is not supported"),
+            TextureError::NotFound => write!(f, "texture not found"),
+            TextureError::OutOfBounds => write!(f, "rect out of the texture bounds"),
+        }
+    }
 }
+impl std::error::Error for TextureError {}
 
 /// The format representation used by `data`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum TextureFormat {
     /// The RGBA8888 format.
     ///
@@ -36,9 +188,143 @@ pub enum TextureFormat {
     ///
     /// The total size of `data` in bytes must be `width * height * 4`.
     Rgba8888,
+    /// A single channel format, used for alpha masks, like glyph atlases.
+    ///
+    /// Each pixel is represented by 1 byte. The texture is sampled as white, with the byte as its
+    /// alpha, so the sprite color gives the color of the mask.
+    ///
+    /// The total size of `data` in bytes must be `width * height`.
+    R8,
+    /// A two channel format.
+    ///
+    /// Each pixel is represented by 2 bytes, the channels Red and Green. The texture is sampled
+    /// with Blue 0 and Alpha 1. Not supported on OpenGL 2.0 and WebGL.
+    ///
+    /// The total size of `data` in bytes must be `width * height * 2`.
+    Rg8,
+    /// The RGB888 format.
+    ///
+    /// Each pixel is represented by 3 bytes, the channels Red, Green and Blue. The texture is
+    /// sampled with Alpha 1.
+    ///
+    /// The total size of `data` in bytes must be `width * height * 3`.
+    Rgb888,
+    /// The RGB565 format.
+    ///
+    /// Each pixel is represented by a native endian `u16`, with 5 bits for Red, 6 for Green and 5
+    /// for Blue, from the most to the least significant bits. Uses half the memory of Rgba8888.
+    ///
+    /// The total size of `data` in bytes must be `width * height * 2`.
+    Rgb565,
+    /// The RGBA4444 format.
+    ///
+    /// Each pixel is represented by a native endian `u16`, with 4 bits for each of the channels
+    /// Red, Green, Blue and Alpha, from the most to the least significant bits. Uses half the
+    /// memory of Rgba8888.
+    ///
+    /// The total size of `data` in bytes must be `width * height * 2`.
+    Rgba4444,
+    /// A half-float RGBA format.
+    ///
+    /// Each pixel is represented by 4 native endian half-precision floats (`f16`), for the channels
+    /// Red, Green, Blue and Alpha. Not supported on OpenGL 2.0, and only supported on WebGL if the
+    /// `OES_texture_half_float` extension is available.
+    ///
+    /// The total size of `data` in bytes must be `width * height * 8`.
+    Rgba16F,
+    /// The Luminance-Alpha format.
+    ///
+    /// Each pixel is represented by 2 bytes, the luminance and the alpha. The texture is sampled
+    /// with the luminance in the channels Red, Green and Blue.
+    ///
+    /// The total size of `data` in bytes must be `width * height * 2`.
+    LuminanceAlpha,
+    /// The BC1 (DXT1) compressed format, with RGB and 1 bit alpha.
+    ///
+    /// Each block of 4x4 pixels is represented by 8 bytes.
+    Bc1,
+    /// The BC2 (DXT3) compressed format, with RGB and explicit 4 bits alpha.
+    ///
+    /// Each block of 4x4 pixels is represented by 16 bytes.
+    Bc2,
+    /// The BC3 (DXT5) compressed format, with RGB and interpolated alpha.
+    ///
+    /// Each block of 4x4 pixels is represented by 16 bytes.
+    Bc3,
+    /// The ETC2 RGB8 compressed format. Also accepts ETC1 data.
+    ///
+    /// Each block of 4x4 pixels is represented by 8 bytes.
+    Etc2Rgb8,
+    /// The ETC2 RGBA8 compressed format, with EAC alpha.
+    ///
+    /// Each block of 4x4 pixels is represented by 16 bytes.
+    Etc2Rgba8,
+    /// The ASTC compressed format, with 4x4 blocks, in the LDR profile.
+    ///
+    /// Each block of 4x4 pixels is represented by 16 bytes.
+    Astc4x4,
+}
+impl TextureFormat {
+    /// If this is a block compressed format.
+    ///
+    /// Compressed textures can only be created with the formats supported by the [SpriteRender],
+    /// see [SpriteRender::supports_texture_format].
+    pub fn is_compressed(&self) -> bool {
+        self.block_size().0 > 1
+    }
+
+    /// The width and height of the blocks of pixels in which the format is encoded, and the
+    /// number of bytes of each block. The block of a uncompressed format is a single pixel.
+    pub fn block_size(&self) -> (u32, u32, u32) {
+        match self {
+            TextureFormat::Rgba8888 => (1, 1, 4),
+            TextureFormat::R8 => (1, 1, 1),
+            TextureFormat::Rg8 => (1, 1, 2),
+            TextureFormat::Rgb888 => (1, 1, 3),
+            TextureFormat::Rgb565 => (1, 1, 2),
+            TextureFormat::Rgba4444 => (1, 1, 2),
+            TextureFormat::Rgba16F => (1, 1, 8),
+            TextureFormat::LuminanceAlpha => (1, 1, 2),
+            TextureFormat::Bc1 | TextureFormat::Etc2Rgb8 => (4, 4, 8),
+            TextureFormat::Bc2
+            | TextureFormat::Bc3
+            | TextureFormat::Etc2Rgba8
+            | TextureFormat::Astc4x4 => (4, 4, 16),
+        }
+    }
+
+    /// The length in bytes of the `data` of a texture with the given dimensions, or None if it
+    /// does not fit in a `usize`.
+    pub fn data_len(&self, width: u32, height: u32) -> Option<usize> {
+        let (block_width, block_height, block_bytes) = self.block_size();
+        let blocks_x = width.div_ceil(block_width) as usize;
+        let blocks_y = height.div_ceil(block_height) as usize;
+        blocks_x
+            .checked_mul(blocks_y)?
+            .checked_mul(block_bytes as usize)
+    }
+}
+
+/// The color space in which the colors are blended.
+///
+/// The colors of the textures, the sprites and `clear_screen` are always given in sRGB, except for
+/// [TextureFormat::R8], [TextureFormat::Rg8] and [TextureFormat::Rgba16F], which are linear.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ColorSpace {
+    /// The colors are blended in sRGB, as they are given. This is cheaper, but gradients and
+    /// alpha blending look darker than they should.
+    #[default]
+    Gamma,
+    /// The colors are converted to linear before being blended, and converted back to sRGB when
+    /// written to the surface.
+    ///
+    /// Uses sRGB textures and a sRGB surface when available. Otherwise the conversions are done in
+    /// the shaders, and the colors are blended in sRGB.
+    Linear,
 }
 
 /// The type of interpolation used when sampling the texture.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum TextureFilter {
     /// Use the nearest sample.
     ///
@@ -48,24 +334,88 @@ pub enum TextureFilter {
     Linear,
 }
 
+/// How texture coordinates outside the range 0.0 to 1.0 are handled.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TextureWrap {
+    /// Clamp the coordinates to the edge of the texture.
+    ClampToEdge,
+    /// Repeat the texture.
+    Repeat,
+    /// Repeat the texture, mirroring it at every integer coordinate.
+    MirroredRepeat,
+}
+
+/// How a texture is sampled.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Sampler {
+    /// The filter used when the texture is minified.
+    pub min_filter: TextureFilter,
+    /// The filter used when the texture is magnified.
+    pub mag_filter: TextureFilter,
+    /// The filter used between mipmap levels. Ignored if the texture has no mipmaps.
+    pub mip_filter: TextureFilter,
+    /// The wrap mode of the horizontal texture coordinate.
+    pub wrap_s: TextureWrap,
+    /// The wrap mode of the vertical texture coordinate.
+    pub wrap_t: TextureWrap,
+    /// The maximum anisotropy used when sampling. A value of 1.0 disables anisotropic filtering.
+    ///
+    /// It is clamped to the maximum supported by the context, and is ignored if anisotropic
+    /// filtering is not available.
+    pub max_anisotropy: f32,
+}
+impl Default for Sampler {
+    fn default() -> Self {
+        Self {
+            min_filter: TextureFilter::Linear,
+            mag_filter: TextureFilter::Linear,
+            mip_filter: TextureFilter::Linear,
+            wrap_s: TextureWrap::ClampToEdge,
+            wrap_t: TextureWrap::ClampToEdge,
+            max_anisotropy: 1.0,
+        }
+    }
+}
+
+/// The mipmap levels of a [Texture].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mipmaps<'a> {
+    /// The texture has no mipmaps.
+    None,
+    /// Generate the mipmaps from the base level. The mipmaps are regenerated when the texture is
+    /// updated.
+    ///
+    /// Compressed textures cannot generate mipmaps, and will have no mipmaps.
+    Generate,
+    /// The data of the levels after the base level, each one with half the dimensions of the
+    /// previous one, rounded down, and with at least 1 pixel.
+    ///
+    /// Only the base level is changed by [SpriteRender::update_texture]. In OpenGL 2.0 and WebGL,
+    /// the levels must go down to 1x1, otherwise the texture has no mipmaps.
+    Levels(&'a [&'a [u8]]),
+}
 /// A Texture to be loaded in [SpriteRender].
 pub struct Texture<'a> {
     id: TextureId,
+    #[cfg_attr(not(any(feature = "opengl", feature = "webgl")), allow(dead_code))]
     width: u32,
+    #[cfg_attr(not(any(feature = "opengl", feature = "webgl")), allow(dead_code))]
     height: u32,
     format: TextureFormat,
-    filter: TextureFilter,
+    sampler: Sampler,
+    mipmaps: Mipmaps<'a>,
     data: Option<&'a [u8]>,
 }
 impl<'a> Texture<'a> {
     /// Creates a new Texture, with the given dimensions.
     pub fn new(width: u32, height: u32) -> Self {
         Self {
-            id: TextureId(u32::max_value()),
+            id: TextureId(u32::MAX),
             width,
             height,
             format: TextureFormat::Rgba8888,
-            filter: TextureFilter::Linear,
+            sampler: Sampler::default(),
+            mipmaps: Mipmaps::None,
             data: None,
         }
     }
@@ -84,9 +434,29 @@ impl<'a> Texture<'a> {
         self
     }
 
-    /// Set the `TexureFilter` when sampling.
+    /// Set the `TexureFilter` used when minifying and magnifying the texture.
     pub fn filter(mut self, filter: TextureFilter) -> Self {
-        self.filter = filter;
+        self.sampler.min_filter = filter;
+        self.sampler.mag_filter = filter;
+        self
+    }
+
+    /// Set the `TextureWrap` of both texture coordinates.
+    pub fn wrap(mut self, wrap: TextureWrap) -> Self {
+        self.sampler.wrap_s = wrap;
+        self.sampler.wrap_t = wrap;
+        self
+    }
+
+    /// Set the `Sampler` of the texture, overriding any previous call to `filter` or `wrap`.
+    pub fn sampler(mut self, sampler: Sampler) -> Self {
+        self.sampler = sampler;
+        self
+    }
+
+    /// Set the `Mipmaps` of the texture. By default the texture has no mipmaps.
+    pub fn mipmaps(mut self, mipmaps: Mipmaps<'a>) -> Self {
+        self.mipmaps = mipmaps;
         self
     }
 
@@ -115,17 +485,83 @@ pub trait Renderer {
         sprites: &[SpriteInstance],
     ) -> &mut dyn Renderer;
 
+    /// Draw solid color shapes, with the built-in material. See [Shapes].
+    fn draw_shapes(&mut self, camera: &mut Camera, shapes: &Shapes) -> &mut dyn Renderer;
+
+    /// Draw a mesh of textured triangles, with the same camera transform and shaders as the
+    /// sprites. See [Mesh].
+    fn draw_mesh(&mut self, camera: &mut Camera, mesh: &Mesh) -> &mut dyn Renderer;
+
+    /// Draw a static batch created by [SpriteRender::new_static_batch], without uploading its
+    /// sprites again.
+    fn draw_static_batch(&mut self, camera: &mut Camera, batch: BatchId) -> &mut dyn Renderer;
+
+    /// Draw a static batch with the given transform applied to its sprites. The transform is
+    /// combined with the camera view, so the sprites are not uploaded again.
+    fn draw_static_batch_transformed(
+        &mut self,
+        camera: &mut Camera,
+        batch: BatchId,
+        transform: &Transform,
+    ) -> &mut dyn Renderer;
+
+    /// Limit the following draws, and [Renderer::clear_screen], to the intersection of the given
+    /// rectangle with the previously pushed ones, until it is popped by [Renderer::pop_clip_rect].
+    fn push_clip_rect(&mut self, rect: ClipRect) -> &mut dyn Renderer;
+
+    /// Remove the last rectangle pushed by [Renderer::push_clip_rect].
+    fn pop_clip_rect(&mut self) -> &mut dyn Renderer;
+
+    /// Start drawing the shapes of a mask. Until [Renderer::end_mask], the draws are not visible,
+    /// and only mark the pixels they cover, inside the currently pushed masks. Any sprite, shape or
+    /// mesh can be drawn in a mask, and the pixels where its alpha is zero are not part of it.
+    ///
+    /// ```
+    /// use sprite_render::{Camera, NoopSpriteRender, Shapes, SpriteRender, SurfaceId};
+    ///
+    /// let mut sprite_render = NoopSpriteRender;
+    /// let mut camera = Camera::new(800, 600, 2.0);
+    /// let mut shapes = Shapes::new();
+    /// shapes.circle(0.0, 0.0, 1.0, 32, [255; 4]);
+    /// sprite_render
+    ///     .render(SurfaceId::default())
+    ///     .begin_mask()
+    ///     .draw_shapes(&mut camera, &shapes)
+    ///     .end_mask()
+    ///     // drawn only inside the circle
+    ///     .draw_sprites(&mut camera, &[])
+    ///     .pop_mask()
+    ///     .finish();
+    /// ```
+    fn begin_mask(&mut self) -> &mut dyn Renderer;
+
+    /// Finish the mask started by [Renderer::begin_mask], and push it. Until it is popped by
+    /// [Renderer::pop_mask], the following draws are only visible inside all pushed masks.
+    fn end_mask(&mut self) -> &mut dyn Renderer;
+
+    /// Remove the last mask pushed by [Renderer::end_mask].
+    fn pop_mask(&mut self) -> &mut dyn Renderer;
+
     fn finish(&mut self);
 }
 
 pub trait SpriteRender {
-    fn add_window(&mut self, window: &Window);
-    fn remove_window(&mut self, window_id: WindowId);
-    /// Load a Texture in the GPU.
+    /// Add a new window to be rendered to, with the given size in pixels.
+    ///
+    /// Returns the id used to refer to its surface.
+    fn add_window(&mut self, window: &dyn RawWindow, width: u32, height: u32) -> SurfaceId;
+    fn remove_window(&mut self, surface: SurfaceId);
+    /// Load a [Texture] in the GPU, with the format, sampler and mipmaps given by its builder.
+    ///
+    /// The length of the data, if given, must be [TextureFormat::data_len] of the texture
+    /// dimensions: `width * height` times the bytes of a pixel for the uncompressed formats, and
+    /// the bytes of a block for each block of 4x4 pixels, rounded up, for the compressed ones. The
+    /// same goes for each mipmap level, with its own dimensions. Otherwise
+    /// [TextureError::InvalidLength] is returned.
     ///
-    /// The texture data must be RGBA, and therefore need have a length
-    /// of width * height * 4. if linear_filter is true, the texture will be sampled with linear
-    /// filter applied.  Pixel art don't use linear filter.
+    /// Returns [TextureError::UnsupportedFormat] if the format is not supported by the context, see
+    /// [SpriteRender::supports_texture_format]. If the texture has the id of a existing texture,
+    /// that texture is replaced.
     fn new_texture(&mut self, texture: Texture) -> Result<TextureId, TextureError>;
     fn update_texture(
         &mut self,
@@ -133,13 +569,144 @@ pub trait SpriteRender {
         data: Option<&[u8]>,
         sub_rect: Option<[u32; 4]>,
     ) -> Result<(), TextureError>;
-    fn render<'a>(&'a mut self, window: WindowId) -> Box<dyn Renderer + 'a>;
-    fn resize(&mut self, window: WindowId, width: u32, height: u32);
+    /// Read the pixels of a texture, or of its `sub_rect` given as `[x, y, width, height]`, as
+    /// RGBA8888.
+    ///
+    /// The rows are in the same order as the `data` given to `new_texture`. The pixels are
+    /// converted as they are sampled, so a `R8` texture is read as `(255, 255, 255, r)`.
+    /// Compressed textures, and formats that cannot be rendered to, return
+    /// [TextureError::UnsupportedFormat].
+    fn read_texture(
+        &mut self,
+        texture: TextureId,
+        sub_rect: Option<[u32; 4]>,
+    ) -> Result<Vec<u8>, TextureError>;
+    /// Decode a PNG, JPEG or QOI image, and load it as a texture with the default sampler.
+    ///
+    /// See [TextureImage] for how the image is converted.
+    #[cfg(feature = "image")]
+    fn load_texture_from_bytes(
+        &mut self,
+        bytes: &[u8],
+        options: ImageOptions,
+    ) -> Result<TextureId, ImageError> {
+        let image = TextureImage::from_bytes(bytes, options)?;
+        Ok(self.new_texture(Texture::from_image(&image))?)
+    }
+    /// Read and decode a PNG, JPEG or QOI image file, and load it as a texture with the default
+    /// sampler.
+    ///
+    /// See [TextureImage] for how the image is converted.
+    #[cfg(feature = "image")]
+    fn load_texture_from_path(
+        &mut self,
+        path: &std::path::Path,
+        options: ImageOptions,
+    ) -> Result<TextureId, ImageError> {
+        let image = TextureImage::from_path(path, options)?;
+        Ok(self.new_texture(Texture::from_image(&image))?)
+    }
+    /// Set the texture drawn in place of the textures that do not exist. By default it is
+    /// [FallbackTexture::Checkerboard].
+    fn set_fallback_texture(&mut self, fallback: FallbackTexture);
+    fn render<'a>(&'a mut self, surface: SurfaceId) -> Box<dyn Renderer + 'a>;
+
+    /// Check if textures of the given format can be created.
+    ///
+    /// The uncompressed formats are supported depending on the version of the underline API, and
+    /// the compressed formats depending on the available extensions. Always returns false if the
+    /// Renderer Context does not exist.
+    fn supports_texture_format(&self, format: TextureFormat) -> bool;
+
+    /// The color space in which the colors are blended, given on the creation of the
+    /// SpriteRender.
+    fn color_space(&self) -> ColorSpace;
+
+    /// Compile a [Material], returning the id used to reference it from a [SpriteInstance].
+    ///
+    /// Sprites with different materials are drawn in separated draw calls.
+    fn new_material(&mut self, material: Material) -> Result<MaterialId, ShaderError>;
+
+    /// Set the value of a uniform of the given material.
+    ///
+    /// A texture uniform can only be set if it was given in the creation of the material.
+    fn set_material_uniform(&mut self, material: MaterialId, name: &str, value: Uniform);
+
+    /// Associate a normal map to a texture, or remove it if `normal_map` is None.
+    ///
+    /// The normal map is sampled with `sampleNormal` by the materials created with
+    /// [Material::normal_maps], like the one of [Lighting::material], when drawing sprites with the
+    /// texture. Its red, green and blue channels are the x (right), y (up) and z (to the viewer)
+    /// components of the normals, mapped from -1.0..1.0 to 0..255, so it must not be created in a
+    /// sRGB format. Like the textures, the association is lost when the SpriteRender is suspended.
+    fn set_normal_map(&mut self, texture: TextureId, normal_map: Option<TextureId>);
+
+    /// Create a texture that can be rendered to, with the given dimensions.
+    ///
+    /// The returned texture can be drawn into with [SpriteRender::render_to_texture], and used in
+    /// a [SpriteInstance] like any other texture. The content of the texture is undefined until it
+    /// is rendered to.
+    fn new_render_texture(&mut self, width: u32, height: u32) -> Result<TextureId, TextureError>;
+
+    /// Get a [Renderer] that draws into the given render texture, created with
+    /// [SpriteRender::new_render_texture].
+    ///
+    /// The rendered image has the same orientation as the one rendered to a window, so a sprite
+    /// with `uv_rect` `[0.0, 0.0, 1.0, 1.0]` shows the texture as it would be seen on the screen.
+    /// [Renderer::finish] must be called to stop rendering to the texture.
+    ///
+    /// A texture cannot be sampled while it is rendered to, so the sprites and meshes that use the
+    /// texture itself are drawn with the fallback texture, see
+    /// [SpriteRender::set_fallback_texture].
+    fn render_to_texture<'a>(&'a mut self, texture: TextureId) -> Box<dyn Renderer + 'a>;
+
+    /// Upload the vertices of the given sprites once to a persistent GPU buffer, to be drawn every
+    /// frame with [Renderer::draw_static_batch], or with
+    /// [Renderer::draw_static_batch_transformed] to move the whole batch.
+    ///
+    /// This is meant for sprites that rarely change, like backgrounds and level geometry, which
+    /// [Renderer::draw_sprites] would write and upload again every frame. The sprites are drawn in
+    /// the given order, in the same batches as [Renderer::draw_sprites] would draw them. Like the
+    /// textures, the static batches are lost when the SpriteRender is suspended.
+    fn new_static_batch(&mut self, sprites: &[SpriteInstance]) -> Result<BatchId, BatchError>;
+
+    /// Replace all sprites of a static batch, uploading them again. The number of sprites may
+    /// change.
+    fn update_static_batch(
+        &mut self,
+        batch: BatchId,
+        sprites: &[SpriteInstance],
+    ) -> Result<(), BatchError>;
+
+    /// Replace the sprites of a static batch from the index `start`, uploading only them.
+    ///
+    /// If a sprite changes to a material or texture that is not used by the draw call of its
+    /// range, the whole batch is uploaded again. Returns [BatchError::OutOfBounds] if the range
+    /// goes beyond the sprites of the batch.
+    fn update_static_batch_range(
+        &mut self,
+        batch: BatchId,
+        start: usize,
+        sprites: &[SpriteInstance],
+    ) -> Result<(), BatchError>;
+
+    /// Delete a static batch, freeing its GPU buffer.
+    fn remove_static_batch(&mut self, batch: BatchId);
+
+    fn resize(&mut self, surface: SurfaceId, width: u32, height: u32);
+
+    /// Set the chain of post-processing effects of the given window surface.
+    ///
+    /// When the chain is not empty, the window is rendered to a intermediate texture, and each
+    /// effect is applied to the result of the previous one, in order, before presenting it in
+    /// [Renderer::finish]. A empty chain disables post-processing.
+    fn set_post_effects(&mut self, surface: SurfaceId, effects: Vec<PostEffect>);
 
-    /// Resume the given window.
+    /// Resume the given window, with the given size in pixels.
     ///
-    /// Only used on Android. Allows recreating the Rendering context when it is lost.
-    fn resume(&mut self, window: &Window);
+    /// Only used on Android. Allows recreating the Rendering context when it is lost. The window
+    /// keeps the default [SurfaceId].
+    fn resume(&mut self, window: &dyn RawWindow, width: u32, height: u32);
 
     /// Suspends the rendering.
     ///
@@ -149,7 +716,8 @@ pub trait SpriteRender {
 
 /// A implementation of SpriteRender that does nothing.
 ///
-/// None of its methods returns a Error.
+/// None of its methods returns a Error. [SpriteRender::read_texture] returns transparent black
+/// pixels for the `sub_rect`, or no pixels if it is not given.
 pub struct NoopSpriteRender;
 /// A implementation of Renderer that does nothing.
 struct NoopRenderer;
@@ -161,12 +729,46 @@ impl Renderer for NoopRenderer {
     fn draw_sprites(&mut self, _: &mut Camera, _: &[SpriteInstance]) -> &mut dyn Renderer {
         self
     }
+    fn draw_shapes(&mut self, _: &mut Camera, _: &Shapes) -> &mut dyn Renderer {
+        self
+    }
+    fn draw_mesh(&mut self, _: &mut Camera, _: &Mesh) -> &mut dyn Renderer {
+        self
+    }
+    fn draw_static_batch(&mut self, _: &mut Camera, _: BatchId) -> &mut dyn Renderer {
+        self
+    }
+    fn draw_static_batch_transformed(
+        &mut self,
+        _: &mut Camera,
+        _: BatchId,
+        _: &Transform,
+    ) -> &mut dyn Renderer {
+        self
+    }
+    fn push_clip_rect(&mut self, _: ClipRect) -> &mut dyn Renderer {
+        self
+    }
+    fn pop_clip_rect(&mut self) -> &mut dyn Renderer {
+        self
+    }
+    fn begin_mask(&mut self) -> &mut dyn Renderer {
+        self
+    }
+    fn end_mask(&mut self) -> &mut dyn Renderer {
+        self
+    }
+    fn pop_mask(&mut self) -> &mut dyn Renderer {
+        self
+    }
     fn finish(&mut self) {}
 }
 
 impl SpriteRender for NoopSpriteRender {
-    fn add_window(&mut self, _window: &Window) {}
-    fn remove_window(&mut self, _window_id: WindowId) {}
+    fn add_window(&mut self, _window: &dyn RawWindow, _width: u32, _height: u32) -> SurfaceId {
+        SurfaceId::default()
+    }
+    fn remove_window(&mut self, _surface: SurfaceId) {}
 
     fn new_texture(&mut self, _: Texture) -> Result<TextureId, TextureError> {
         Ok(TextureId(0))
@@ -179,14 +781,69 @@ impl SpriteRender for NoopSpriteRender {
     ) -> Result<(), TextureError> {
         Ok(())
     }
+    fn read_texture(
+        &mut self,
+        _: TextureId,
+        sub_rect: Option<[u32; 4]>,
+    ) -> Result<Vec<u8>, TextureError> {
+        let [_, _, width, height] = sub_rect.unwrap_or_default();
+        Ok(vec![0; width as usize * height as usize * 4])
+    }
+
+    fn set_fallback_texture(&mut self, _: FallbackTexture) {}
+
+    fn render<'a>(&'a mut self, _surface: SurfaceId) -> Box<dyn Renderer + 'a> {
+        Box::new(NoopRenderer)
+    }
+
+    fn supports_texture_format(&self, _: TextureFormat) -> bool {
+        true
+    }
+
+    fn color_space(&self) -> ColorSpace {
+        ColorSpace::Gamma
+    }
 
-    fn render<'a>(&'a mut self, _window: WindowId) -> Box<dyn Renderer + 'a> {
+    fn new_material(&mut self, _: Material) -> Result<MaterialId, ShaderError> {
+        Ok(MaterialId(0))
+    }
+
+    fn set_material_uniform(&mut self, _: MaterialId, _: &str, _: Uniform) {}
+
+    fn set_normal_map(&mut self, _: TextureId, _: Option<TextureId>) {}
+
+    fn new_render_texture(&mut self, _: u32, _: u32) -> Result<TextureId, TextureError> {
+        Ok(TextureId(0))
+    }
+
+    fn render_to_texture<'a>(&'a mut self, _: TextureId) -> Box<dyn Renderer + 'a> {
         Box::new(NoopRenderer)
     }
 
-    fn resize(&mut self, _window: WindowId, _width: u32, _height: u32) {}
+    fn new_static_batch(&mut self, _: &[SpriteInstance]) -> Result<BatchId, BatchError> {
+        Ok(BatchId(0))
+    }
+
+    fn update_static_batch(&mut self, _: BatchId, _: &[SpriteInstance]) -> Result<(), BatchError> {
+        Ok(())
+    }
+
+    fn update_static_batch_range(
+        &mut self,
+        _: BatchId,
+        _: usize,
+        _: &[SpriteInstance],
+    ) -> Result<(), BatchError> {
+        Ok(())
+    }
+
+    fn remove_static_batch(&mut self, _: BatchId) {}
+
+    fn resize(&mut self, _surface: SurfaceId, _width: u32, _height: u32) {}
+
+    fn set_post_effects(&mut self, _: SurfaceId, _: Vec<PostEffect>) {}
 
-    fn resume(&mut self, _: &Window) {}
+    fn resume(&mut self, _: &dyn RawWindow, _: u32, _: u32) {}
 
     fn suspend(&mut self) {}
 }
@@ -0,0 +1,255 @@
+use std::f32::consts::PI;
+
+use crate::{material, ColorSpace, Material, MaterialId, SpriteRender, Uniform};
+
+/// The maximum number of lights that shade the sprites at once. Each light uses 3 uniform vectors
+/// of the fragment shader.
+pub const MAX_LIGHTS: usize = 8;
+
+/// The fragment shader of the material of [Lighting::material], after the `MAX_LIGHTS` define.
+const LIT_FRAGMENT_SOURCE: &str = r#"
+uniform vec3 ambient;
+uniform float lightCount;
+// xy: position, z: height, w: radius.
+uniform vec4 lightPosition[MAX_LIGHTS];
+// rgb: color multiplied by the intensity, a: falloff.
+uniform vec4 lightColor[MAX_LIGHTS];
+// xy: direction, z: cosine of the outer half angle, w: cosine of the inner half angle.
+uniform vec4 lightCone[MAX_LIGHTS];
+
+void main() {
+    vec4 textureColor = sampleTexture(TexCoord);
+    if (textureColor.a == 0.0 || color.a == 0.0) {
+        discard;
+    }
+    vec3 normal = sampleNormal(TexCoord);
+    vec3 light = ambient;
+    for (int i = 0; i < MAX_LIGHTS; i++) {
+        if (float(i) >= lightCount) break;
+        vec4 position = lightPosition[i];
+        vec3 toLight = vec3(position.xy - WorldPos, position.z);
+        float dist = length(toLight.xy);
+        float t = clamp(1.0 - dist / position.w, 0.0, 1.0);
+        if (t == 0.0) continue;
+        float attenuation = pow(t, lightColor[i].a);
+        vec4 cone = lightCone[i];
+        if (dist > 0.0) {
+            attenuation *= smoothstep(cone.z, cone.w, dot(-toLight.xy / dist, cone.xy));
+        }
+        float diffuse = max(dot(normal, normalize(toLight)), 0.0);
+        light += lightColor[i].rgb * attenuation * diffuse;
+    }
+    gl_FragColor = vec4(textureColor.rgb * color.rgb * light, textureColor.a * color.a);
+}
+"#;
+
+/// The shape of a [Light].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LightKind {
+    /// Emits in all directions.
+    Point,
+    /// Emits in a cone.
+    Spot {
+        /// The direction of the center of the cone, in radians, where 0.0 points to +x.
+        direction: f32,
+        /// The angle of the cone, in radians.
+        angle: f32,
+        /// The fraction of the cone, from its border, where the light fades out, from 0.0 (a hard
+        /// edge) to 1.0.
+        softness: f32,
+    },
+}
+
+/// A light of a [Lighting], in world space.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Light {
+    pub kind: LightKind,
+    pub position: [f32; 2],
+    /// The distance of the light above the plane of the sprites, in world units. Lower lights make
+    /// the relief of the normal maps more pronounced.
+    pub height: f32,
+    /// The color of the light, in the range 0.0 to 1.0.
+    pub color: [f32; 3],
+    /// The multiplier of the color.
+    pub intensity: f32,
+    /// The distance where the light fades to zero, in world units.
+    pub radius: f32,
+    /// The exponent of the attenuation by the distance. 1.0 fades linearly to the radius, and
+    /// higher values concentrate the light near its center.
+    pub falloff: f32,
+}
+impl Light {
+    /// Create a new white point light, at a height of a quarter of the radius.
+    pub fn point(x: f32, y: f32, radius: f32) -> Self {
+        Self {
+            kind: LightKind::Point,
+            position: [x, y],
+            height: radius * 0.25,
+            color: [1.0; 3],
+            intensity: 1.0,
+            radius,
+            falloff: 2.0,
+        }
+    }
+
+    /// Create a new white spot light, pointing to `direction`, with a cone of `angle` radians.
+    pub fn spot(x: f32, y: f32, radius: f32, direction: f32, angle: f32) -> Self {
+        Self {
+            kind: LightKind::Spot {
+                direction,
+                angle,
+                softness: 0.2,
+            },
+            ..Self::point(x, y, radius)
+        }
+    }
+
+    /// set the color, in a functional way (get owership of the value, and return it modified).
+    #[inline]
+    pub fn with_color(mut self, color: [f32; 3]) -> Self {
+        self.color = color;
+        self
+    }
+
+    /// set the intensity, in a functional way (get owership of the value, and return it modified).
+    #[inline]
+    pub fn with_intensity(mut self, intensity: f32) -> Self {
+        self.intensity = intensity;
+        self
+    }
+
+    /// set the height, in a functional way (get owership of the value, and return it modified).
+    #[inline]
+    pub fn with_height(mut self, height: f32) -> Self {
+        self.height = height;
+        self
+    }
+
+    /// set the falloff, in a functional way (get owership of the value, and return it modified).
+    #[inline]
+    pub fn with_falloff(mut self, falloff: f32) -> Self {
+        self.falloff = falloff;
+        self
+    }
+
+    /// The color multiplied by the intensity, converted to linear space if `linear`, because the
+    /// colors are then blended in linear space.
+    pub(crate) fn shader_color(&self, linear: bool) -> [f32; 3] {
+        let color = if linear {
+            self.color.map(material::srgb_to_linear)
+        } else {
+            self.color
+        };
+        color.map(|x| x * self.intensity)
+    }
+
+    /// The value of the `lightCone` uniform. Point lights use a cone that includes all directions.
+    pub(crate) fn cone(&self) -> [f32; 4] {
+        match self.kind {
+            LightKind::Point => [1.0, 0.0, -2.0, -1.0],
+            LightKind::Spot {
+                direction,
+                angle,
+                softness,
+            } => {
+                let half = angle.clamp(0.0, 2.0 * PI) * 0.5;
+                let outer = half.cos();
+                // smoothstep is undefined if its edges are equal.
+                let inner = (half * (1.0 - softness.clamp(0.0, 1.0)))
+                    .cos()
+                    .max(outer + 1e-4);
+                [direction.cos(), direction.sin(), outer, inner]
+            }
+        }
+    }
+}
+
+/// The lights that shade the sprites drawn with a lit material.
+///
+/// A lit material is created from [Lighting::material], and the sprites drawn with it are shaded
+/// by the ambient light plus the diffuse light of each [Light], using the normal maps associated to
+/// their textures with [SpriteRender::set_normal_map]. Textures without a normal map are shaded as
+/// flat surfaces facing the viewer. The lights are in world space, so they are drawn in the same
+/// place for any [Camera](crate::Camera), and the sprites stay in the same batches as without
+/// lighting.
+///
+/// The lights are given to the material as uniforms, by [Lighting::apply], which must be called
+/// again after the lights change. Only the first [MAX_LIGHTS] lights are used.
+///
+/// ```
+/// use sprite_render::{Light, Lighting, NoopSpriteRender, SpriteRender, TextureId};
+///
+/// let mut sprite_render = NoopSpriteRender;
+/// sprite_render.set_normal_map(TextureId(1), Some(TextureId(2)));
+///
+/// let mut lighting = Lighting::new([0.1, 0.1, 0.2]);
+/// lighting.lights.push(Light::point(4.0, 3.0, 5.0).with_color([1.0, 0.6, 0.2]));
+/// if let Ok(material) = lighting.material().create(&mut sprite_render) {
+///     lighting.apply(&mut sprite_render, material);
+/// }
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Lighting {
+    /// The light that reaches all sprites, in the range 0.0 to 1.0.
+    pub ambient: [f32; 3],
+    pub lights: Vec<Light>,
+}
+impl Lighting {
+    /// Create a new Lighting with the given ambient light, and no lights.
+    pub fn new(ambient: [f32; 3]) -> Self {
+        Self {
+            ambient,
+            lights: Vec::new(),
+        }
+    }
+
+    /// Create a lit [Material], that shades the sprites with the lights of a Lighting. The lights
+    /// are given by [Lighting::apply] after the material is created.
+    ///
+    /// The material samples the normal maps (see [Material::normal_maps]), so half of the texture
+    /// units are left to the sprite textures.
+    pub fn material(&self) -> Material {
+        let source = format!("#define MAX_LIGHTS {}\n{}", MAX_LIGHTS, LIT_FRAGMENT_SOURCE);
+        Material::new(&source)
+            .normal_maps(true)
+            .uniform("ambient", Uniform::Vec3(self.ambient))
+            .uniform("lightCount", Uniform::Float(0.0))
+    }
+
+    /// Set the ambient light and the lights in the uniforms of a material created from
+    /// [Lighting::material].
+    pub fn apply(&self, sprite_render: &mut dyn SpriteRender, material: MaterialId) {
+        if self.lights.len() > MAX_LIGHTS {
+            log::warn!(
+                "{} lights, only the first {} are used",
+                self.lights.len(),
+                MAX_LIGHTS
+            );
+        }
+        let linear = sprite_render.color_space() == ColorSpace::Linear;
+        let ambient = if linear {
+            self.ambient.map(material::srgb_to_linear)
+        } else {
+            self.ambient
+        };
+        let ambient = Uniform::Vec3(ambient);
+        sprite_render.set_material_uniform(material, "ambient", ambient);
+        let lights = &self.lights[..self.lights.len().min(MAX_LIGHTS)];
+        let count = Uniform::Float(lights.len() as f32);
+        sprite_render.set_material_uniform(material, "lightCount", count);
+        for (i, light) in lights.iter().enumerate() {
+            let [x, y] = light.position;
+            let position = [x, y, light.height, light.radius];
+            let [r, g, b] = light.shader_color(linear);
+            let color = [r, g, b, light.falloff];
+            for (name, value) in [
+                ("lightPosition", position),
+                ("lightColor", color),
+                ("lightCone", light.cone()),
+            ] {
+                let name = format!("{}[{}]", name, i);
+                sprite_render.set_material_uniform(material, &name, Uniform::Vec4(value));
+            }
+        }
+    }
+}
@@ -0,0 +1,424 @@
+#[cfg(any(feature = "opengl", feature = "webgl"))]
+use crate::ColorSpace;
+use crate::{MaterialId, ShaderError, SpriteRender, TextureId};
+
+/// The value of a uniform of a [Material].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Uniform {
+    /// A `float` uniform.
+    Float(f32),
+    /// A `vec2` uniform.
+    Vec2([f32; 2]),
+    /// A `vec3` uniform.
+    Vec3([f32; 3]),
+    /// A `vec4` uniform.
+    Vec4([f32; 4]),
+    /// A `sampler2D` uniform.
+    ///
+    /// Each texture uniform uses one texture unit, reducing the number of textures that can be
+    /// draw in a single draw call.
+    Texture(TextureId),
+}
+
+/// How the colors drawn with a [Material] are combined with the colors already in the target.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BlendMode {
+    /// The colors are mixed by their alpha, as in `src * src.a + dst * (1 - src.a)`.
+    #[default]
+    Alpha,
+    /// The colors are added, scaled by their alpha, as in `src * src.a + dst`. Used for glowing
+    /// effects, like fire and sparks.
+    Additive,
+    /// The colors are multiplied, as in `src * dst`. The alpha is ignored, so transparent regions
+    /// should be white.
+    Multiply,
+    /// The colors are premultiplied by their alpha, as in `src + dst * (1 - src.a)`.
+    Premultiplied,
+}
+
+/// A custom fragment shader, used to draw the sprites that reference it.
+///
+/// The fragment shader source is written in GLSL ES 1.00 (the same of WebGL 1.0), and must contain
+/// a `void main()` function that writes to `gl_FragColor`. It must not contain a `#version`
+/// directive. The following prelude is prepended to it:
+///
+/// ```glsl
+/// precision mediump float;
+///
+/// // The color of the sprite, in the range 0.0 to 1.0.
+/// varying vec4 color;
+/// // The texture coordinate of the fragment, interpolated from the sprite's uv_rect.
+/// varying vec2 TexCoord;
+/// // The position of the fragment in world space.
+/// varying vec2 WorldPos;
+///
+/// // Sample the texture of the sprite at the given texture coordinates.
+/// vec4 sampleTexture(vec2 uv);
+///
+/// // Only with `Material::normal_maps(true)`. Sample the normal map of the sprite texture (see
+/// // `SpriteRender::set_normal_map`), as a unit vector in world space, with z pointing to the
+/// // viewer. Returns `vec3(0.0, 0.0, 1.0)` if the texture has no normal map.
+/// vec3 sampleNormal(vec2 uv);
+/// ```
+///
+/// Any other uniform must be declared in the source, and its value given with
+/// [Material::uniform] or [SpriteRender::set_material_uniform].
+///
+/// A material also chooses how its colors are blended, with [Material::blend]. The
+/// [Default](Material::default) material has the same shader of the sprites without a material, so
+/// it can be used to only change the blend mode.
+///
+/// If the [SpriteRender] uses [ColorSpace::Linear](crate::ColorSpace::Linear), `color` and the
+/// sampled colors are in linear space, and `gl_FragColor` must be written in linear space.
+///
+/// # Example
+///
+/// ```
+/// # use sprite_render::{Material, Uniform};
+/// let material = Material::new(
+///     r#"
+///     uniform float time;
+///     void main() {
+///         vec4 texture_color = sampleTexture(TexCoord + vec2(sin(time + TexCoord.y * 10.0) * 0.01, 0.0));
+///         gl_FragColor = texture_color * color;
+///     }
+///     "#,
+/// )
+/// .uniform("time", Uniform::Float(0.0));
+/// ```
+#[derive(Clone, Debug)]
+pub struct Material {
+    #[cfg_attr(not(any(feature = "opengl", feature = "webgl")), allow(dead_code))]
+    pub(crate) fragment_source: String,
+    pub(crate) uniforms: Vec<(String, Uniform)>,
+    pub(crate) blend: BlendMode,
+    /// If the normal maps of the sprite textures are bound, for `sampleNormal`.
+    pub(crate) normal_maps: bool,
+    /// The file the fragment shader source was loaded from, to be watched for changes.
+    #[cfg(all(feature = "hot-reload", not(target_arch = "wasm32")))]
+    pub(crate) path: Option<std::path::PathBuf>,
+}
+impl Default for Material {
+    fn default() -> Self {
+        Self::new(DEFAULT_FRAGMENT_SOURCE)
+    }
+}
+impl Material {
+    /// Create a new Material, with the given fragment shader source.
+    pub fn new(fragment_source: &str) -> Self {
+        Self {
+            fragment_source: fragment_source.to_string(),
+            uniforms: Vec::new(),
+            blend: BlendMode::Alpha,
+            normal_maps: false,
+            #[cfg(all(feature = "hot-reload", not(target_arch = "wasm32")))]
+            path: None,
+        }
+    }
+
+    /// Create a new Material, with the fragment shader source loaded from the given file.
+    ///
+    /// The file is watched for changes, and the material is recompiled between frames when it
+    /// changes. If the compilation fails, the error is logged and the previous shader is kept.
+    #[cfg(all(feature = "hot-reload", not(target_arch = "wasm32")))]
+    pub fn from_file(path: impl AsRef<std::path::Path>) -> std::io::Result<Self> {
+        let path = path.as_ref();
+        let mut material = Self::new(&std::fs::read_to_string(path)?);
+        material.path = Some(path.to_path_buf());
+        Ok(material)
+    }
+
+    /// Set the initial value of a uniform.
+    pub fn uniform(mut self, name: &str, value: Uniform) -> Self {
+        set_uniform(&mut self.uniforms, name, value);
+        self
+    }
+
+    /// Set how the colors are blended. The default is [BlendMode::Alpha].
+    pub fn blend(mut self, blend: BlendMode) -> Self {
+        self.blend = blend;
+        self
+    }
+
+    /// Set if the normal maps of the sprite textures are available in the shader, with
+    /// `sampleNormal`. The default is false.
+    ///
+    /// The normal maps use one texture unit for each sprite texture, halving the number of
+    /// textures that can be draw in a single draw call.
+    pub fn normal_maps(mut self, normal_maps: bool) -> Self {
+        self.normal_maps = normal_maps;
+        self
+    }
+
+    /// Create this material in the given [SpriteRender].
+    ///
+    /// Same as calling `sprite_render.new_material(self)`.
+    pub fn create(self, sprite_render: &mut dyn SpriteRender) -> Result<MaterialId, ShaderError> {
+        sprite_render.new_material(self)
+    }
+
+    /// The number of texture uniforms of this material.
+    #[cfg(any(feature = "opengl", feature = "webgl"))]
+    pub(crate) fn texture_count(&self) -> usize {
+        self.uniforms
+            .iter()
+            .filter(|(_, x)| matches!(x, Uniform::Texture(_)))
+            .count()
+    }
+}
+
+/// Set the value of the uniform `name` in `uniforms`, adding it if it does not exist.
+pub(crate) fn set_uniform(uniforms: &mut Vec<(String, Uniform)>, name: &str, value: Uniform) {
+    match uniforms.iter_mut().find(|(x, _)| x == name) {
+        Some((_, x)) => *x = value,
+        None => uniforms.push((name.to_string(), value)),
+    }
+}
+
+/// If the uniform `name` can be set to `value` after the creation of the material. The texture
+/// uniforms cannot be added or removed, because they change the number of texture units used.
+#[cfg(any(feature = "opengl", feature = "webgl"))]
+pub(crate) fn can_set_uniform(uniforms: &[(String, Uniform)], name: &str, value: &Uniform) -> bool {
+    let is_texture = |x: &Uniform| matches!(x, Uniform::Texture(_));
+    match uniforms.iter().find(|(x, _)| x == name) {
+        Some((_, x)) => is_texture(x) == is_texture(value),
+        None => !is_texture(value),
+    }
+}
+
+// How the color sampled from a texture is remapped in the shader, when the texture format has no
+// equivalent in OpenGL. It is encoded in the texture index of the vertices, as `unit | swizzle << 8`.
+
+/// Sample the color unchanged.
+#[cfg(any(feature = "opengl", feature = "webgl"))]
+pub(crate) const SWIZZLE_NONE: u16 = 0;
+/// Sample as `(1, 1, 1, r)`, for alpha masks.
+#[cfg(any(feature = "opengl", feature = "webgl"))]
+pub(crate) const SWIZZLE_ALPHA: u16 = 1;
+/// Sample as `(r, r, r, g)`, for luminance-alpha stored in two channels.
+#[cfg(any(feature = "opengl", feature = "webgl"))]
+pub(crate) const SWIZZLE_LUMINANCE_ALPHA: u16 = 2;
+/// Remap RGBA8888 `pixels` read from a texture to the color they are sampled as, with the given
+/// swizzle.
+#[cfg(any(feature = "opengl", feature = "webgl"))]
+pub(crate) fn swizzle_pixels(swizzle: u16, pixels: &mut [u8]) {
+    for pixel in pixels.chunks_exact_mut(4) {
+        match swizzle & !SRGB_DECODE {
+            SWIZZLE_ALPHA => pixel.copy_from_slice(&[255, 255, 255, pixel[0]]),
+            SWIZZLE_LUMINANCE_ALPHA => {
+                pixel.copy_from_slice(&[pixel[0], pixel[0], pixel[0], pixel[1]])
+            }
+            _ => return,
+        }
+    }
+}
+
+/// Flag added to the swizzle when the texture is stored in sRGB, but the sampled color is not
+/// converted to linear by the hardware. Only used in [ColorSpace::Linear].
+#[cfg(any(feature = "opengl", feature = "webgl"))]
+pub(crate) const SRGB_DECODE: u16 = 4;
+
+/// Convert a sRGB color component to linear.
+pub(crate) fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Defines and functions prepended to the vertex and fragment shaders of the sprites, after the
+/// precision statement, for the given color space.
+#[cfg(any(feature = "opengl", feature = "webgl"))]
+pub(crate) fn color_space_prelude(color_space: ColorSpace) -> &'static str {
+    match color_space {
+        ColorSpace::Gamma => "",
+        ColorSpace::Linear => {
+            r#"
+#define LINEAR_COLOR
+vec3 srgbToLinear(vec3 c) {
+    return mix(c / 12.92, pow((c + 0.055) / 1.055, vec3(2.4)), step(0.04045, c));
+}
+vec3 linearToSrgb(vec3 c) {
+    c = clamp(c, 0.0, 1.0);
+    return mix(c * 12.92, 1.055 * pow(c, vec3(1.0 / 2.4)) - 0.055, step(0.0031308, c));
+}
+"#
+        }
+    }
+}
+
+/// The fragment shader of the default material.
+pub(crate) const DEFAULT_FRAGMENT_SOURCE: &str = r#"
+void main() {
+    vec4 textureColor = sampleTexture(TexCoord);
+    if (textureColor.a == 0.0 || color.a == 0.0) {
+        discard;
+    }
+    gl_FragColor = textureColor*color;
+}
+"#;
+
+/// Prepend the prelude to the fragment shader source of a material, where `texture_units` is the
+/// number of texture units available to the sprite textures, and `normal_maps` is if their normal
+/// maps are bound in the units after them. Does not include the `#version` directive.
+///
+/// In [ColorSpace::Linear], the `main` of the material is wrapped to convert its output to sRGB
+/// when the `encodeSrgb` uniform is true, because the framebuffer does not do it.
+#[cfg(any(feature = "opengl", feature = "webgl"))]
+pub(crate) fn fragment_source(
+    source: &str,
+    texture_units: i32,
+    normal_maps: bool,
+    color_space: ColorSpace,
+) -> String {
+    format!(
+        r#"
+#define MAX_TEXTURE_IMAGE_UNITS {}
+{}
+precision mediump float;
+{}
+uniform sampler2D text[MAX_TEXTURE_IMAGE_UNITS];
+#ifdef NORMAL_MAPS
+uniform sampler2D normalMaps[MAX_TEXTURE_IMAGE_UNITS];
+uniform bool hasNormalMap[MAX_TEXTURE_IMAGE_UNITS];
+#endif
+#ifdef LINEAR_COLOR
+uniform bool encodeSrgb;
+#endif
+
+varying vec4 color;
+varying vec2 TexCoord;
+varying vec2 WorldPos;
+varying float textureIndex;
+varying float textureSwizzle;
+
+vec4 sampleTexture(vec2 uv) {{
+    int t = int(textureIndex);
+    vec4 textureColor;
+    for (int i = 0; i < MAX_TEXTURE_IMAGE_UNITS; i++ ) {{
+        if (i == t) textureColor = texture2D(text[i], uv);
+    }}
+    float swizzle = textureSwizzle;
+#ifdef LINEAR_COLOR
+    bool decode = swizzle > 3.5;
+    if (decode) swizzle -= 4.0;
+#endif
+    if (swizzle > 1.5) {{
+        textureColor = textureColor.rrrg;
+    }} else if (swizzle > 0.5) {{
+        textureColor = vec4(1.0, 1.0, 1.0, textureColor.r);
+    }}
+#ifdef LINEAR_COLOR
+    if (decode) textureColor.rgb = srgbToLinear(textureColor.rgb);
+#endif
+    return textureColor;
+}}
+
+#ifdef NORMAL_MAPS
+vec3 sampleNormal(vec2 uv) {{
+    int t = int(textureIndex);
+    vec3 normal = vec3(0.5, 0.5, 1.0);
+    for (int i = 0; i < MAX_TEXTURE_IMAGE_UNITS; i++ ) {{
+        if (i == t && hasNormalMap[i]) normal = texture2D(normalMaps[i], uv).rgb;
+    }}
+    // the green channel of the normal maps points up, but the y axis of the world points down.
+    normal = normal * 2.0 - 1.0;
+    return normalize(vec3(normal.x, -normal.y, normal.z));
+}}
+#endif
+
+#ifdef LINEAR_COLOR
+#define main materialMain
+#endif
+{}
+#ifdef LINEAR_COLOR
+#undef main
+void main() {{
+    materialMain();
+    if (encodeSrgb) gl_FragColor.rgb = linearToSrgb(gl_FragColor.rgb);
+}}
+#endif
+"#,
+        texture_units,
+        if normal_maps {
+            "#define NORMAL_MAPS"
+        } else {
+            ""
+        },
+        color_space_prelude(color_space),
+        source
+    )
+}
+
+/// The `hasNormalMap` uniform of a batch drawn with a material that samples the normal maps: if
+/// the sprite texture in each texture unit has a normal map bound after it.
+#[cfg(any(feature = "opengl", feature = "webgl", test))]
+#[derive(Debug, Default)]
+pub(crate) struct NormalMapFlags {
+    flags: Vec<i32>,
+}
+#[cfg(any(feature = "opengl", feature = "webgl", test))]
+impl NormalMapFlags {
+    /// Start a new batch with `texture_units` sprite textures, none of them with a normal map.
+    /// Must be called before binding the textures of every batch, or the flags of the previous
+    /// batch are reused.
+    pub(crate) fn clear(&mut self, texture_units: i32) {
+        self.flags.clear();
+        self.flags.resize(texture_units as usize, 0);
+    }
+
+    /// Mark the sprite texture in `unit` as having a normal map.
+    pub(crate) fn set(&mut self, unit: u32) {
+        self.flags[unit as usize] = 1;
+    }
+
+    /// The value of the uniform, with one flag for each of the `texture_units` of the batch.
+    pub(crate) fn uniform(&self, texture_units: i32) -> &[i32] {
+        debug_assert_eq!(
+            self.flags.len(),
+            texture_units as usize,
+            "the normal maps of the batch were not cleared"
+        );
+        &self.flags
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normal_map_flags_of_quads() {
+        let mut flags = NormalMapFlags::default();
+        flags.clear(4);
+        flags.set(0);
+        flags.set(2);
+        assert_eq!(flags.uniform(4), &[1, 0, 1, 0]);
+    }
+
+    #[test]
+    fn normal_map_flags_of_mesh_after_quads() {
+        let mut flags = NormalMapFlags::default();
+        flags.clear(4);
+        flags.set(1);
+        flags.set(3);
+
+        // a mesh has a single texture, in the unit 0.
+        flags.clear(4);
+        flags.set(0);
+        assert_eq!(flags.uniform(4), &[1, 0, 0, 0]);
+
+        // the texture of the mesh has no normal map.
+        flags.clear(4);
+        assert_eq!(flags.uniform(4), &[0, 0, 0, 0]);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "not cleared")]
+    fn normal_map_flags_not_cleared() {
+        let flags = NormalMapFlags::default();
+        flags.uniform(4);
+    }
+}
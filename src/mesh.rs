@@ -0,0 +1,257 @@
+#[cfg(any(test, feature = "opengl", feature = "webgl"))]
+use std::{borrow::Cow, collections::HashMap};
+
+use crate::{MaterialId, TextureId};
+
+/// The maximum number of vertices that can be addressed by u16 indices.
+#[cfg(any(test, feature = "opengl", feature = "webgl"))]
+const MAX_CHUNK_VERTICES: usize = u16::MAX as usize + 1;
+
+/// A vertex of a [Mesh].
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Vertex {
+    /// The position of the vertex, in world space.
+    pub position: [f32; 2],
+    /// The texture coordinate of the vertex.
+    pub uv: [f32; 2],
+    /// The color that multiplies the texture color, in the RGBA format.
+    pub color: [u8; 4],
+}
+impl Vertex {
+    pub fn new(position: [f32; 2], uv: [f32; 2], color: [u8; 4]) -> Self {
+        Self {
+            position,
+            uv,
+            color,
+        }
+    }
+}
+
+/// The indices of the vertices of each triangle of a [Mesh].
+#[derive(Clone, Copy, Debug)]
+pub enum Indices<'a> {
+    U16(&'a [u16]),
+    U32(&'a [u32]),
+}
+impl Indices<'_> {
+    #[cfg(any(test, feature = "opengl", feature = "webgl"))]
+    fn len(&self) -> usize {
+        match self {
+            Indices::U16(x) => x.len(),
+            Indices::U32(x) => x.len(),
+        }
+    }
+
+    #[cfg(any(test, feature = "opengl", feature = "webgl"))]
+    fn get(&self, i: usize) -> u32 {
+        match self {
+            Indices::U16(x) => x[i] as u32,
+            Indices::U32(x) => x[i],
+        }
+    }
+}
+impl<'a> From<&'a [u16]> for Indices<'a> {
+    fn from(value: &'a [u16]) -> Self {
+        Indices::U16(value)
+    }
+}
+impl<'a> From<&'a [u32]> for Indices<'a> {
+    fn from(value: &'a [u32]) -> Self {
+        Indices::U32(value)
+    }
+}
+
+/// A list of textured triangles, drawn by [Renderer::draw_mesh](crate::Renderer::draw_mesh) with
+/// the same camera transform and shaders as the sprites.
+///
+/// Each three indices form a triangle. Meshes with more than 65536 vertices are split in multiple
+/// draw calls, so u32 indices are supported even where the graphics API only supports u16 indices.
+#[derive(Clone, Copy, Debug)]
+pub struct Mesh<'a> {
+    pub vertices: &'a [Vertex],
+    pub indices: Indices<'a>,
+    pub texture: TextureId,
+    /// The material used to draw the mesh.
+    pub material: MaterialId,
+}
+impl<'a> Mesh<'a> {
+    /// Create a new Mesh with the given vertices, indices and texture, and the default material.
+    pub fn new(
+        vertices: &'a [Vertex],
+        indices: impl Into<Indices<'a>>,
+        texture: TextureId,
+    ) -> Self {
+        Self {
+            vertices,
+            indices: indices.into(),
+            texture,
+            material: MaterialId::default(),
+        }
+    }
+
+    /// set the material of the mesh, in a functional way (get owership of the value, and return it
+    /// modified).
+    pub fn with_material(mut self, material: MaterialId) -> Self {
+        self.material = material;
+        self
+    }
+}
+
+/// A part of a [Mesh] that can be drawn with u16 indices.
+#[cfg(any(test, feature = "opengl", feature = "webgl"))]
+pub(crate) struct MeshChunk<'a> {
+    pub vertices: Cow<'a, [Vertex]>,
+    pub indices: Cow<'a, [u16]>,
+}
+
+/// Split a mesh in chunks of at most 65536 vertices. Returns no chunks if the mesh has indices out
+/// of bounds.
+#[cfg(any(test, feature = "opengl", feature = "webgl"))]
+pub(crate) fn chunks<'a>(mesh: &Mesh<'a>) -> Vec<MeshChunk<'a>> {
+    let len = mesh.indices.len() / 3 * 3;
+    if len != mesh.indices.len() {
+        log::warn!(
+            "mesh has {} indices, which is not a multiple of 3",
+            mesh.indices.len()
+        );
+    }
+    if (0..len).any(|i| mesh.indices.get(i) as usize >= mesh.vertices.len()) {
+        log::error!("mesh has indices out of bounds");
+        return Vec::new();
+    }
+    if len == 0 {
+        return Vec::new();
+    }
+
+    if mesh.vertices.len() <= MAX_CHUNK_VERTICES {
+        let indices = match mesh.indices {
+            Indices::U16(x) => Cow::Borrowed(&x[..len]),
+            Indices::U32(x) => Cow::Owned(x[..len].iter().map(|&i| i as u16).collect()),
+        };
+        return vec![MeshChunk {
+            vertices: Cow::Borrowed(mesh.vertices),
+            indices,
+        }];
+    }
+
+    // remap the vertices of each chunk of triangles to local indices.
+    let mut chunks = Vec::new();
+    let mut map = HashMap::new();
+    let mut vertices = Vec::new();
+    let mut indices = Vec::new();
+    for triangle in (0..len).step_by(3) {
+        if vertices.len() + 3 > MAX_CHUNK_VERTICES {
+            map.clear();
+            chunks.push(MeshChunk {
+                vertices: Cow::Owned(std::mem::take(&mut vertices)),
+                indices: Cow::Owned(std::mem::take(&mut indices)),
+            });
+        }
+        for i in triangle..triangle + 3 {
+            let index = mesh.indices.get(i);
+            let local = *map.entry(index).or_insert_with(|| {
+                vertices.push(mesh.vertices[index as usize]);
+                (vertices.len() - 1) as u16
+            });
+            indices.push(local);
+        }
+    }
+    chunks.push(MeshChunk {
+        vertices: Cow::Owned(vertices),
+        indices: Cow::Owned(indices),
+    });
+    chunks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Vertices whose x is their index.
+    fn vertices(count: usize) -> Vec<Vertex> {
+        (0..count)
+            .map(|i| Vertex::new([i as f32, 0.0], [0.0; 2], [255; 4]))
+            .collect()
+    }
+
+    /// The triangles of the chunks, as the x of their vertices.
+    fn triangles(chunks: &[MeshChunk]) -> Vec<[f32; 3]> {
+        chunks
+            .iter()
+            .flat_map(|chunk| {
+                assert!(chunk.vertices.len() <= MAX_CHUNK_VERTICES);
+                chunk
+                    .indices
+                    .chunks(3)
+                    .map(move |t| [0, 1, 2].map(|i| chunk.vertices[t[i] as usize].position[0]))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn single_chunk() {
+        let vertices = vertices(4);
+        let indices: &[u16] = &[0, 1, 2, 2, 1, 3];
+        let split = chunks(&Mesh::new(&vertices, indices, TextureId(0)));
+        assert_eq!(split.len(), 1);
+        assert!(matches!(split[0].vertices, Cow::Borrowed(_)));
+        assert_eq!(split[0].indices[..], *indices);
+
+        let indices: &[u32] = &[0, 1, 2, 2, 1, 3];
+        let split = chunks(&Mesh::new(&vertices, indices, TextureId(0)));
+        assert_eq!(split.len(), 1);
+        assert_eq!(split[0].indices[..], [0, 1, 2, 2, 1, 3]);
+    }
+
+    #[test]
+    fn invalid_indices() {
+        let vertices = vertices(4);
+        // the incomplete triangle is ignored.
+        let indices: &[u16] = &[0, 1, 2, 3];
+        let split = chunks(&Mesh::new(&vertices, indices, TextureId(0)));
+        assert_eq!(split[0].indices[..], [0, 1, 2]);
+
+        let indices: &[u16] = &[0, 1, 4];
+        assert!(chunks(&Mesh::new(&vertices, indices, TextureId(0))).is_empty());
+        let indices: &[u32] = &[];
+        assert!(chunks(&Mesh::new(&vertices, indices, TextureId(0))).is_empty());
+    }
+
+    #[test]
+    fn reindex_large_mesh() {
+        let count = 70_000;
+        let vertices = vertices(count);
+        let indices = (0..count as u32 / 3 * 3).collect::<Vec<_>>();
+        let split = chunks(&Mesh::new(&vertices, indices.as_slice(), TextureId(0)));
+        assert_eq!(split.len(), 2);
+        // the triangles are not split between chunks.
+        assert_eq!(split[0].vertices.len(), 65535);
+        assert_eq!(split[1].vertices.len(), count / 3 * 3 - 65535);
+
+        let expected = indices
+            .chunks(3)
+            .map(|t| t.iter().map(|&i| i as f32).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        let triangles = triangles(&split);
+        assert_eq!(triangles.len(), expected.len());
+        assert!(triangles.iter().zip(&expected).all(|(a, b)| a[..] == b[..]));
+    }
+
+    #[test]
+    fn reindex_shared_vertices() {
+        // a fan around the vertex 0, which is copied to each chunk.
+        let count = 140_000;
+        let vertices = vertices(count);
+        let indices = (1..count as u32 - 1)
+            .flat_map(|i| [0, i, i + 1])
+            .collect::<Vec<_>>();
+        let split = chunks(&Mesh::new(&vertices, indices.as_slice(), TextureId(0)));
+        assert_eq!(split.len(), 3);
+        let triangles = triangles(&split);
+        assert_eq!(triangles.len(), count - 2);
+        for (i, triangle) in triangles.iter().enumerate() {
+            let i = i as f32 + 1.0;
+            assert_eq!(*triangle, [0.0, i, i + 1.0]);
+        }
+    }
+}
@@ -0,0 +1,330 @@
+use crate::{MaterialId, SpriteInstance, TextureId};
+
+/// The maximum number of tiles in a column or row of a tiled slice.
+const MAX_TILES: f32 = 1024.0;
+
+/// How the edges or the center of a [NineSlice] fill their area.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SliceMode {
+    /// Stretch the slice to fill the area.
+    #[default]
+    Stretch,
+    /// Repeat the slice with its original size, clipping the last repetition.
+    Tile,
+}
+
+/// A sprite divided in a 3x3 grid by its border insets, whose corners keep their size when drawn
+/// with any size, while the edges and the center stretch or tile to fill the rest. Used to draw UI
+/// panels and buttons.
+///
+/// [NineSlice::expand] generates the [SpriteInstance]s of the 9 slices, to be drawn with
+/// [Renderer::draw_sprites](crate::Renderer::draw_sprites).
+#[derive(Clone, Debug)]
+pub struct NineSlice {
+    pub texture: TextureId,
+    /// The region of the texture, in normalized texture coordinates.
+    pub uv_rect: [f32; 4],
+    /// The size of the texture, in pixels, used to convert the insets to texture coordinates.
+    pub texture_size: [u32; 2],
+    /// The size of the left, right, top and bottom borders, in pixels of the texture.
+    pub insets: [f32; 4],
+    /// The size of a pixel of the texture, in world space. The default is 1.0.
+    pub scale: f32,
+    /// How the edges fill their length. The default is [SliceMode::Stretch].
+    pub edge_mode: SliceMode,
+    /// How the center fills its area. The default is [SliceMode::Stretch].
+    pub center_mode: SliceMode,
+    pub color: [u8; 4],
+    pub material: MaterialId,
+}
+impl NineSlice {
+    /// Create a new NineSlice from the `uv_rect` region of a texture with the given size in pixels,
+    /// and with the left, right, top and bottom `insets` in pixels.
+    /// The default color is white ([255, 255, 255, 255]).
+    pub fn new(
+        texture: TextureId,
+        texture_width: u32,
+        texture_height: u32,
+        uv_rect: [f32; 4],
+        insets: [f32; 4],
+    ) -> Self {
+        Self {
+            texture,
+            uv_rect,
+            texture_size: [texture_width, texture_height],
+            insets,
+            scale: 1.0,
+            edge_mode: SliceMode::Stretch,
+            center_mode: SliceMode::Stretch,
+            color: [0xff; 4],
+            material: MaterialId::default(),
+        }
+    }
+
+    /// set the size of a pixel of the texture in world space, in a functional way (get owership of
+    /// the value, and return it modified).
+    #[inline]
+    pub fn with_scale(mut self, scale: f32) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    /// set how the edges and the center fill their area, in a functional way (get owership of the
+    /// value, and return it modified).
+    #[inline]
+    pub fn with_modes(mut self, edge_mode: SliceMode, center_mode: SliceMode) -> Self {
+        self.edge_mode = edge_mode;
+        self.center_mode = center_mode;
+        self
+    }
+
+    /// set the color of the slices, in a functional way (get owership of the value, and return it
+    /// modified).
+    #[inline]
+    pub fn with_color(mut self, color: [u8; 4]) -> Self {
+        self.color = color;
+        self
+    }
+
+    /// set the material of the slices, in a functional way (get owership of the value, and return
+    /// it modified).
+    #[inline]
+    pub fn with_material(mut self, material: MaterialId) -> Self {
+        self.material = material;
+        self
+    }
+
+    /// Push the sprites of the nine slices to `sprites`, filling a rectangle with center in (x,y),
+    /// the given width and height, and rotated by `angle` counterclockwise radians.
+    ///
+    /// If the rectangle is smaller than the borders, the borders are shrunk proportionally.
+    pub fn expand(
+        &self,
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+        angle: f32,
+        sprites: &mut Vec<SpriteInstance>,
+    ) {
+        let [left, right, top, bottom] = self.insets;
+        let [u, v, uv_width, uv_height] = self.uv_rect;
+        let du = 1.0 / self.texture_size[0].max(1) as f32;
+        let dv = 1.0 / self.texture_size[1].max(1) as f32;
+
+        let columns = axis(
+            [u, u + left * du, u + uv_width - right * du, u + uv_width],
+            [left * self.scale, right * self.scale],
+            width,
+        );
+        let rows = axis(
+            [v, v + top * dv, v + uv_height - bottom * dv, v + uv_height],
+            [top * self.scale, bottom * self.scale],
+            height,
+        );
+
+        let (sin, cos) = angle.sin_cos();
+        for row in &rows {
+            for column in &columns {
+                let (i, j) = (column.index, row.index);
+                let mode = match (i, j) {
+                    (1, 1) => self.center_mode,
+                    (1, _) | (_, 1) => self.edge_mode,
+                    _ => SliceMode::Stretch,
+                };
+                // corners never tile, and edges only tile along their length.
+                let tile_x = mode == SliceMode::Tile && i == 1;
+                let tile_y = mode == SliceMode::Tile && j == 1;
+                for (cx, cw, cu, cuw) in column.tiles(tile_x, du / self.scale) {
+                    for (cy, ch, cv, cvh) in row.tiles(tile_y, dv / self.scale) {
+                        // the center of the tile, relative to the center of the rectangle.
+                        let lx = cx + cw / 2.0 - width / 2.0;
+                        let ly = cy + ch / 2.0 - height / 2.0;
+                        sprites.push(SpriteInstance {
+                            scale: [cw, ch],
+                            angle,
+                            uv_rect: [cu, cv, cuw, cvh],
+                            color: self.color,
+                            pos: [x + cos * lx - sin * ly, y + sin * lx + cos * ly],
+                            texture: self.texture,
+                            material: self.material,
+                        });
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// A column or row of the nine slices.
+struct Span {
+    /// 0 for the first border, 1 for the center and 2 for the last border.
+    index: usize,
+    /// The start and the size of the span in the rectangle.
+    start: f32,
+    size: f32,
+    /// The start and the size of the span in texture coordinates.
+    uv_start: f32,
+    uv_size: f32,
+}
+impl Span {
+    /// Divide the span in tiles of the original size, or return the whole span if not `tile`.
+    /// `uv_per_unit` is the size of a world space unit in texture coordinates.
+    fn tiles(&self, tile: bool, uv_per_unit: f32) -> Vec<(f32, f32, f32, f32)> {
+        let tile_size = self.uv_size / uv_per_unit;
+        // stretch if the tiles are too small, to not generate a excessive number of sprites.
+        let count = self.size / tile_size;
+        if !tile || tile_size <= 0.0 || count.is_nan() || count > MAX_TILES {
+            return vec![(self.start, self.size, self.uv_start, self.uv_size)];
+        }
+        let mut tiles = Vec::new();
+        let mut offset = 0.0;
+        while offset < self.size {
+            let size = tile_size.min(self.size - offset);
+            let uv_size = self.uv_size * size / tile_size;
+            tiles.push((self.start + offset, size, self.uv_start, uv_size));
+            offset += tile_size;
+        }
+        tiles
+    }
+}
+
+/// Compute the 3 spans of a axis, from the 4 texture coordinates that bound them, the size of the
+/// two borders and the total size. Spans with no size are skipped.
+fn axis(uv: [f32; 4], borders: [f32; 2], size: f32) -> Vec<Span> {
+    let [mut first, mut last] = borders;
+    if first + last > size && first + last > 0.0 {
+        let shrink = size / (first + last);
+        first *= shrink;
+        last *= shrink;
+    }
+    let sizes = [first, size - first - last, last];
+    let mut start = 0.0;
+    let mut spans = Vec::with_capacity(3);
+    for i in 0..3 {
+        if sizes[i] > 0.0 {
+            spans.push(Span {
+                index: i,
+                start,
+                size: sizes[i],
+                uv_start: uv[i],
+                uv_size: uv[i + 1] - uv[i],
+            });
+        }
+        start += sizes[i];
+    }
+    spans
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expand(slice: &NineSlice, width: f32, height: f32, angle: f32) -> Vec<SpriteInstance> {
+        let mut sprites = Vec::new();
+        slice.expand(0.0, 0.0, width, height, angle, &mut sprites);
+        sprites
+    }
+
+    #[test]
+    fn stretch() {
+        let slice = NineSlice::new(TextureId(1), 32, 32, [0.0, 0.0, 1.0, 1.0], [8.0; 4]);
+        let sprites = expand(&slice, 64.0, 48.0, 0.0);
+        assert_eq!(sprites.len(), 9);
+
+        let top_left = &sprites[0];
+        assert_eq!(top_left.scale, [8.0, 8.0]);
+        assert_eq!(top_left.pos, [-28.0, -20.0]);
+        assert_eq!(top_left.uv_rect, [0.0, 0.0, 0.25, 0.25]);
+
+        let center = &sprites[4];
+        assert_eq!(center.scale, [48.0, 32.0]);
+        assert_eq!(center.pos, [0.0, 0.0]);
+        assert_eq!(center.uv_rect, [0.25, 0.25, 0.5, 0.5]);
+
+        let bottom_right = &sprites[8];
+        assert_eq!(bottom_right.scale, [8.0, 8.0]);
+        assert_eq!(bottom_right.pos, [28.0, 20.0]);
+        assert_eq!(bottom_right.uv_rect, [0.75, 0.75, 0.25, 0.25]);
+    }
+
+    #[test]
+    fn scale_and_angle() {
+        let slice =
+            NineSlice::new(TextureId(1), 32, 32, [0.0, 0.0, 1.0, 1.0], [8.0; 4]).with_scale(2.0);
+        let sprites = expand(&slice, 64.0, 48.0, std::f32::consts::FRAC_PI_2);
+        let top_left = &sprites[0];
+        assert_eq!(top_left.scale, [16.0, 16.0]);
+        assert_eq!(top_left.angle, std::f32::consts::FRAC_PI_2);
+        // (-24, -16) rotated a quarter turn counterclockwise.
+        assert!((top_left.pos[0] - 16.0).abs() < 1e-4);
+        assert!((top_left.pos[1] + 24.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn shrinking_borders() {
+        let slice = NineSlice::new(
+            TextureId(1),
+            32,
+            32,
+            [0.0, 0.0, 1.0, 1.0],
+            [8.0, 8.0, 4.0, 12.0],
+        );
+        // the horizontal borders are shrunk to half, and there is no center column.
+        let sprites = expand(&slice, 8.0, 32.0, 0.0);
+        assert_eq!(sprites.len(), 6);
+        assert_eq!(sprites[0].scale, [4.0, 4.0]);
+        assert_eq!(sprites[0].pos, [-2.0, -14.0]);
+        assert_eq!(sprites[0].uv_rect, [0.0, 0.0, 0.25, 0.125]);
+        assert_eq!(sprites[1].scale, [4.0, 4.0]);
+        assert_eq!(sprites[1].uv_rect, [0.75, 0.0, 0.25, 0.125]);
+
+        // the vertical borders keep their proportion.
+        let sprites = expand(&slice, 32.0, 8.0, 0.0);
+        assert_eq!(sprites.len(), 6);
+        assert_eq!(sprites[0].scale, [8.0, 2.0]);
+        assert_eq!(sprites[3].scale, [8.0, 6.0]);
+
+        assert!(expand(&slice, 0.0, 0.0, 0.0).is_empty());
+    }
+
+    #[test]
+    fn tile_clipping() {
+        let slice = NineSlice::new(TextureId(1), 16, 16, [0.0, 0.0, 1.0, 1.0], [4.0; 4])
+            .with_modes(SliceMode::Stretch, SliceMode::Tile);
+        // the center is 20x8, 2.5 tiles of 8x8.
+        let sprites = expand(&slice, 28.0, 16.0, 0.0);
+        assert_eq!(sprites.len(), 11);
+        let center = &sprites[4..7];
+        assert_eq!(center[0].scale, [8.0, 8.0]);
+        assert_eq!(center[0].uv_rect, [0.25, 0.25, 0.5, 0.5]);
+        assert_eq!(center[1].pos, [2.0, 0.0]);
+        // the last tile is clipped, showing the left part of the center.
+        assert_eq!(center[2].scale, [4.0, 8.0]);
+        assert_eq!(center[2].pos, [8.0, 0.0]);
+        assert_eq!(center[2].uv_rect, [0.25, 0.25, 0.25, 0.5]);
+    }
+
+    #[test]
+    fn tile_edges() {
+        let slice = NineSlice::new(TextureId(1), 16, 16, [0.0, 0.0, 1.0, 1.0], [4.0; 4])
+            .with_modes(SliceMode::Tile, SliceMode::Stretch);
+        let sprites = expand(&slice, 28.0, 16.0, 0.0);
+        // the top and bottom edges have 3 tiles, the left and right edges 1, and the center is
+        // stretched.
+        assert_eq!(sprites.len(), 4 + 3 + 3 + 1 + 1 + 1);
+        let top = &sprites[1..4];
+        assert!(top.iter().all(|x| x.scale[1] == 4.0));
+        assert_eq!(top.iter().map(|x| x.scale[0]).sum::<f32>(), 20.0);
+        assert_eq!(sprites[5].scale, [4.0, 8.0]);
+        assert_eq!(sprites[6].scale, [20.0, 8.0]);
+    }
+
+    #[test]
+    fn too_many_tiles_stretch() {
+        let slice = NineSlice::new(TextureId(1), 16, 16, [0.0, 0.0, 1.0, 1.0], [4.0; 4])
+            .with_scale(0.001)
+            .with_modes(SliceMode::Tile, SliceMode::Tile);
+        assert_eq!(expand(&slice, 100.0, 100.0, 0.0).len(), 9);
+    }
+}
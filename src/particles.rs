@@ -0,0 +1,477 @@
+use std::f32::consts::PI;
+
+use crate::{AnimationClip, Camera, MaterialId, Renderer, SpriteInstance, TextureId};
+
+/// The area where the particles of a [ParticleEmitter] are spawned, centered in its position.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum EmitterShape {
+    Point,
+    /// A filled circle with the given radius.
+    Circle(f32),
+    /// A filled axis aligned rectangle with the given width and height.
+    Rect(f32, f32),
+}
+
+struct Particle {
+    pos: [f32; 2],
+    velocity: [f32; 2],
+    acceleration: [f32; 2],
+    angle: f32,
+    angular_velocity: f32,
+    /// The time since the particle was spawned, in seconds.
+    age: f32,
+    lifetime: f32,
+}
+
+/// A CPU particle system, that spawns particles with random properties, moves them and renders
+/// them as [SpriteInstance]s.
+///
+/// The ranges of the properties are given as `[min, max]`, and each particle picks a uniformly
+/// random value in them. The size and color of the particles change over their lifetime, following
+/// curves of `(time, value)` keys, sorted by time, where the time goes from 0.0 at spawn to 1.0 at
+/// death, and the value is linearly interpolated between the keys.
+///
+/// The particles are drawn with the emitter material, which also chooses the blend mode (see
+/// [Material::blend](crate::Material::blend)).
+pub struct ParticleEmitter {
+    /// The center of the emitter shape, in world space.
+    pub position: [f32; 2],
+    pub shape: EmitterShape,
+    /// The number of particles spawned per second, while emitting.
+    pub rate: f32,
+    /// The range of the lifetime of the particles, in seconds.
+    pub lifetime: [f32; 2],
+    /// The range of the initial speed of the particles.
+    pub speed: [f32; 2],
+    /// The direction of the initial velocity, in counterclockwise radians.
+    pub direction: f32,
+    /// The angle of the cone around `direction` where the initial velocity is picked, in radians.
+    /// 2π emits in all directions.
+    pub spread: f32,
+    /// The range of the constant acceleration of each particle, like gravity or wind.
+    pub acceleration: [[f32; 2]; 2],
+    /// The range of the initial angle of the particles, in counterclockwise radians.
+    pub angle: [f32; 2],
+    /// The range of the angular velocity of the particles, in counterclockwise radians per second.
+    pub angular_velocity: [f32; 2],
+    /// The size of the particles over their lifetime.
+    pub size: Vec<(f32, f32)>,
+    /// The color of the particles over their lifetime.
+    pub color: Vec<(f32, [u8; 4])>,
+    pub texture: TextureId,
+    pub uv_rect: [f32; 4],
+    /// A animation played by each particle since its spawn, replacing `texture` and `uv_rect`.
+    pub clip: Option<AnimationClip>,
+    pub material: MaterialId,
+    /// The maximum number of live particles. No particle is spawned while it is reached.
+    pub max_particles: usize,
+    emitting: bool,
+    particles: Vec<Particle>,
+    /// The fraction of a particle left to be spawned.
+    accumulator: f32,
+    /// The state of the xorshift random number generator.
+    rng: u32,
+    sprites: Vec<SpriteInstance>,
+}
+impl ParticleEmitter {
+    /// Create a new ParticleEmitter with the given texture, emitting 10 white particles of size 1.0
+    /// per second, in all directions, with speed 1.0 and a lifetime of 1 second.
+    pub fn new(texture: TextureId) -> Self {
+        Self {
+            position: [0.0, 0.0],
+            shape: EmitterShape::Point,
+            rate: 10.0,
+            lifetime: [1.0, 1.0],
+            speed: [1.0, 1.0],
+            direction: 0.0,
+            spread: 2.0 * PI,
+            acceleration: [[0.0, 0.0], [0.0, 0.0]],
+            angle: [0.0, 0.0],
+            angular_velocity: [0.0, 0.0],
+            size: vec![(0.0, 1.0)],
+            color: vec![(0.0, [255; 4])],
+            texture,
+            uv_rect: [0.0, 0.0, 1.0, 1.0],
+            clip: None,
+            material: MaterialId::default(),
+            max_particles: 1000,
+            emitting: true,
+            particles: Vec::new(),
+            accumulator: 0.0,
+            rng: 0x9E37_79B9,
+            sprites: Vec::new(),
+        }
+    }
+
+    /// set the position, in a functional way (get owership of the value, and return it modified).
+    #[inline]
+    pub fn with_position(mut self, x: f32, y: f32) -> Self {
+        self.position = [x, y];
+        self
+    }
+
+    /// set the spawn shape, in a functional way (get owership of the value, and return it
+    /// modified).
+    #[inline]
+    pub fn with_shape(mut self, shape: EmitterShape) -> Self {
+        self.shape = shape;
+        self
+    }
+
+    /// set the particles spawned per second, in a functional way (get owership of the value, and
+    /// return it modified).
+    #[inline]
+    pub fn with_rate(mut self, rate: f32) -> Self {
+        self.rate = rate;
+        self
+    }
+
+    /// set the range of the lifetime, in a functional way (get owership of the value, and return
+    /// it modified).
+    #[inline]
+    pub fn with_lifetime(mut self, min: f32, max: f32) -> Self {
+        self.lifetime = [min, max];
+        self
+    }
+
+    /// set the direction and the range of the initial velocity, in a functional way (get owership
+    /// of the value, and return it modified).
+    #[inline]
+    pub fn with_velocity(mut self, direction: f32, spread: f32, speed: [f32; 2]) -> Self {
+        self.direction = direction;
+        self.spread = spread;
+        self.speed = speed;
+        self
+    }
+
+    /// set the range of the acceleration, in a functional way (get owership of the value, and
+    /// return it modified).
+    #[inline]
+    pub fn with_acceleration(mut self, min: [f32; 2], max: [f32; 2]) -> Self {
+        self.acceleration = [min, max];
+        self
+    }
+
+    /// set the range of the initial angle and angular velocity, in a functional way (get owership
+    /// of the value, and return it modified).
+    #[inline]
+    pub fn with_rotation(mut self, angle: [f32; 2], angular_velocity: [f32; 2]) -> Self {
+        self.angle = angle;
+        self.angular_velocity = angular_velocity;
+        self
+    }
+
+    /// set the size curve, in a functional way (get owership of the value, and return it
+    /// modified).
+    #[inline]
+    pub fn with_size(mut self, keys: &[(f32, f32)]) -> Self {
+        self.size = keys.to_vec();
+        self
+    }
+
+    /// set the color curve, in a functional way (get owership of the value, and return it
+    /// modified).
+    #[inline]
+    pub fn with_color(mut self, keys: &[(f32, [u8; 4])]) -> Self {
+        self.color = keys.to_vec();
+        self
+    }
+
+    /// set the region of the texture, in a functional way (get owership of the value, and return
+    /// it modified).
+    #[inline]
+    pub fn with_uv_rect(mut self, uv_rect: [f32; 4]) -> Self {
+        self.uv_rect = uv_rect;
+        self
+    }
+
+    /// set the animation of the particles, in a functional way (get owership of the value, and
+    /// return it modified).
+    #[inline]
+    pub fn with_clip(mut self, clip: AnimationClip) -> Self {
+        self.clip = Some(clip);
+        self
+    }
+
+    /// set the material, in a functional way (get owership of the value, and return it modified).
+    #[inline]
+    pub fn with_material(mut self, material: MaterialId) -> Self {
+        self.material = material;
+        self
+    }
+
+    /// set the maximum number of particles, in a functional way (get owership of the value, and
+    /// return it modified).
+    #[inline]
+    pub fn with_max_particles(mut self, max_particles: usize) -> Self {
+        self.max_particles = max_particles;
+        self
+    }
+
+    /// set the seed of the random number generator, in a functional way (get owership of the
+    /// value, and return it modified).
+    #[inline]
+    pub fn with_seed(mut self, seed: u32) -> Self {
+        // xorshift never leaves the zero state.
+        self.rng = seed.max(1);
+        self
+    }
+
+    /// Start or stop spawning particles by the rate. The live particles keep being updated.
+    pub fn set_emitting(&mut self, emitting: bool) {
+        self.emitting = emitting;
+        self.accumulator = 0.0;
+    }
+
+    pub fn is_emitting(&self) -> bool {
+        self.emitting
+    }
+
+    /// The number of live particles.
+    pub fn particle_count(&self) -> usize {
+        self.particles.len()
+    }
+
+    /// Remove all live particles.
+    pub fn clear(&mut self) {
+        self.particles.clear();
+    }
+
+    /// Spawn `count` particles at once, even if not emitting.
+    pub fn burst(&mut self, count: usize) {
+        for _ in 0..count {
+            self.spawn();
+        }
+    }
+
+    /// Spawn particles by the rate, and move the live particles, by `dt` seconds.
+    pub fn update(&mut self, dt: f32) {
+        for particle in &mut self.particles {
+            particle.age += dt;
+            particle.velocity[0] += particle.acceleration[0] * dt;
+            particle.velocity[1] += particle.acceleration[1] * dt;
+            particle.pos[0] += particle.velocity[0] * dt;
+            particle.pos[1] += particle.velocity[1] * dt;
+            particle.angle += particle.angular_velocity * dt;
+        }
+        self.particles.retain(|x| x.age < x.lifetime);
+
+        if self.emitting && self.rate > 0.0 {
+            self.accumulator += self.rate * dt;
+            let count = self.accumulator.floor();
+            self.accumulator -= count;
+            self.burst(count as usize);
+        }
+    }
+
+    fn spawn(&mut self) {
+        if self.particles.len() >= self.max_particles {
+            return;
+        }
+        let [x, y] = self.position;
+        let pos = match self.shape {
+            EmitterShape::Point => [x, y],
+            EmitterShape::Circle(radius) => {
+                let r = radius * self.random().sqrt();
+                let a = self.random() * 2.0 * PI;
+                [x + r * a.cos(), y + r * a.sin()]
+            }
+            EmitterShape::Rect(width, height) => [
+                x + (self.random() - 0.5) * width,
+                y + (self.random() - 0.5) * height,
+            ],
+        };
+        let direction = self.direction + (self.random() - 0.5) * self.spread;
+        let speed = self.range(self.speed);
+        let [min, max] = self.acceleration;
+        let particle = Particle {
+            pos,
+            velocity: [speed * direction.cos(), speed * direction.sin()],
+            acceleration: [self.range([min[0], max[0]]), self.range([min[1], max[1]])],
+            angle: self.range(self.angle),
+            angular_velocity: self.range(self.angular_velocity),
+            age: 0.0,
+            lifetime: self.range(self.lifetime),
+        };
+        self.particles.push(particle);
+    }
+
+    /// A random number in the range 0.0 to 1.0.
+    fn random(&mut self) -> f32 {
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 17;
+        self.rng ^= self.rng << 5;
+        (self.rng >> 8) as f32 / (1 << 24) as f32
+    }
+
+    fn range(&mut self, [min, max]: [f32; 2]) -> f32 {
+        min + (max - min) * self.random()
+    }
+
+    /// Push a sprite for each live particle to `sprites`, from the oldest to the newest.
+    pub fn write_sprites(&self, sprites: &mut Vec<SpriteInstance>) {
+        for particle in &self.particles {
+            let t = particle.age / particle.lifetime;
+            let size = sample(&self.size, t, 1.0, |a, b, t| a + (b - a) * t);
+            let color = sample(&self.color, t, [255; 4], |a, b, t| {
+                [0, 1, 2, 3].map(|i| (a[i] as f32 + (b[i] as f32 - a[i] as f32) * t).round() as u8)
+            });
+            let (texture, uv_rect) = match self.clip.as_ref().and_then(|x| x.frame_at(particle.age))
+            {
+                Some(frame) => (frame.texture, frame.uv_rect),
+                None => (self.texture, self.uv_rect),
+            };
+            sprites.push(SpriteInstance {
+                scale: [size, size],
+                angle: particle.angle,
+                uv_rect,
+                color,
+                pos: particle.pos,
+                texture,
+                material: self.material,
+            });
+        }
+    }
+
+    /// Draw the live particles.
+    pub fn draw(&mut self, renderer: &mut dyn Renderer, camera: &mut Camera) {
+        let mut sprites = std::mem::take(&mut self.sprites);
+        sprites.clear();
+        self.write_sprites(&mut sprites);
+        renderer.draw_sprites(camera, &sprites);
+        self.sprites = sprites;
+    }
+}
+
+/// Sample a curve of `(time, value)` keys at `t`, or return `default` if it has no keys.
+fn sample<T: Copy>(keys: &[(f32, T)], t: f32, default: T, lerp: impl Fn(T, T, f32) -> T) -> T {
+    let i = keys.partition_point(|x| x.0 <= t);
+    match (i.checked_sub(1).map(|i| keys[i]), keys.get(i)) {
+        (Some(a), Some(b)) => lerp(a.1, b.1, (t - a.0) / (b.0 - a.0)),
+        (Some(a), None) => a.1,
+        (None, Some(b)) => b.1,
+        (None, None) => default,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sprites(emitter: &ParticleEmitter) -> Vec<SpriteInstance> {
+        let mut sprites = Vec::new();
+        emitter.write_sprites(&mut sprites);
+        sprites
+    }
+
+    #[test]
+    fn sample_curve() {
+        let lerp = |a: f32, b: f32, t: f32| a + (b - a) * t;
+        assert_eq!(sample(&[], 0.5, 7.0, lerp), 7.0);
+
+        let keys = [(0.25, 0.0), (0.75, 10.0), (1.0, 20.0)];
+        assert_eq!(sample(&keys, 0.0, 7.0, lerp), 0.0);
+        assert_eq!(sample(&keys, 0.5, 7.0, lerp), 5.0);
+        assert_eq!(sample(&keys, 0.75, 7.0, lerp), 10.0);
+        assert_eq!(sample(&keys, 0.875, 7.0, lerp), 15.0);
+        assert_eq!(sample(&keys, 1.5, 7.0, lerp), 20.0);
+
+        // keys with the same time make a step, without dividing by zero.
+        let step = [(0.5, 0.0), (0.5, 10.0)];
+        assert_eq!(sample(&step, 0.4, 7.0, lerp), 0.0);
+        assert_eq!(sample(&step, 0.5, 7.0, lerp), 10.0);
+    }
+
+    #[test]
+    fn rate() {
+        let mut emitter = ParticleEmitter::new(TextureId(1)).with_rate(10.0);
+        emitter.update(0.25);
+        assert_eq!(emitter.particle_count(), 2);
+        // the fraction of a particle is carried to the next update.
+        emitter.update(0.05);
+        assert_eq!(emitter.particle_count(), 3);
+
+        emitter.set_emitting(false);
+        emitter.update(0.5);
+        assert_eq!(emitter.particle_count(), 3);
+        // the particles die after their lifetime.
+        emitter.update(0.5);
+        assert_eq!(emitter.particle_count(), 0);
+    }
+
+    #[test]
+    fn max_particles() {
+        let mut emitter = ParticleEmitter::new(TextureId(1)).with_max_particles(5);
+        emitter.burst(10);
+        assert_eq!(emitter.particle_count(), 5);
+        emitter.clear();
+        assert_eq!(emitter.particle_count(), 0);
+    }
+
+    #[test]
+    fn motion() {
+        let mut emitter = ParticleEmitter::new(TextureId(1))
+            .with_position(1.0, 1.0)
+            .with_velocity(0.0, 0.0, [2.0, 2.0])
+            .with_acceleration([0.0, -10.0], [0.0, -10.0])
+            .with_rotation([1.0, 1.0], [2.0, 2.0]);
+        emitter.set_emitting(false);
+        emitter.burst(1);
+        emitter.update(0.5);
+        let sprite = &sprites(&emitter)[0];
+        assert_eq!(sprite.pos, [2.0, -1.5]);
+        assert_eq!(sprite.angle, 2.0);
+    }
+
+    #[test]
+    fn curves() {
+        let mut emitter = ParticleEmitter::new(TextureId(1))
+            .with_lifetime(2.0, 2.0)
+            .with_size(&[(0.0, 1.0), (1.0, 3.0)])
+            .with_color(&[(0.0, [0, 0, 0, 255]), (1.0, [255, 255, 255, 0])]);
+        emitter.set_emitting(false);
+        emitter.burst(1);
+        assert_eq!(sprites(&emitter)[0].scale, [1.0, 1.0]);
+        emitter.update(1.0);
+        let sprite = &sprites(&emitter)[0];
+        assert_eq!(sprite.scale, [2.0, 2.0]);
+        assert_eq!(sprite.color, [128, 128, 128, 128]);
+        assert_eq!(sprite.texture, TextureId(1));
+    }
+
+    #[test]
+    fn shapes() {
+        let mut emitter = ParticleEmitter::new(TextureId(1))
+            .with_position(10.0, 20.0)
+            .with_shape(EmitterShape::Rect(4.0, 2.0))
+            .with_max_particles(usize::MAX);
+        emitter.burst(100);
+        for sprite in sprites(&emitter) {
+            assert!((8.0..=12.0).contains(&sprite.pos[0]));
+            assert!((19.0..=21.0).contains(&sprite.pos[1]));
+        }
+
+        emitter.clear();
+        emitter.shape = EmitterShape::Circle(3.0);
+        emitter.burst(100);
+        for sprite in sprites(&emitter) {
+            let [dx, dy] = [sprite.pos[0] - 10.0, sprite.pos[1] - 20.0];
+            assert!(dx * dx + dy * dy <= 9.0 + 1e-4);
+        }
+    }
+
+    #[test]
+    fn seed() {
+        let emitter = |seed| {
+            let mut emitter = ParticleEmitter::new(TextureId(1)).with_seed(seed);
+            emitter.burst(10);
+            emitter.update(0.5);
+            sprites(&emitter).iter().map(|x| x.pos).collect::<Vec<_>>()
+        };
+        assert_eq!(emitter(1), emitter(1));
+        assert_ne!(emitter(1), emitter(2));
+        // the zero seed is not stuck in the zero state.
+        let positions = emitter(0);
+        assert_ne!(positions[0], positions[1]);
+    }
+}
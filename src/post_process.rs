@@ -0,0 +1,243 @@
+use crate::TextureId;
+
+/// A full-screen effect applied to the rendered image of a window, before presenting it.
+///
+/// See [SpriteRender::set_post_effects](crate::SpriteRender::set_post_effects).
+#[derive(Clone, Debug, PartialEq)]
+pub enum PostEffect {
+    /// Add a glow around the bright parts of the image.
+    Bloom {
+        /// Only the part of the color brighter than this value glows, in the range 0.0 to 1.0.
+        threshold: f32,
+        /// How strong the glow is added to the image.
+        intensity: f32,
+        /// The radius of the glow, in pixels.
+        radius: f32,
+    },
+    /// Remap the colors of the image using a color lookup table.
+    ColorGrading {
+        /// A texture with `size` slices of `size`x`size` pixels, laid side by side horizontally,
+        /// with a total dimension of `size * size` x `size`.
+        ///
+        /// In each slice, the red channel increases to the right and the green channel increases
+        /// downwards. The blue channel increases from slice to slice.
+        lut: TextureId,
+        /// The number of samples of each color channel in the lookup table, usually 16 or 32.
+        size: u32,
+    },
+    /// Simulate a CRT screen, with a curved screen and scanlines.
+    Crt {
+        /// How much the screen is curved. 0.0 means no curvature.
+        curvature: f32,
+        /// How dark the scanlines are, in the range 0.0 to 1.0.
+        scanline_intensity: f32,
+    },
+    /// Blur the image.
+    Blur {
+        /// The radius of the blur, in pixels.
+        radius: f32,
+    },
+    /// Darken the borders of the image.
+    Vignette {
+        /// The distance from the center where the darkening ends, where 1.0 is the corners of the
+        /// screen.
+        radius: f32,
+        /// The width of the transition between darkened and not darkened.
+        softness: f32,
+        /// How dark the borders are, in the range 0.0 to 1.0.
+        intensity: f32,
+    },
+}
+#[cfg(any(feature = "opengl", feature = "webgl"))]
+impl PostEffect {
+    /// A index that identifies the variant of the effect, and therefore its shader program.
+    pub(crate) fn kind(&self) -> usize {
+        match self {
+            PostEffect::Bloom { .. } => 0,
+            PostEffect::ColorGrading { .. } => 1,
+            PostEffect::Crt { .. } => 2,
+            PostEffect::Blur { .. } => 3,
+            PostEffect::Vignette { .. } => 4,
+        }
+    }
+
+    /// The source of the fragment shader of the effect, without the `#version` directive.
+    pub(crate) fn fragment_source(&self) -> String {
+        let main = match self {
+            PostEffect::Bloom { .. } => BLOOM_SOURCE,
+            PostEffect::ColorGrading { .. } => COLOR_GRADING_SOURCE,
+            PostEffect::Crt { .. } => CRT_SOURCE,
+            PostEffect::Blur { .. } => BLUR_SOURCE,
+            PostEffect::Vignette { .. } => VIGNETTE_SOURCE,
+        };
+        format!("{}{}", FRAGMENT_PRELUDE, main)
+    }
+
+    /// The value of the uniform `u_params` of the effect.
+    pub(crate) fn params(&self) -> [f32; 4] {
+        match *self {
+            PostEffect::Bloom {
+                threshold,
+                intensity,
+                radius,
+            } => [threshold, intensity, radius, 0.0],
+            PostEffect::ColorGrading { size, .. } => [size as f32, 0.0, 0.0, 0.0],
+            PostEffect::Crt {
+                curvature,
+                scanline_intensity,
+            } => [curvature, scanline_intensity, 0.0, 0.0],
+            PostEffect::Blur { radius } => [radius, 0.0, 0.0, 0.0],
+            PostEffect::Vignette {
+                radius,
+                softness,
+                intensity,
+            } => [radius, softness, intensity, 0.0],
+        }
+    }
+
+    /// The texture that must be bound to the uniform `u_lut`, if any.
+    pub(crate) fn texture(&self) -> Option<TextureId> {
+        match *self {
+            PostEffect::ColorGrading { lut, .. } => Some(lut),
+            _ => None,
+        }
+    }
+}
+
+/// The vertex shader of all effects, without the `#version` directive.
+///
+/// Expects the 4 vertices of a full-screen triangle strip in clip space.
+#[cfg(any(feature = "opengl", feature = "webgl"))]
+pub(crate) const POST_VERTEX_SHADER_SOURCE: &str = r#"
+attribute vec2 position;
+
+varying vec2 v_uv;
+
+void main() {
+    v_uv = position * 0.5 + 0.5;
+    gl_Position = vec4(position, 0.0, 1.0);
+}
+"#;
+
+/// The source of a fragment shader that only copies the image, without the `#version` directive.
+///
+/// Used in place of a effect whose shader failed to compile.
+#[cfg(any(feature = "opengl", feature = "webgl"))]
+pub(crate) fn copy_fragment_source() -> String {
+    format!("{}{}", FRAGMENT_PRELUDE, COPY_SOURCE)
+}
+
+/// The vertices of the full-screen triangle strip.
+#[cfg(any(feature = "opengl", feature = "webgl"))]
+pub(crate) const POST_QUAD_VERTICES: [f32; 8] = [-1.0, -1.0, 1.0, -1.0, -1.0, 1.0, 1.0, 1.0];
+
+#[cfg(any(feature = "opengl", feature = "webgl"))]
+const FRAGMENT_PRELUDE: &str = r#"
+precision mediump float;
+
+uniform sampler2D u_texture;
+uniform sampler2D u_lut;
+uniform vec2 u_resolution;
+uniform vec4 u_params;
+
+varying vec2 v_uv;
+"#;
+
+#[cfg(any(feature = "opengl", feature = "webgl"))]
+const COPY_SOURCE: &str = r#"
+void main() {
+    gl_FragColor = texture2D(u_texture, v_uv);
+}
+"#;
+
+#[cfg(any(feature = "opengl", feature = "webgl"))]
+const BLOOM_SOURCE: &str = r#"
+void main() {
+    float threshold = u_params.x;
+    float intensity = u_params.y;
+    vec2 texel = u_params.z / 2.0 / u_resolution;
+
+    vec4 color = texture2D(u_texture, v_uv);
+    vec3 glow = vec3(0.0);
+    float total = 0.0;
+    for (int x = -2; x <= 2; x++) {
+        for (int y = -2; y <= 2; y++) {
+            vec2 offset = vec2(float(x), float(y));
+            float weight = exp(-dot(offset, offset) / 4.0);
+            vec3 s = texture2D(u_texture, v_uv + offset * texel).rgb;
+            glow += max(s - vec3(threshold), vec3(0.0)) * weight;
+            total += weight;
+        }
+    }
+    gl_FragColor = vec4(color.rgb + glow / total * intensity, color.a);
+}
+"#;
+
+#[cfg(any(feature = "opengl", feature = "webgl"))]
+const COLOR_GRADING_SOURCE: &str = r#"
+void main() {
+    float size = u_params.x;
+    vec4 color = texture2D(u_texture, v_uv);
+    vec3 c = clamp(color.rgb, 0.0, 1.0) * (size - 1.0);
+
+    float slice = floor(c.b);
+    float next = min(slice + 1.0, size - 1.0);
+    vec2 uv = vec2((c.r + 0.5) / (size * size), (c.g + 0.5) / size);
+    vec3 a = texture2D(u_lut, uv + vec2(slice / size, 0.0)).rgb;
+    vec3 b = texture2D(u_lut, uv + vec2(next / size, 0.0)).rgb;
+    gl_FragColor = vec4(mix(a, b, c.b - slice), color.a);
+}
+"#;
+
+#[cfg(any(feature = "opengl", feature = "webgl"))]
+const CRT_SOURCE: &str = r#"
+void main() {
+    float curvature = u_params.x;
+    float scanline_intensity = u_params.y;
+
+    vec2 uv = v_uv * 2.0 - 1.0;
+    uv += uv * uv.yx * uv.yx * curvature;
+    uv = uv * 0.5 + 0.5;
+    if (uv.x < 0.0 || uv.x > 1.0 || uv.y < 0.0 || uv.y > 1.0) {
+        gl_FragColor = vec4(0.0, 0.0, 0.0, 1.0);
+        return;
+    }
+
+    vec4 color = texture2D(u_texture, uv);
+    float scanline = sin(uv.y * u_resolution.y * 3.14159265) * 0.5 + 0.5;
+    gl_FragColor = vec4(color.rgb * (1.0 - scanline_intensity * scanline), color.a);
+}
+"#;
+
+#[cfg(any(feature = "opengl", feature = "webgl"))]
+const BLUR_SOURCE: &str = r#"
+void main() {
+    vec2 texel = u_params.x / 2.0 / u_resolution;
+
+    vec4 color = vec4(0.0);
+    float total = 0.0;
+    for (int x = -2; x <= 2; x++) {
+        for (int y = -2; y <= 2; y++) {
+            vec2 offset = vec2(float(x), float(y));
+            float weight = exp(-dot(offset, offset) / 4.0);
+            color += texture2D(u_texture, v_uv + offset * texel) * weight;
+            total += weight;
+        }
+    }
+    gl_FragColor = color / total;
+}
+"#;
+
+#[cfg(any(feature = "opengl", feature = "webgl"))]
+const VIGNETTE_SOURCE: &str = r#"
+void main() {
+    float radius = u_params.x;
+    float softness = u_params.y;
+    float intensity = u_params.z;
+
+    vec4 color = texture2D(u_texture, v_uv);
+    float dist = distance(v_uv, vec2(0.5)) * 1.41421356;
+    float vignette = 1.0 - smoothstep(radius - softness, radius, dist) * intensity;
+    gl_FragColor = vec4(color.rgb * vignette, color.a);
+}
+"#;
@@ -0,0 +1,471 @@
+use std::f32::consts::SQRT_2;
+
+use crate::{
+    BlendMode, Camera, ColorSpace, Lighting, Material, MaterialId, Mesh, Renderer, ShaderError,
+    SpriteInstance, SpriteRender, TextureError, TextureId, Uniform, Vertex, WHITE_TEXTURE,
+};
+
+/// The fragment shader of the lights drawn in a [ShadowMask]. The light covers the sprite, with
+/// its center in the center of the sprite.
+const LIGHT_FRAGMENT_SOURCE: &str = r#"
+// the color multiplied by the intensity.
+uniform vec3 lightColor;
+uniform float falloff;
+// xy: direction, z: cosine of the outer half angle, w: cosine of the inner half angle.
+uniform vec4 cone;
+
+void main() {
+    vec2 offset = TexCoord * 2.0 - 1.0;
+    float dist = length(offset);
+    float t = clamp(1.0 - dist, 0.0, 1.0);
+    float attenuation = t > 0.0 ? pow(t, falloff) : 0.0;
+    if (dist > 0.0) {
+        attenuation *= smoothstep(cone.z, cone.w, dot(offset / dist, cone.xy));
+    }
+    gl_FragColor = vec4(lightColor * attenuation, 1.0);
+}
+"#;
+
+/// Error generate in [ShadowMask::new].
+#[derive(Debug)]
+pub enum ShadowError {
+    /// The render textures of the mask could not be created.
+    Texture(TextureError),
+    /// The materials of the mask could not be created.
+    Shader(ShaderError),
+}
+impl From<TextureError> for ShadowError {
+    fn from(value: TextureError) -> Self {
+        Self::Texture(value)
+    }
+}
+impl From<ShaderError> for ShadowError {
+    fn from(value: ShaderError) -> Self {
+        Self::Shader(value)
+    }
+}
+impl std::fmt::Display for ShadowError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ShadowError::Texture(err) => write!(f, "failed to create render texture: {}", err),
+            ShadowError::Shader(err) => write!(f, "failed to create material: {}", err),
+        }
+    }
+}
+impl std::error::Error for ShadowError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ShadowError::Texture(err) => Some(err),
+            ShadowError::Shader(err) => Some(err),
+        }
+    }
+}
+
+/// A shape that blocks the light, made of the segments between consecutive points, in world
+/// space.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Occluder {
+    points: Vec<[f32; 2]>,
+    /// If the last point is connected back to the first one.
+    closed: bool,
+}
+impl Occluder {
+    /// Create a closed polygon, with the given vertices, in any winding order.
+    pub fn polygon(points: Vec<[f32; 2]>) -> Self {
+        Self {
+            points,
+            closed: true,
+        }
+    }
+
+    /// Create a open chain of segments, through the given points.
+    pub fn polyline(points: Vec<[f32; 2]>) -> Self {
+        Self {
+            points,
+            closed: false,
+        }
+    }
+
+    /// Create a single segment.
+    pub fn segment(a: [f32; 2], b: [f32; 2]) -> Self {
+        Self::polyline(vec![a, b])
+    }
+
+    /// Create a axis aligned rectangle, with the top left corner in (x, y).
+    pub fn rect(x: f32, y: f32, width: f32, height: f32) -> Self {
+        Self::polygon(vec![
+            [x, y],
+            [x + width, y],
+            [x + width, y + height],
+            [x, y + height],
+        ])
+    }
+
+    pub fn points(&self) -> &[[f32; 2]] {
+        &self.points
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.closed
+    }
+
+    fn edges(&self) -> impl Iterator<Item = ([f32; 2], [f32; 2])> + '_ {
+        let closing = match self.points.as_slice() {
+            [first, .., last] if self.closed && self.points.len() > 2 => Some((*last, *first)),
+            _ => None,
+        };
+        self.points.windows(2).map(|x| (x[0], x[1])).chain(closing)
+    }
+}
+
+/// Push to `vertices` and `indices` the triangles of the shadows that the `occluders` cast from a
+/// light in `light`, covering at least the circle of the given `radius` around the light.
+///
+/// The shadow of each segment is extruded away from the light, so the occluders are inside their
+/// own shadows. The triangles have the given color, and are meant to be drawn with
+/// [WHITE_TEXTURE], for example to darken the light of a [ShadowMask], or to draw a line of sight
+/// mask for a fog of war.
+pub fn shadow_geometry(
+    light: [f32; 2],
+    radius: f32,
+    occluders: &[Occluder],
+    color: [u8; 4],
+    vertices: &mut Vec<Vertex>,
+    indices: &mut Vec<u32>,
+) {
+    let [lx, ly] = light;
+    // the far points are placed at this distance at least. The angle between the far points of a
+    // segment is split in two parts smaller than 90°, so the chords between them stay outside the
+    // circle.
+    let far = radius * SQRT_2;
+    for (a, b) in occluders.iter().flat_map(Occluder::edges) {
+        let da = [a[0] - lx, a[1] - ly];
+        let db = [b[0] - lx, b[1] - ly];
+        let (la, lb) = (da[0].hypot(da[1]), db[0].hypot(db[1]));
+        // segments that pass through the light, or are seen edge-on, cast no shadow.
+        let cross = da[0] * db[1] - da[1] * db[0];
+        if cross.abs() <= f32::EPSILON * la * lb || la == 0.0 || lb == 0.0 {
+            continue;
+        }
+        if segment_distance(da, db) >= radius {
+            continue;
+        }
+
+        let na = [da[0] / la, da[1] / la];
+        let nb = [db[0] / lb, db[1] / lb];
+        let mid = [na[0] + nb[0], na[1] + nb[1]];
+        let lm = mid[0].hypot(mid[1]);
+        let extrude = |n: [f32; 2], d: f32| [lx + n[0] * d, ly + n[1] * d];
+
+        let first = vertices.len() as u32;
+        vertices.extend(
+            [
+                a,
+                b,
+                extrude(nb, far.max(lb)),
+                extrude([mid[0] / lm, mid[1] / lm], far),
+                extrude(na, far.max(la)),
+            ]
+            .iter()
+            .map(|&position| Vertex::new(position, [0.5, 0.5], color)),
+        );
+        indices.extend([0, 1, 2, 0, 2, 3, 0, 3, 4].iter().map(|x| first + x));
+    }
+}
+
+/// The distance from the origin to the segment from `a` to `b`.
+fn segment_distance(a: [f32; 2], b: [f32; 2]) -> f32 {
+    let ab = [b[0] - a[0], b[1] - a[1]];
+    let len2 = ab[0] * ab[0] + ab[1] * ab[1];
+    let t = if len2 > 0.0 {
+        (-(a[0] * ab[0] + a[1] * ab[1]) / len2).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    (a[0] + ab[0] * t).hypot(a[1] + ab[1] * t)
+}
+
+/// A image of the light that reaches each point of the view, with the shadows of the occluders,
+/// drawn over the scene to darken it.
+///
+/// The mask is rendered by [ShadowMask::render] from the ambient light and the lights of a
+/// [Lighting], each one blocked by the [Occluder]s, and added together. [ShadowMask::draw]
+/// multiplies the colors already drawn by the mask, so it must be drawn after the scene, with the
+/// same camera. With a black ambient and a single light in the position of the player, the mask is
+/// a line of sight fog of war.
+///
+/// The mask is rendered to textures with the given size, independent of the size of the window.
+/// Smaller masks are faster to render, and smooth the edges of the shadows.
+///
+/// ```no_run
+/// # use sprite_render::{Camera, Light, Lighting, Occluder, Renderer, ShadowMask, SpriteRender};
+/// # fn frame(sprite_render: &mut dyn SpriteRender, camera: &mut Camera) {
+/// let mut mask = ShadowMask::new(sprite_render, 400, 300).unwrap();
+/// let mut lighting = Lighting::new([0.0, 0.0, 0.0]);
+/// lighting.lights.push(Light::point(0.0, 0.0, 8.0));
+/// let occluders = [Occluder::rect(2.0, -1.0, 1.0, 2.0)];
+///
+/// mask.render(sprite_render, camera, &lighting, &occluders);
+/// let mut renderer = sprite_render.render(Default::default());
+/// // draw the scene ...
+/// mask.draw(renderer.as_mut(), camera);
+/// renderer.finish();
+/// # }
+/// ```
+pub struct ShadowMask {
+    /// The render texture with the sum of the lights.
+    mask: TextureId,
+    /// The render texture where each light and its shadows are drawn, before being added to the
+    /// mask.
+    light_target: TextureId,
+    light_material: MaterialId,
+    additive: MaterialId,
+    multiply: MaterialId,
+    vertices: Vec<Vertex>,
+    indices: Vec<u32>,
+}
+impl ShadowMask {
+    /// Create a new ShadowMask, rendered to textures of `width` x `height` pixels.
+    pub fn new(
+        sprite_render: &mut dyn SpriteRender,
+        width: u32,
+        height: u32,
+    ) -> Result<Self, ShadowError> {
+        let light_material = Material::new(LIGHT_FRAGMENT_SOURCE)
+            .uniform("lightColor", Uniform::Vec3([1.0; 3]))
+            .uniform("falloff", Uniform::Float(1.0))
+            .uniform("cone", Uniform::Vec4([1.0, 0.0, -2.0, -1.0]))
+            .create(sprite_render)?;
+        let additive = Material::default()
+            .blend(BlendMode::Additive)
+            .create(sprite_render)?;
+        let multiply = Material::default()
+            .blend(BlendMode::Multiply)
+            .create(sprite_render)?;
+        Ok(Self {
+            mask: sprite_render.new_render_texture(width, height)?,
+            light_target: sprite_render.new_render_texture(width, height)?,
+            light_material,
+            additive,
+            multiply,
+            vertices: Vec::new(),
+            indices: Vec::new(),
+        })
+    }
+
+    /// The render texture of the mask, for compositing it in a custom way, like in a texture
+    /// uniform of a [Material].
+    pub fn texture(&self) -> TextureId {
+        self.mask
+    }
+
+    /// Render the mask of the view of `camera`, from the ambient light and the lights of
+    /// `lighting`, blocked by the `occluders`.
+    ///
+    /// Each light is rendered in a separated pass, so there is no limit in the number of lights,
+    /// but the lights that do not reach the view are skipped. The height of the lights is ignored.
+    pub fn render(
+        &mut self,
+        sprite_render: &mut dyn SpriteRender,
+        camera: &mut Camera,
+        lighting: &Lighting,
+        occluders: &[Occluder],
+    ) {
+        let [r, g, b] = lighting.ambient;
+        let mut renderer = sprite_render.render_to_texture(self.mask);
+        renderer.clear_screen(&[r, g, b, 1.0]);
+        renderer.finish();
+        drop(renderer);
+
+        let linear = sprite_render.color_space() == ColorSpace::Linear;
+        let (cx, cy) = camera.get_position();
+        let view_radius = camera.width().hypot(camera.height()) * 0.5;
+        for light in &lighting.lights {
+            let [x, y] = light.position;
+            if (x - cx).hypot(y - cy) >= light.radius + view_radius {
+                continue;
+            }
+            let color = Uniform::Vec3(light.shader_color(linear));
+            sprite_render.set_material_uniform(self.light_material, "lightColor", color);
+            let falloff = Uniform::Float(light.falloff);
+            sprite_render.set_material_uniform(self.light_material, "falloff", falloff);
+            let cone = Uniform::Vec4(light.cone());
+            sprite_render.set_material_uniform(self.light_material, "cone", cone);
+
+            self.vertices.clear();
+            self.indices.clear();
+            shadow_geometry(
+                light.position,
+                light.radius,
+                occluders,
+                [0, 0, 0, 255],
+                &mut self.vertices,
+                &mut self.indices,
+            );
+            let size = light.radius * 2.0;
+            let sprite = SpriteInstance::new(x, y, size, size, WHITE_TEXTURE, [0.0, 0.0, 1.0, 1.0])
+                .with_material(self.light_material);
+
+            let mut renderer = sprite_render.render_to_texture(self.light_target);
+            renderer
+                .clear_screen(&[0.0, 0.0, 0.0, 1.0])
+                .draw_sprites(camera, &[sprite]);
+            if !self.indices.is_empty() {
+                let mesh = Mesh::new(&self.vertices, &self.indices[..], WHITE_TEXTURE);
+                renderer.draw_mesh(camera, &mesh);
+            }
+            renderer.finish();
+            drop(renderer);
+
+            let sprite = view_sprite(camera, self.light_target, self.additive);
+            let mut renderer = sprite_render.render_to_texture(self.mask);
+            renderer.draw_sprites(camera, &[sprite]);
+            renderer.finish();
+        }
+    }
+
+    /// Multiply the colors drawn in the view of `camera` by the mask.
+    pub fn draw(&self, renderer: &mut dyn Renderer, camera: &mut Camera) {
+        let sprite = view_sprite(camera, self.mask, self.multiply);
+        renderer.draw_sprites(camera, &[sprite]);
+    }
+}
+
+/// A sprite that covers the view of `camera` with a render texture.
+fn view_sprite(camera: &mut Camera, texture: TextureId, material: MaterialId) -> SpriteInstance {
+    let (x, y) = camera.get_position();
+    let (width, height) = (camera.width(), camera.height());
+    SpriteInstance::new(x, y, width, height, texture, [0.0, 0.0, 1.0, 1.0])
+        .with_angle(camera.view_rotation())
+        .with_material(material)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn geometry(light: [f32; 2], radius: f32, occluders: &[Occluder]) -> (Vec<Vertex>, Vec<u32>) {
+        let (mut vertices, mut indices) = (Vec::new(), Vec::new());
+        shadow_geometry(
+            light,
+            radius,
+            occluders,
+            [0; 4],
+            &mut vertices,
+            &mut indices,
+        );
+        (vertices, indices)
+    }
+
+    /// If `p` is inside any of the triangles.
+    fn covered(vertices: &[Vertex], indices: &[u32], p: [f32; 2]) -> bool {
+        indices.chunks(3).any(|t| {
+            let [a, b, c] = [0, 1, 2].map(|i| vertices[t[i] as usize].position);
+            let side = |a: [f32; 2], b: [f32; 2]| {
+                (b[0] - a[0]) * (p[1] - a[1]) - (b[1] - a[1]) * (p[0] - a[0])
+            };
+            let [ab, bc, ca] = [side(a, b), side(b, c), side(c, a)];
+            (ab >= 0.0 && bc >= 0.0 && ca >= 0.0) || (ab <= 0.0 && bc <= 0.0 && ca <= 0.0)
+        })
+    }
+
+    #[test]
+    fn segment_distance_to_origin() {
+        // the closest point is inside the segment.
+        assert_eq!(segment_distance([-1.0, 2.0], [1.0, 2.0]), 2.0);
+        // the closest point is a end of the segment.
+        assert_eq!(segment_distance([3.0, 4.0], [6.0, 4.0]), 5.0);
+        assert_eq!(segment_distance([6.0, -4.0], [3.0, -4.0]), 5.0);
+        // a degenerate segment is a point.
+        assert_eq!(segment_distance([-3.0, 4.0], [-3.0, 4.0]), 5.0);
+        assert_eq!(segment_distance([-1.0, 0.0], [1.0, 0.0]), 0.0);
+    }
+
+    #[test]
+    fn segment_shadow() {
+        let a = [-1.0, 2.0];
+        let b = [1.0, 2.0];
+        let (vertices, indices) = geometry([0.0, 0.0], 10.0, &[Occluder::segment(a, b)]);
+        assert_eq!(vertices.len(), 5);
+        assert_eq!(indices, [0, 1, 2, 0, 2, 3, 0, 3, 4]);
+        assert_eq!(vertices[0].position, a);
+        assert_eq!(vertices[1].position, b);
+        // the far points are out of the light radius, in the direction of the ends.
+        for (v, end) in [(&vertices[2], b), (&vertices[4], a)] {
+            let [x, y] = v.position;
+            assert!(x.hypot(y) >= 10.0);
+            assert!((x * end[1] - y * end[0]).abs() < 1e-4);
+        }
+        let [x, y] = vertices[3].position;
+        assert!(x.abs() < 1e-4 && y >= 10.0);
+
+        assert!(covered(&vertices, &indices, [0.0, 2.5]));
+        assert!(covered(&vertices, &indices, [4.0, 9.0]));
+        assert!(!covered(&vertices, &indices, [0.0, 1.5]));
+        assert!(!covered(&vertices, &indices, [0.0, -2.5]));
+    }
+
+    #[test]
+    fn wide_segment_covers_the_radius() {
+        let (a, b) = ([-20.0, 1.0], [20.0, 1.0]);
+        let (vertices, indices) = geometry([0.0, 0.0], 5.0, &[Occluder::segment(a, b)]);
+        for i in 0..=40 {
+            let x = -20.0 + i as f32;
+            let len = x.hypot(1.0);
+            if len < 5.0 {
+                let p = [x / len * 4.99, 4.99 / len];
+                assert!(covered(&vertices, &indices, p), "{:?}", p);
+            }
+        }
+    }
+
+    #[test]
+    fn skipped_segments() {
+        let light = [1.0, 1.0];
+        let occluders = [
+            // passes through the light.
+            Occluder::segment([0.0, 1.0], [2.0, 1.0]),
+            // seen edge-on.
+            Occluder::segment([2.0, 2.0], [3.0, 3.0]),
+            // ends in the light.
+            Occluder::segment([1.0, 1.0], [2.0, 5.0]),
+            // out of the light radius.
+            Occluder::segment([-10.0, 20.0], [10.0, 20.0]),
+        ];
+        let (vertices, indices) = geometry(light, 10.0, &occluders);
+        assert!(vertices.is_empty());
+        assert!(indices.is_empty());
+    }
+
+    #[test]
+    fn occluder_edges() {
+        let rect = Occluder::rect(2.0, -1.0, 2.0, 2.0);
+        let (vertices, indices) = geometry([0.0, 0.0], 10.0, &[rect]);
+        assert_eq!(vertices.len(), 4 * 5);
+        assert_eq!(indices.len(), 4 * 9);
+
+        let polyline = Occluder::polyline(vec![[2.0, -1.0], [4.0, -1.0], [4.0, 1.0]]);
+        assert_eq!(geometry([0.0, 0.0], 10.0, &[polyline]).0.len(), 2 * 5);
+        // a polygon of two points has a single edge.
+        let polygon = Occluder::polygon(vec![[2.0, -1.0], [2.0, 1.0]]);
+        assert_eq!(geometry([0.0, 0.0], 10.0, &[polygon]).0.len(), 5);
+    }
+
+    #[test]
+    fn appends_to_vertices() {
+        let mut vertices = vec![Vertex::default(); 3];
+        let mut indices = vec![0, 1, 2];
+        let occluder = Occluder::segment([-1.0, 2.0], [1.0, 2.0]);
+        shadow_geometry(
+            [0.0, 0.0],
+            10.0,
+            &[occluder],
+            [1, 2, 3, 4],
+            &mut vertices,
+            &mut indices,
+        );
+        assert_eq!(vertices.len(), 8);
+        assert_eq!(indices[3..], [3, 4, 5, 3, 5, 6, 3, 6, 7]);
+        assert!(vertices[3..].iter().all(|x| x.color == [1, 2, 3, 4]));
+    }
+}
@@ -0,0 +1,502 @@
+use std::f32::consts::PI;
+
+use crate::{MaterialId, SpriteInstance, TextureId};
+#[cfg(any(feature = "opengl", feature = "webgl"))]
+use crate::{SpriteRender, Texture, TextureFilter, TextureWrap};
+
+/// A white texture, created by every [SpriteRender](crate::SpriteRender), that can be used to draw
+/// a [SpriteInstance] with a solid color. It is the texture used by [Shapes].
+pub const WHITE_TEXTURE: TextureId = TextureId(u32::MAX - 2);
+
+/// Create the texture [WHITE_TEXTURE] in the given SpriteRender.
+#[cfg(any(feature = "opengl", feature = "webgl"))]
+pub(crate) fn create_white_texture(sprite_render: &mut dyn SpriteRender) {
+    let result = Texture::new(1, 1)
+        .id(WHITE_TEXTURE)
+        .filter(TextureFilter::Nearest)
+        .wrap(TextureWrap::Repeat)
+        .data(&[255; 4])
+        .create(sprite_render);
+    if let Err(err) = result {
+        log::error!("failed to create white texture: {}", err);
+    }
+}
+
+/// The four vertices drawn by the sprite program for each sprite, in the order bottom left, bottom
+/// right, top left and top right. They form the triangles `(0, 1, 2)` and `(1, 2, 3)`.
+#[cfg_attr(not(any(feature = "opengl", feature = "webgl")), allow(dead_code))]
+#[derive(Clone, Debug)]
+pub(crate) struct Quad {
+    pub positions: [[f32; 2]; 4],
+    pub uvs: [[f32; 2]; 4],
+    pub color: [u8; 4],
+    pub texture: TextureId,
+    pub material: MaterialId,
+}
+impl From<&SpriteInstance> for Quad {
+    fn from(sprite: &SpriteInstance) -> Self {
+        let cos = sprite.angle.cos();
+        let sin = sprite.angle.sin();
+        let width = sprite.get_width() / 2.0;
+        let height = sprite.get_height() / 2.0;
+        let x = sprite.get_x();
+        let y = sprite.get_y();
+        let [u, v, w, h] = sprite.uv_rect;
+        Self {
+            positions: [
+                [
+                    -cos * width + sin * height + x,
+                    -sin * width - cos * height + y,
+                ],
+                [
+                    cos * width + sin * height + x,
+                    sin * width - cos * height + y,
+                ],
+                [
+                    -cos * width - sin * height + x,
+                    -sin * width + cos * height + y,
+                ],
+                [
+                    cos * width - sin * height + x,
+                    sin * width + cos * height + y,
+                ],
+            ],
+            uvs: [[u, v], [u + w, v], [u, v + h], [u + w, v + h]],
+            color: sprite.color,
+            texture: sprite.texture,
+            material: sprite.material,
+        }
+    }
+}
+
+/// A list of solid color shapes, drawn by [Renderer::draw_shapes](crate::Renderer::draw_shapes).
+///
+/// The shapes are decomposed in the same quads used to draw sprites, textured with
+/// [WHITE_TEXTURE], so they are drawn in the same batches as the sprites of the default material.
+/// All coordinates are in world space, and the outlines and lines are centered on their path.
+///
+/// ```
+/// # use sprite_render::Shapes;
+/// let mut shapes = Shapes::new();
+/// shapes
+///     .rect(0.0, 0.0, 2.0, 1.0, [255, 0, 0, 255])
+///     .circle_outline(0.0, 0.0, 3.0, 32, 0.1, [255; 4])
+///     .line([-4.0, -4.0], [4.0, 4.0], 0.05, [0, 255, 0, 255]);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct Shapes {
+    pub(crate) quads: Vec<Quad>,
+}
+impl Shapes {
+    /// Create a empty list of shapes.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Remove all shapes, keeping the allocated memory.
+    pub fn clear(&mut self) {
+        self.quads.clear();
+    }
+
+    /// If there are no shapes.
+    pub fn is_empty(&self) -> bool {
+        self.quads.is_empty()
+    }
+
+    /// A filled rectangle with center in (x, y).
+    pub fn rect(&mut self, x: f32, y: f32, width: f32, height: f32, color: [u8; 4]) -> &mut Self {
+        let (w, h) = (width / 2.0, height / 2.0);
+        self.push_quad(
+            [
+                [x - w, y - h],
+                [x + w, y - h],
+                [x - w, y + h],
+                [x + w, y + h],
+            ],
+            color,
+        );
+        self
+    }
+
+    /// The outline of a rectangle with center in (x, y).
+    pub fn rect_outline(
+        &mut self,
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+        thickness: f32,
+        color: [u8; 4],
+    ) -> &mut Self {
+        let (w, h) = (width / 2.0, height / 2.0);
+        let points = [
+            [x - w, y - h],
+            [x + w, y - h],
+            [x + w, y + h],
+            [x - w, y + h],
+        ];
+        self.stroke(&points, true, thickness, color);
+        self
+    }
+
+    /// A filled triangle.
+    pub fn triangle(&mut self, a: [f32; 2], b: [f32; 2], c: [f32; 2], color: [u8; 4]) -> &mut Self {
+        self.push_quad([a, b, c, c], color);
+        self
+    }
+
+    /// A filled circle with center in (x, y), approximated by a polygon with the given number of
+    /// segments.
+    pub fn circle(
+        &mut self,
+        x: f32,
+        y: f32,
+        radius: f32,
+        segments: u32,
+        color: [u8; 4],
+    ) -> &mut Self {
+        self.ellipse(x, y, radius, radius, segments, color)
+    }
+
+    /// The outline of a circle with center in (x, y), approximated by a polygon with the given
+    /// number of segments.
+    pub fn circle_outline(
+        &mut self,
+        x: f32,
+        y: f32,
+        radius: f32,
+        segments: u32,
+        thickness: f32,
+        color: [u8; 4],
+    ) -> &mut Self {
+        self.ellipse_outline(x, y, radius, radius, segments, thickness, color)
+    }
+
+    /// A filled ellipse with center in (x, y) and the given horizontal and vertical radius,
+    /// approximated by a polygon with the given number of segments (at least 3).
+    pub fn ellipse(
+        &mut self,
+        x: f32,
+        y: f32,
+        radius_x: f32,
+        radius_y: f32,
+        segments: u32,
+        color: [u8; 4],
+    ) -> &mut Self {
+        let points = ellipse_points(x, y, radius_x, radius_y, segments);
+        self.convex_polygon(&points, color);
+        self
+    }
+
+    /// The outline of a ellipse with center in (x, y) and the given horizontal and vertical radius,
+    /// approximated by a polygon with the given number of segments (at least 3).
+    #[allow(clippy::too_many_arguments)]
+    pub fn ellipse_outline(
+        &mut self,
+        x: f32,
+        y: f32,
+        radius_x: f32,
+        radius_y: f32,
+        segments: u32,
+        thickness: f32,
+        color: [u8; 4],
+    ) -> &mut Self {
+        let points = ellipse_points(x, y, radius_x, radius_y, segments);
+        self.stroke(&points, true, thickness, color);
+        self
+    }
+
+    /// A filled simple polygon (its edges don't cross), convex or not, in clockwise or
+    /// counterclockwise order.
+    pub fn polygon(&mut self, points: &[[f32; 2]], color: [u8; 4]) -> &mut Self {
+        let points = dedup(points, true);
+        if points.len() < 3 {
+            return self;
+        }
+        if is_convex(&points) {
+            self.convex_polygon(&points, color);
+            return self;
+        }
+        for [a, b, c] in triangulate(&points) {
+            self.push_quad([points[a], points[b], points[c], points[c]], color);
+        }
+        self
+    }
+
+    /// The outline of a polygon.
+    pub fn polygon_outline(
+        &mut self,
+        points: &[[f32; 2]],
+        thickness: f32,
+        color: [u8; 4],
+    ) -> &mut Self {
+        self.stroke(points, true, thickness, color);
+        self
+    }
+
+    /// A line segment from `a` to `b`.
+    pub fn line(&mut self, a: [f32; 2], b: [f32; 2], thickness: f32, color: [u8; 4]) -> &mut Self {
+        self.stroke(&[a, b], false, thickness, color);
+        self
+    }
+
+    /// A sequence of connected line segments, with mitered joints.
+    pub fn polyline(&mut self, points: &[[f32; 2]], thickness: f32, color: [u8; 4]) -> &mut Self {
+        self.stroke(points, false, thickness, color);
+        self
+    }
+
+    fn push_quad(&mut self, positions: [[f32; 2]; 4], color: [u8; 4]) {
+        self.quads.push(Quad {
+            positions,
+            uvs: [[0.5; 2]; 4],
+            color,
+            texture: WHITE_TEXTURE,
+            material: MaterialId::default(),
+        });
+    }
+
+    /// Fill a convex polygon as a triangle strip, zigzagging between its two sides, with two
+    /// triangles per quad.
+    fn convex_polygon(&mut self, points: &[[f32; 2]], color: [u8; 4]) {
+        let n = points.len();
+        if n < 3 {
+            return;
+        }
+        let strip = (0..n)
+            .map(|i| (if i % 2 == 0 { n - i / 2 } else { i / 2 + 1 }) % n)
+            .map(|i| points[i])
+            .collect::<Vec<_>>();
+        let mut i = 0;
+        while i + 2 < n {
+            let d = strip[(i + 3).min(n - 1)];
+            self.push_quad([strip[i], strip[i + 1], strip[i + 2], d], color);
+            i += 2;
+        }
+    }
+
+    /// Stroke a path with the given thickness, with mitered joints, and closing it if `closed`.
+    fn stroke(&mut self, points: &[[f32; 2]], closed: bool, thickness: f32, color: [u8; 4]) {
+        let points = dedup(points, closed);
+        let n = points.len();
+        if n < 2 {
+            return;
+        }
+        let half = thickness / 2.0;
+        let normal = |a: [f32; 2], b: [f32; 2]| {
+            let [x, y] = normalize([b[0] - a[0], b[1] - a[1]]);
+            [-y, x]
+        };
+        // the offset from each point to the sides of the stroke.
+        let offsets = (0..n)
+            .map(|i| {
+                let prev = (i > 0 || closed).then(|| normal(points[(i + n - 1) % n], points[i]));
+                let next = (i + 1 < n || closed).then(|| normal(points[i], points[(i + 1) % n]));
+                let (a, b) = match (prev, next) {
+                    (Some(a), Some(b)) => (a, b),
+                    (Some(a), None) => (a, a),
+                    (None, Some(b)) => (b, b),
+                    (None, None) => unreachable!(),
+                };
+                let miter = normalize([a[0] + b[0], a[1] + b[1]]);
+                let cos = miter[0] * a[0] + miter[1] * a[1];
+                // limit the length of sharp joints.
+                let len = half / cos.max(0.25);
+                [miter[0] * len, miter[1] * len]
+            })
+            .collect::<Vec<_>>();
+        let segments = if closed { n } else { n - 1 };
+        for i in 0..segments {
+            let j = (i + 1) % n;
+            let (p, q) = (points[i], points[j]);
+            let (o, r) = (offsets[i], offsets[j]);
+            self.push_quad(
+                [
+                    [p[0] - o[0], p[1] - o[1]],
+                    [p[0] + o[0], p[1] + o[1]],
+                    [q[0] - r[0], q[1] - r[1]],
+                    [q[0] + r[0], q[1] + r[1]],
+                ],
+                color,
+            );
+        }
+    }
+}
+
+fn ellipse_points(x: f32, y: f32, radius_x: f32, radius_y: f32, segments: u32) -> Vec<[f32; 2]> {
+    let segments = segments.max(3);
+    (0..segments)
+        .map(|i| {
+            let angle = i as f32 * 2.0 * PI / segments as f32;
+            [x + radius_x * angle.cos(), y + radius_y * angle.sin()]
+        })
+        .collect()
+}
+
+fn normalize([x, y]: [f32; 2]) -> [f32; 2] {
+    let len = (x * x + y * y).sqrt();
+    if len == 0.0 {
+        [0.0, 0.0]
+    } else {
+        [x / len, y / len]
+    }
+}
+
+fn cross(o: [f32; 2], a: [f32; 2], b: [f32; 2]) -> f32 {
+    (a[0] - o[0]) * (b[1] - o[1]) - (a[1] - o[1]) * (b[0] - o[0])
+}
+
+/// Remove the consecutive repeated points, which have no direction between them.
+fn dedup(points: &[[f32; 2]], closed: bool) -> Vec<[f32; 2]> {
+    let mut points = points.to_vec();
+    points.dedup();
+    if closed && points.len() > 1 && points.first() == points.last() {
+        points.pop();
+    }
+    points
+}
+
+fn is_convex(points: &[[f32; 2]]) -> bool {
+    let n = points.len();
+    let mut sign = 0.0;
+    for i in 0..n {
+        let c = cross(points[i], points[(i + 1) % n], points[(i + 2) % n]);
+        if c != 0.0 {
+            if sign * c < 0.0 {
+                return false;
+            }
+            sign = c;
+        }
+    }
+    true
+}
+
+/// Triangulate a simple polygon by ear clipping, returning the indices of the triangles.
+fn triangulate(points: &[[f32; 2]]) -> Vec<[usize; 3]> {
+    let area: f32 = (0..points.len())
+        .map(|i| cross([0.0; 2], points[i], points[(i + 1) % points.len()]))
+        .sum();
+    let orientation = area.signum();
+
+    let mut remaining = (0..points.len()).collect::<Vec<usize>>();
+    let mut triangles = Vec::with_capacity(points.len() - 2);
+    while remaining.len() > 3 {
+        let n = remaining.len();
+        let is_ear = |i: usize| {
+            let [a, b, c] = [
+                remaining[(i + n - 1) % n],
+                remaining[i],
+                remaining[(i + 1) % n],
+            ];
+            let [pa, pb, pc] = [points[a], points[b], points[c]];
+            if cross(pa, pb, pc) * orientation <= 0.0 {
+                return false;
+            }
+            !remaining.iter().any(|&p| {
+                p != a
+                    && p != b
+                    && p != c
+                    && cross(pa, pb, points[p]) * orientation >= 0.0
+                    && cross(pb, pc, points[p]) * orientation >= 0.0
+                    && cross(pc, pa, points[p]) * orientation >= 0.0
+            })
+        };
+        // if the polygon is not simple there may be no ear, so clip any vertex to make progress.
+        let i = (0..n).find(|&i| is_ear(i)).unwrap_or(0);
+        triangles.push([
+            remaining[(i + n - 1) % n],
+            remaining[i],
+            remaining[(i + 1) % n],
+        ]);
+        remaining.remove(i);
+    }
+    triangles.push([remaining[0], remaining[1], remaining[2]]);
+    triangles
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SQUARE: [[f32; 2]; 4] = [[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0]];
+    /// A concave L shape, with area 3.
+    const L_SHAPE: [[f32; 2]; 6] = [
+        [0.0, 0.0],
+        [2.0, 0.0],
+        [2.0, 1.0],
+        [1.0, 1.0],
+        [1.0, 2.0],
+        [0.0, 2.0],
+    ];
+
+    /// Check that the triangles use each point, have the winding of the polygon, and cover its
+    /// area.
+    fn assert_triangulation(points: &[[f32; 2]], area: f32) {
+        let triangles = triangulate(points);
+        assert_eq!(triangles.len(), points.len() - 2);
+        let mut used = vec![false; points.len()];
+        let mut total = 0.0;
+        for [a, b, c] in triangles {
+            used[a] = true;
+            used[b] = true;
+            used[c] = true;
+            let triangle_area = cross(points[a], points[b], points[c]) / 2.0;
+            assert!(triangle_area * area > 0.0, "{:?} is inverted", [a, b, c]);
+            total += triangle_area;
+        }
+        assert!(used.iter().all(|x| *x));
+        assert!((total - area).abs() < 1e-5, "{} != {}", total, area);
+    }
+
+    #[test]
+    fn convex() {
+        assert!(is_convex(&SQUARE));
+        let mut clockwise = SQUARE;
+        clockwise.reverse();
+        assert!(is_convex(&clockwise));
+        // collinear points do not make a polygon concave.
+        assert!(is_convex(&[[0.0, 0.0], [1.0, 0.0], [2.0, 0.0], [1.0, 1.0]]));
+        assert!(!is_convex(&L_SHAPE));
+    }
+
+    #[test]
+    fn triangulate_convex() {
+        assert_triangulation(&SQUARE, 1.0);
+        assert_triangulation(&[[0.0, 0.0], [1.0, 0.0], [0.0, 1.0]], 0.5);
+    }
+
+    #[test]
+    fn triangulate_concave() {
+        assert_triangulation(&L_SHAPE, 3.0);
+        let mut clockwise = L_SHAPE;
+        clockwise.reverse();
+        assert_triangulation(&clockwise, -3.0);
+
+        // a comb with two teeth, whose first vertices are not ears.
+        let comb = [
+            [0.0, 0.0],
+            [3.0, 0.0],
+            [3.0, 2.0],
+            [2.0, 2.0],
+            [2.0, 1.0],
+            [1.0, 1.0],
+            [1.0, 2.0],
+            [0.0, 2.0],
+        ];
+        assert_triangulation(&comb, 5.0);
+    }
+
+    #[test]
+    fn triangulate_not_simple() {
+        // a bow tie has no valid triangulation, but it must not loop forever.
+        let bow_tie = [[0.0, 0.0], [1.0, 1.0], [1.0, 0.0], [0.0, 1.0]];
+        assert_eq!(triangulate(&bow_tie).len(), 2);
+    }
+
+    #[test]
+    fn dedup_points() {
+        let points = [[0.0, 0.0], [0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 0.0]];
+        assert_eq!(dedup(&points, false), points[1..]);
+        assert_eq!(dedup(&points, true), points[1..4]);
+    }
+}
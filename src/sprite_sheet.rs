@@ -0,0 +1,767 @@
+//! Importers of the sprite sheets exported by Aseprite and TexturePacker.
+
+mod json;
+mod xml;
+
+use std::f32::consts::FRAC_PI_2;
+
+use crate::{AnimationClip, Frame, NineSlice, PlayMode, SpriteInstance, TextureId};
+
+use json::Value;
+
+/// Error generate when importing a [SpriteSheet].
+#[derive(Debug)]
+pub enum SheetError {
+    /// The data is not a valid JSON or XML document. Contains the line of the error.
+    Syntax(usize),
+    /// A required field is missing, or has the wrong type.
+    MissingField(&'static str),
+}
+impl std::fmt::Display for SheetError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SheetError::Syntax(line) => write!(f, "syntax error at line {}", line),
+            SheetError::MissingField(name) => write!(f, "missing or invalid field '{}'", name),
+        }
+    }
+}
+impl std::error::Error for SheetError {}
+
+/// The line of a byte offset of `text`, starting from 1.
+fn syntax_error(text: &str, offset: usize) -> SheetError {
+    SheetError::Syntax(
+        text.as_bytes()[..offset]
+            .iter()
+            .filter(|&&x| x == b'\n')
+            .count()
+            + 1,
+    )
+}
+
+/// A sprite packed in a [SpriteSheet].
+#[derive(Clone, Debug, PartialEq)]
+pub struct AtlasRegion {
+    pub name: String,
+    /// The `[x, y, width, height]` of the region in the texture, in pixels. For rotated regions,
+    /// this is the rotated rectangle, as stored in the texture.
+    pub rect: [u32; 4],
+    /// The region of the texture, in normalized texture coordinates, equivalent to `rect`.
+    pub uv_rect: [f32; 4],
+    /// If the sprite is stored rotated 90 degrees clockwise in the texture.
+    pub rotated: bool,
+    /// The `[width, height]` of the original sprite, before the transparent borders were trimmed.
+    pub source_size: [u32; 2],
+    /// The `[x, y, width, height]` of the region in the original sprite, in pixels. It is the whole
+    /// sprite if it was not trimmed.
+    pub trim_rect: [u32; 4],
+    /// The pivot of the original sprite, in normalized coordinates, if the sheet defines one.
+    pub pivot: Option<[f32; 2]>,
+    /// The duration of the frame, in seconds, or 0.0 if the sheet has no durations.
+    pub duration: f32,
+}
+impl AtlasRegion {
+    fn new(name: String, rect: [u32; 4], rotated: bool, texture_size: [u32; 2]) -> Self {
+        let [x, y, w, h] = rect;
+        let (w, h) = if rotated { (h, w) } else { (w, h) };
+        let [tw, th] = texture_size.map(|x| x.max(1) as f32);
+        Self {
+            name,
+            rect: [x, y, w, h],
+            uv_rect: [x as f32 / tw, y as f32 / th, w as f32 / tw, h as f32 / th],
+            rotated,
+            source_size: [rect[2], rect[3]],
+            trim_rect: [0, 0, rect[2], rect[3]],
+            pivot: None,
+            duration: 0.0,
+        }
+    }
+
+    /// If the transparent borders of the sprite were trimmed.
+    pub fn is_trimmed(&self) -> bool {
+        self.trim_rect != [0, 0, self.source_size[0], self.source_size[1]]
+    }
+
+    /// A sprite showing this region with the original sprite centered in (x, y), and with `scale`
+    /// world units per pixel. Trimmed regions are offset to their place in the original sprite, and
+    /// rotated regions are rotated back.
+    pub fn sprite(&self, texture: TextureId, x: f32, y: f32, scale: f32) -> SpriteInstance {
+        let [tx, ty, tw, th] = self.trim_rect.map(|x| x as f32);
+        let [sw, sh] = self.source_size.map(|x| x as f32);
+        let x = x + (tx + tw / 2.0 - sw / 2.0) * scale;
+        let y = y + (ty + th / 2.0 - sh / 2.0) * scale;
+        if self.rotated {
+            SpriteInstance::new(x, y, th * scale, tw * scale, texture, self.uv_rect)
+                .with_angle(-FRAC_PI_2)
+        } else {
+            SpriteInstance::new(x, y, tw * scale, th * scale, texture, self.uv_rect)
+        }
+    }
+
+    /// A animation frame showing this region for `duration` seconds.
+    ///
+    /// The pivot of the frame keeps the sprite in place when the frames are trimmed differently.
+    /// The size of trimmed frames is `trim_rect`, smaller than the original sprite. The frames do not
+    /// support rotated regions.
+    pub fn frame(&self, texture: TextureId, duration: f32) -> Frame {
+        if self.rotated {
+            log::warn!(
+                "animation frame '{}' is rotated in the sprite sheet",
+                self.name
+            );
+        }
+        let mut frame = Frame::new(texture, self.uv_rect, duration);
+        if self.is_trimmed() || self.pivot.is_some() {
+            let [px, py] = self.pivot.unwrap_or([0.5, 0.5]);
+            let [tx, ty, tw, th] = self.trim_rect.map(|x| x as f32);
+            let [sw, sh] = self.source_size.map(|x| x as f32);
+            frame = frame.with_pivot((px * sw - tx) / tw.max(1.0), (py * sh - ty) / th.max(1.0));
+        }
+        frame
+    }
+}
+
+/// A named range of frames of a Aseprite sprite sheet.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FrameTag {
+    pub name: String,
+    /// The index of the first frame.
+    pub from: usize,
+    /// The index of the last frame, inclusive.
+    pub to: usize,
+    /// If the frames are played from the last to the first.
+    pub reverse: bool,
+    pub mode: PlayMode,
+}
+
+/// A named region of the sprite of a Aseprite sprite sheet, with optional 9-patch data.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SheetSlice {
+    pub name: String,
+    /// The index of the frame where the slice is defined.
+    pub frame: usize,
+    /// The `[x, y, width, height]` of the slice in the original sprite, in pixels.
+    pub bounds: [u32; 4],
+    /// The `[x, y, width, height]` of the center of the 9-patch, relative to `bounds`.
+    pub center: Option<[u32; 4]>,
+    /// The pivot of the slice, relative to `bounds`, in pixels.
+    pub pivot: Option<[i32; 2]>,
+}
+
+/// The regions of the sprites packed in a texture, with animation tags and slices, imported from
+/// the JSON sheets of Aseprite and TexturePacker, or the XML atlases of TexturePacker.
+///
+/// The regions can be turned into [SpriteInstance]s with [AtlasRegion::sprite], and into
+/// animation clips with [SpriteSheet::clip] and [SpriteSheet::clip_by_prefix]. The texture itself
+/// must be loaded separately, from the file in `image`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SpriteSheet {
+    /// The file name of the texture, if the sheet gives it.
+    pub image: Option<String>,
+    /// The `[width, height]` of the texture, in pixels.
+    pub texture_size: [u32; 2],
+    /// The regions, in the order they appear in the sheet.
+    pub regions: Vec<AtlasRegion>,
+    pub tags: Vec<FrameTag>,
+    pub slices: Vec<SheetSlice>,
+}
+impl SpriteSheet {
+    /// Import a JSON sprite sheet exported by Aseprite, or by TexturePacker in the "JSON (Hash)"
+    /// or "JSON (Array)" formats.
+    ///
+    /// The frame durations, tags and slices are only present in the Aseprite sheets. Only the
+    /// first key of each slice is imported.
+    pub fn from_json(text: &str) -> Result<Self, SheetError> {
+        let root = json::parse(text).map_err(|x| syntax_error(text, x))?;
+        let meta = field(&root, "meta")?;
+        let size = field(meta, "size")?;
+        let texture_size = [number(size, "w")? as u32, number(size, "h")? as u32];
+
+        let mut sheet = SpriteSheet {
+            image: meta.get("image").and_then(Value::as_str).map(String::from),
+            texture_size,
+            ..Default::default()
+        };
+        match field(&root, "frames")? {
+            Value::Object(frames) => {
+                for (name, frame) in frames {
+                    sheet
+                        .regions
+                        .push(json_region(name.clone(), frame, texture_size)?);
+                }
+            }
+            Value::Array(frames) => {
+                for frame in frames {
+                    let name = field(frame, "filename")?.as_str();
+                    let name = name.ok_or(SheetError::MissingField("filename"))?;
+                    sheet
+                        .regions
+                        .push(json_region(name.into(), frame, texture_size)?);
+                }
+            }
+            _ => return Err(SheetError::MissingField("frames")),
+        }
+
+        let tags = meta.get("frameTags").and_then(Value::as_array);
+        for tag in tags.unwrap_or_default() {
+            let direction = tag.get("direction").and_then(Value::as_str);
+            let repeat = tag.get("repeat").and_then(Value::as_str);
+            let mode = match direction {
+                Some("pingpong" | "pingpong_reverse") => PlayMode::PingPong,
+                _ if repeat == Some("1") => PlayMode::Once,
+                _ => PlayMode::Loop,
+            };
+            sheet.tags.push(FrameTag {
+                name: string(tag, "name")?,
+                from: number(tag, "from")? as usize,
+                to: number(tag, "to")? as usize,
+                reverse: matches!(direction, Some("reverse" | "pingpong_reverse")),
+                mode,
+            });
+        }
+
+        let slices = meta.get("slices").and_then(Value::as_array);
+        for slice in slices.unwrap_or_default() {
+            let keys = field(slice, "keys")?.as_array();
+            let Some(key) = keys.and_then(|x| x.first()) else {
+                continue;
+            };
+            let center = key.get("center").map(rect).transpose()?;
+            let pivot = match key.get("pivot") {
+                Some(p) => Some([number(p, "x")? as i32, number(p, "y")? as i32]),
+                None => None,
+            };
+            sheet.slices.push(SheetSlice {
+                name: string(slice, "name")?,
+                frame: key.get("frame").and_then(Value::as_f64).unwrap_or(0.0) as usize,
+                bounds: rect(field(key, "bounds")?)?,
+                center,
+                pivot,
+            });
+        }
+        Ok(sheet)
+    }
+
+    /// Import a XML atlas exported by TexturePacker in the "XML (generic)" format.
+    pub fn from_texture_packer_xml(text: &str) -> Result<Self, SheetError> {
+        let elements = xml::elements(text).map_err(|x| syntax_error(text, x))?;
+        let atlas = elements
+            .iter()
+            .find(|x| x.name == "TextureAtlas")
+            .ok_or(SheetError::MissingField("TextureAtlas"))?;
+        let texture_size = [attribute(atlas, "width")?, attribute(atlas, "height")?];
+
+        let mut sheet = SpriteSheet {
+            image: atlas.attribute("imagePath").map(String::from),
+            texture_size,
+            ..Default::default()
+        };
+        for sprite in elements.iter().filter(|x| x.name == "sprite") {
+            let name = sprite.attribute("n").ok_or(SheetError::MissingField("n"))?;
+            let rect = [
+                attribute(sprite, "x")?,
+                attribute(sprite, "y")?,
+                attribute(sprite, "w")?,
+                attribute(sprite, "h")?,
+            ];
+            let rotated = sprite.attribute("r") == Some("y");
+            let mut region = AtlasRegion::new(name.into(), rect, rotated, texture_size);
+            if let (Some(w), Some(h)) = (sprite.attribute("oW"), sprite.attribute("oH")) {
+                let parse = |x: &str| x.parse().map_err(|_| SheetError::MissingField("oW"));
+                region.source_size = [parse(w)?, parse(h)?];
+                let offset = |name| sprite.attribute(name).and_then(|x| x.parse().ok());
+                region.trim_rect = [
+                    offset("oX").unwrap_or(0),
+                    offset("oY").unwrap_or(0),
+                    rect[2],
+                    rect[3],
+                ];
+            }
+            let pivot = |name| sprite.attribute(name).and_then(|x| x.parse().ok());
+            if let (Some(x), Some(y)) = (pivot("pX"), pivot("pY")) {
+                region.pivot = Some([x, y]);
+            }
+            sheet.regions.push(region);
+        }
+        Ok(sheet)
+    }
+
+    /// The region with the given name.
+    pub fn region(&self, name: &str) -> Option<&AtlasRegion> {
+        self.regions.iter().find(|x| x.name == name)
+    }
+
+    /// The animation clip of the frames of the tag with the given name, with their durations from
+    /// the sheet.
+    pub fn clip(&self, tag: &str, texture: TextureId) -> Option<AnimationClip> {
+        let tag = self.tags.iter().find(|x| x.name == tag)?;
+        let regions = self.regions.get(tag.from..=tag.to)?;
+        let mut frames = regions
+            .iter()
+            .map(|x| x.frame(texture, x.duration))
+            .collect::<Vec<_>>();
+        if tag.reverse {
+            frames.reverse();
+        }
+        Some(AnimationClip::new(frames).with_mode(tag.mode))
+    }
+
+    /// A looping animation clip of the regions whose names start with `prefix`, in the order they
+    /// appear in the sheet. Regions with no duration are shown for `default_duration` seconds.
+    pub fn clip_by_prefix(
+        &self,
+        prefix: &str,
+        texture: TextureId,
+        default_duration: f32,
+    ) -> AnimationClip {
+        let frames = self
+            .regions
+            .iter()
+            .filter(|x| x.name.starts_with(prefix))
+            .map(|x| {
+                let duration = if x.duration > 0.0 {
+                    x.duration
+                } else {
+                    default_duration
+                };
+                x.frame(texture, duration)
+            })
+            .collect();
+        AnimationClip::new(frames)
+    }
+
+    /// A [NineSlice] of the slice with the given name, using its 9-patch center as the insets.
+    ///
+    /// Returns None if the slice does not exist, has no 9-patch data, or its frame is rotated.
+    pub fn nine_slice(&self, slice: &str, texture: TextureId) -> Option<NineSlice> {
+        let slice = self.slices.iter().find(|x| x.name == slice)?;
+        let [cx, cy, cw, ch] = slice.center?;
+        let region = self.regions.get(slice.frame)?;
+        if region.rotated {
+            log::warn!("the frame of slice '{}' is rotated", slice.name);
+            return None;
+        }
+        // the slice bounds are in the original sprite, which may be trimmed.
+        let [bx, by, bw, bh] = slice.bounds.map(|x| x as f32);
+        let x = region.rect[0] as f32 - region.trim_rect[0] as f32 + bx;
+        let y = region.rect[1] as f32 - region.trim_rect[1] as f32 + by;
+        let [tw, th] = self.texture_size.map(|x| x.max(1) as f32);
+        let uv_rect = [x / tw, y / th, bw / tw, bh / th];
+        let [cx, cy, cw, ch] = [cx, cy, cw, ch].map(|x| x as f32);
+        let insets = [cx, bw - cx - cw, cy, bh - cy - ch];
+        let [tw, th] = self.texture_size;
+        Some(NineSlice::new(texture, tw, th, uv_rect, insets))
+    }
+}
+
+fn field<'a>(value: &'a Value, name: &'static str) -> Result<&'a Value, SheetError> {
+    value.get(name).ok_or(SheetError::MissingField(name))
+}
+
+fn number(value: &Value, name: &'static str) -> Result<f64, SheetError> {
+    field(value, name)?
+        .as_f64()
+        .ok_or(SheetError::MissingField(name))
+}
+
+fn string(value: &Value, name: &'static str) -> Result<String, SheetError> {
+    let string = field(value, name)?.as_str();
+    string
+        .map(String::from)
+        .ok_or(SheetError::MissingField(name))
+}
+
+/// A `{ "x", "y", "w", "h" }` object.
+fn rect(value: &Value) -> Result<[u32; 4], SheetError> {
+    Ok([
+        number(value, "x")? as u32,
+        number(value, "y")? as u32,
+        number(value, "w")? as u32,
+        number(value, "h")? as u32,
+    ])
+}
+
+fn attribute(element: &xml::Element, name: &'static str) -> Result<u32, SheetError> {
+    let value = element.attribute(name).and_then(|x| x.parse().ok());
+    value.ok_or(SheetError::MissingField(name))
+}
+
+/// A frame of a JSON sheet.
+fn json_region(
+    name: String,
+    frame: &Value,
+    texture_size: [u32; 2],
+) -> Result<AtlasRegion, SheetError> {
+    // the rect of rotated frames has the size of the unrotated sprite.
+    let frame_rect = rect(field(frame, "frame")?)?;
+    let rotated = frame
+        .get("rotated")
+        .and_then(Value::as_bool)
+        .unwrap_or(false);
+    let mut region = AtlasRegion::new(name, frame_rect, rotated, texture_size);
+    if let Some(size) = frame.get("sourceSize") {
+        region.source_size = [number(size, "w")? as u32, number(size, "h")? as u32];
+    }
+    if let Some(trim) = frame.get("spriteSourceSize") {
+        region.trim_rect = rect(trim)?;
+    }
+    if let Some(pivot) = frame.get("pivot") {
+        region.pivot = Some([number(pivot, "x")? as f32, number(pivot, "y")? as f32]);
+    }
+    if let Some(duration) = frame.get("duration").and_then(Value::as_f64) {
+        region.duration = duration as f32 / 1000.0;
+    }
+    Ok(region)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ASEPRITE_HASH: &str = r##"{ "frames": {
+  "run 0.aseprite": {
+    "frame": { "x": 0, "y": 0, "w": 16, "h": 16 },
+    "rotated": false,
+    "trimmed": false,
+    "spriteSourceSize": { "x": 0, "y": 0, "w": 16, "h": 16 },
+    "sourceSize": { "w": 16, "h": 16 },
+    "duration": 100
+  },
+  "run 1.aseprite": {
+    "frame": { "x": 16, "y": 0, "w": 12, "h": 14 },
+    "rotated": false,
+    "trimmed": true,
+    "spriteSourceSize": { "x": 2, "y": 1, "w": 12, "h": 14 },
+    "sourceSize": { "w": 16, "h": 16 },
+    "duration": 150
+  }
+ },
+ "meta": {
+  "app": "https://www.aseprite.org/",
+  "version": "1.3",
+  "image": "run.png",
+  "format": "RGBA8888",
+  "size": { "w": 32, "h": 16 },
+  "scale": "1",
+  "frameTags": [
+   { "name": "run", "from": 0, "to": 1, "direction": "pingpong", "color": "#000000ff" },
+   { "name": "fall", "from": 0, "to": 1, "direction": "reverse", "repeat": "1" }
+  ],
+  "layers": [
+   { "name": "Layer", "opacity": 255, "blendMode": "normal" }
+  ],
+  "slices": [
+   { "name": "panel", "color": "#0000ffff", "keys": [
+     { "frame": 0, "bounds": { "x": 0, "y": 0, "w": 16, "h": 16 },
+       "center": { "x": 4, "y": 3, "w": 8, "h": 9 }, "pivot": { "x": 8, "y": 16 } }
+   ] }
+  ]
+ }
+}
+"##;
+
+    const ASEPRITE_ARRAY: &str = r##"{ "frames": [
+  {
+    "filename": "run 0.aseprite",
+    "frame": { "x": 0, "y": 0, "w": 16, "h": 16 },
+    "rotated": false,
+    "trimmed": false,
+    "spriteSourceSize": { "x": 0, "y": 0, "w": 16, "h": 16 },
+    "sourceSize": { "w": 16, "h": 16 },
+    "duration": 100
+  },
+  {
+    "filename": "run 1.aseprite",
+    "frame": { "x": 16, "y": 0, "w": 12, "h": 14 },
+    "rotated": false,
+    "trimmed": true,
+    "spriteSourceSize": { "x": 2, "y": 1, "w": 12, "h": 14 },
+    "sourceSize": { "w": 16, "h": 16 },
+    "duration": 150
+  }
+ ],
+ "meta": {
+  "app": "https://www.aseprite.org/",
+  "image": "run.png",
+  "size": { "w": 32, "h": 16 },
+  "frameTags": [],
+  "slices": []
+ }
+}
+"##;
+
+    const TEXTURE_PACKER_JSON: &str = r##"{"frames": {
+
+"hero.png":
+{
+	"frame": {"x":2,"y":2,"w":30,"h":20},
+	"rotated": true,
+	"trimmed": true,
+	"spriteSourceSize": {"x":1,"y":3,"w":30,"h":20},
+	"sourceSize": {"w":32,"h":24},
+	"pivot": {"x":0.5,"y":1}
+},
+"coin.png":
+{
+	"frame": {"x":24,"y":2,"w":8,"h":8},
+	"rotated": false,
+	"trimmed": false,
+	"spriteSourceSize": {"x":0,"y":0,"w":8,"h":8},
+	"sourceSize": {"w":8,"h":8},
+	"pivot": {"x":0.5,"y":0.5}
+}},
+"meta": {
+	"app": "https://www.codeandweb.com/texturepacker",
+	"version": "1.0",
+	"image": "atlas.png",
+	"format": "RGBA8888",
+	"size": {"w":64,"h":32},
+	"scale": "1"
+}
+}
+"##;
+
+    const TEXTURE_PACKER_XML: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!-- Created with TexturePacker http://www.codeandweb.com/texturepacker-->
+<TextureAtlas imagePath="atlas.png" width="64" height="32">
+    <sprite n="coin.png" x="0" y="0" w="16" h="16" pX="0.5" pY="0.25"/>
+    <sprite n="gem &amp; ring.png" x="16" y="0" w="10" h="12" oX="3" oY="2" oW="16" oH="16" r="y"/>
+</TextureAtlas>
+"#;
+
+    fn assert_close(a: f32, b: f32) {
+        assert!((a - b).abs() < 1e-5, "{} != {}", a, b);
+    }
+
+    fn assert_run_regions(sheet: &SpriteSheet) {
+        assert_eq!(sheet.image.as_deref(), Some("run.png"));
+        assert_eq!(sheet.texture_size, [32, 16]);
+        assert_eq!(sheet.regions.len(), 2);
+
+        let first = &sheet.regions[0];
+        assert_eq!(first.name, "run 0.aseprite");
+        assert_eq!(first.rect, [0, 0, 16, 16]);
+        assert_eq!(first.uv_rect, [0.0, 0.0, 0.5, 1.0]);
+        assert!(!first.is_trimmed());
+        assert_close(first.duration, 0.1);
+
+        let second = &sheet.regions[1];
+        assert_eq!(second.name, "run 1.aseprite");
+        assert_eq!(second.rect, [16, 0, 12, 14]);
+        assert_eq!(second.source_size, [16, 16]);
+        assert_eq!(second.trim_rect, [2, 1, 12, 14]);
+        assert!(second.is_trimmed());
+        assert_close(second.duration, 0.15);
+    }
+
+    #[test]
+    fn aseprite_hash() {
+        let sheet = SpriteSheet::from_json(ASEPRITE_HASH).unwrap();
+        assert_run_regions(&sheet);
+
+        assert_eq!(
+            sheet.tags,
+            [
+                FrameTag {
+                    name: "run".into(),
+                    from: 0,
+                    to: 1,
+                    reverse: false,
+                    mode: PlayMode::PingPong,
+                },
+                FrameTag {
+                    name: "fall".into(),
+                    from: 0,
+                    to: 1,
+                    reverse: true,
+                    mode: PlayMode::Once,
+                },
+            ]
+        );
+        assert_eq!(
+            sheet.slices,
+            [SheetSlice {
+                name: "panel".into(),
+                frame: 0,
+                bounds: [0, 0, 16, 16],
+                center: Some([4, 3, 8, 9]),
+                pivot: Some([8, 16]),
+            }]
+        );
+    }
+
+    #[test]
+    fn aseprite_array() {
+        let sheet = SpriteSheet::from_json(ASEPRITE_ARRAY).unwrap();
+        assert_run_regions(&sheet);
+        assert!(sheet.tags.is_empty());
+        assert!(sheet.slices.is_empty());
+    }
+
+    #[test]
+    fn aseprite_clips() {
+        let sheet = SpriteSheet::from_json(ASEPRITE_HASH).unwrap();
+        let texture = TextureId(1);
+
+        let run = sheet.clip("run", texture).unwrap();
+        assert_eq!(run.mode(), PlayMode::PingPong);
+        assert_eq!(run.frames().len(), 2);
+        assert_eq!(run.frames()[0].pivot, None);
+        // the trimmed frame is kept in place by its pivot, the center of the original sprite.
+        assert_eq!(run.frames()[1].pivot, Some([0.5, 0.5]));
+        assert_close(run.frames()[1].duration, 0.15);
+
+        let fall = sheet.clip("fall", texture).unwrap();
+        assert_eq!(fall.mode(), PlayMode::Once);
+        assert_eq!(fall.frames()[0].uv_rect, sheet.regions[1].uv_rect);
+
+        assert!(sheet.clip("jump", texture).is_none());
+
+        let by_prefix = sheet.clip_by_prefix("run ", texture, 0.5);
+        assert_eq!(by_prefix.mode(), PlayMode::Loop);
+        assert_eq!(by_prefix.frames().len(), 2);
+    }
+
+    #[test]
+    fn aseprite_nine_slice() {
+        let sheet = SpriteSheet::from_json(ASEPRITE_HASH).unwrap();
+        let slice = sheet.nine_slice("panel", TextureId(1)).unwrap();
+        assert_eq!(slice.uv_rect, [0.0, 0.0, 0.5, 1.0]);
+        assert_eq!(slice.texture_size, [32, 16]);
+        assert_eq!(slice.insets, [4.0, 4.0, 3.0, 4.0]);
+        assert!(sheet.nine_slice("missing", TextureId(1)).is_none());
+    }
+
+    #[test]
+    fn texture_packer_json() {
+        let sheet = SpriteSheet::from_json(TEXTURE_PACKER_JSON).unwrap();
+        assert_eq!(sheet.image.as_deref(), Some("atlas.png"));
+        assert_eq!(sheet.texture_size, [64, 32]);
+
+        let hero = sheet.region("hero.png").unwrap();
+        assert!(hero.rotated);
+        // the frame of rotated sprites has the unrotated size.
+        assert_eq!(hero.rect, [2, 2, 20, 30]);
+        assert_eq!(
+            hero.uv_rect,
+            [2.0 / 64.0, 2.0 / 32.0, 20.0 / 64.0, 30.0 / 32.0]
+        );
+        assert_eq!(hero.source_size, [32, 24]);
+        assert_eq!(hero.trim_rect, [1, 3, 30, 20]);
+        assert!(hero.is_trimmed());
+        assert_eq!(hero.pivot, Some([0.5, 1.0]));
+        assert_eq!(hero.duration, 0.0);
+
+        let sprite = hero.sprite(TextureId(1), 10.0, 20.0, 2.0);
+        assert_eq!(sprite.pos, [10.0, 22.0]);
+        assert_eq!(sprite.scale, [40.0, 60.0]);
+        assert_close(sprite.angle, -FRAC_PI_2);
+
+        let frame = hero.frame(TextureId(1), 0.1);
+        let [px, py] = frame.pivot.unwrap();
+        assert_close(px, 0.5);
+        assert_close(py, 21.0 / 20.0);
+
+        let coin = sheet.region("coin.png").unwrap();
+        assert!(!coin.rotated);
+        assert!(!coin.is_trimmed());
+        let sprite = coin.sprite(TextureId(1), 1.0, 2.0, 1.0);
+        assert_eq!(sprite.pos, [1.0, 2.0]);
+        assert_eq!(sprite.scale, [8.0, 8.0]);
+        assert_eq!(sprite.angle, 0.0);
+        assert_eq!(coin.frame(TextureId(1), 0.1).pivot, Some([0.5, 0.5]));
+    }
+
+    #[test]
+    fn texture_packer_xml() {
+        let sheet = SpriteSheet::from_texture_packer_xml(TEXTURE_PACKER_XML).unwrap();
+        assert_eq!(sheet.image.as_deref(), Some("atlas.png"));
+        assert_eq!(sheet.texture_size, [64, 32]);
+        assert_eq!(sheet.regions.len(), 2);
+
+        let coin = &sheet.regions[0];
+        assert_eq!(coin.name, "coin.png");
+        assert_eq!(coin.rect, [0, 0, 16, 16]);
+        assert_eq!(coin.uv_rect, [0.0, 0.0, 0.25, 0.5]);
+        assert!(!coin.rotated);
+        assert!(!coin.is_trimmed());
+        assert_eq!(coin.pivot, Some([0.5, 0.25]));
+
+        let gem = &sheet.regions[1];
+        assert_eq!(gem.name, "gem & ring.png");
+        assert!(gem.rotated);
+        assert_eq!(gem.rect, [16, 0, 12, 10]);
+        assert_eq!(gem.source_size, [16, 16]);
+        assert_eq!(gem.trim_rect, [3, 2, 10, 12]);
+        assert!(gem.is_trimmed());
+        assert_eq!(gem.pivot, None);
+    }
+
+    #[test]
+    fn malformed_json() {
+        assert!(matches!(
+            SpriteSheet::from_json("{ \"frames\": {},\n\"meta\": }"),
+            Err(SheetError::Syntax(2))
+        ));
+        assert!(matches!(
+            SpriteSheet::from_json("{ \"frames\": {} }"),
+            Err(SheetError::MissingField("meta"))
+        ));
+        assert!(matches!(
+            SpriteSheet::from_json(r#"{ "frames": 3, "meta": { "size": { "w": 1, "h": 1 } } }"#),
+            Err(SheetError::MissingField("frames"))
+        ));
+        assert!(matches!(
+            SpriteSheet::from_json(
+                r#"{ "frames": [{ "frame": { "x": 0, "y": 0, "w": 1, "h": 1 } }],
+                "meta": { "size": { "w": 1, "h": 1 } } }"#
+            ),
+            Err(SheetError::MissingField("filename"))
+        ));
+        assert!(matches!(
+            SpriteSheet::from_json(
+                r#"{ "frames": { "a": { "frame": { "x": 0, "y": 0, "w": "1", "h": 1 } } },
+                "meta": { "size": { "w": 1, "h": 1 } } }"#
+            ),
+            Err(SheetError::MissingField("w"))
+        ));
+    }
+
+    #[test]
+    fn malformed_xml() {
+        assert!(matches!(
+            SpriteSheet::from_texture_packer_xml("<TextureAtlas width=\"1\"\nheight=1>"),
+            Err(SheetError::Syntax(2))
+        ));
+        assert!(matches!(
+            SpriteSheet::from_texture_packer_xml("<atlas/>"),
+            Err(SheetError::MissingField("TextureAtlas"))
+        ));
+        assert!(matches!(
+            SpriteSheet::from_texture_packer_xml(
+                r#"<TextureAtlas width="1" height="1"><sprite n="a" y="0" w="1" h="1"/>"#
+            ),
+            Err(SheetError::MissingField("x"))
+        ));
+        assert!(matches!(
+            SpriteSheet::from_texture_packer_xml(
+                r#"<TextureAtlas width="1" height="1">
+                <sprite n="a" x="0" y="0" w="1" h="1" oW="a" oH="1"/>"#
+            ),
+            Err(SheetError::MissingField("oW"))
+        ));
+    }
+
+    #[test]
+    fn truncated_sheets_do_not_panic() {
+        for text in [ASEPRITE_HASH, ASEPRITE_ARRAY, TEXTURE_PACKER_JSON] {
+            for len in (0..text.len()).filter(|&x| text.is_char_boundary(x)) {
+                let _ = SpriteSheet::from_json(&text[..len]);
+            }
+        }
+        for len in 0..TEXTURE_PACKER_XML.len() {
+            let _ = SpriteSheet::from_texture_packer_xml(&TEXTURE_PACKER_XML[..len]);
+        }
+        let _ = SpriteSheet::from_json("{ \"frames\": \"\u{e9}");
+        let _ = SpriteSheet::from_texture_packer_xml("<TextureAtlas n=\"&#xffffffff;\"/>");
+    }
+}
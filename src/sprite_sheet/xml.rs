@@ -0,0 +1,120 @@
+//! A minimal XML reader, enough to read the atlases exported by TexturePacker. Only the tags and
+//! their attributes are read; text, comments and declarations are skipped.
+
+/// A opening or empty element tag.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Element {
+    pub name: String,
+    pub attributes: Vec<(String, String)>,
+}
+impl Element {
+    pub fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|x| x.0 == name)
+            .map(|x| x.1.as_str())
+    }
+}
+
+/// Read the opening and empty element tags of a XML document, in order. On error, returns the
+/// byte offset where the error was found.
+pub fn elements(text: &str) -> Result<Vec<Element>, usize> {
+    let mut elements = Vec::new();
+    let mut pos = 0;
+    while let Some(start) = text[pos..].find('<').map(|x| x + pos) {
+        let rest = &text[start..];
+        // skip the declaration, processing instructions, comments, CDATA and the doctype.
+        let skip_until = if rest.starts_with("<?") {
+            Some("?>")
+        } else if rest.starts_with("<!--") {
+            Some("-->")
+        } else if rest.starts_with("<![CDATA[") {
+            Some("]]>")
+        } else if rest.starts_with("<!") || rest.starts_with("</") {
+            Some(">")
+        } else {
+            None
+        };
+        if let Some(end) = skip_until {
+            pos = start + rest.find(end).ok_or(start)? + end.len();
+            continue;
+        }
+        let (element, len) = element(rest).map_err(|x| start + x)?;
+        elements.push(element);
+        pos = start + len;
+    }
+    Ok(elements)
+}
+
+/// Parse a element tag at the start of `text`, returning it and the length of the tag.
+fn element(text: &str) -> Result<(Element, usize), usize> {
+    let is_name = |c: char| !c.is_whitespace() && !matches!(c, '/' | '>' | '=' | '"' | '\'');
+    let name_len = text[1..].find(|c| !is_name(c)).ok_or(text.len())? + 1;
+    if name_len == 1 {
+        return Err(1);
+    }
+    let mut element = Element {
+        name: text[1..name_len].to_string(),
+        attributes: Vec::new(),
+    };
+    let mut pos = name_len;
+    loop {
+        pos += text[pos..].len() - text[pos..].trim_start().len();
+        let rest = &text[pos..];
+        if rest.starts_with("/>") {
+            return Ok((element, pos + 2));
+        } else if rest.starts_with('>') {
+            return Ok((element, pos + 1));
+        }
+
+        let len = rest.find(|c| !is_name(c)).ok_or(text.len())?;
+        if len == 0 {
+            return Err(pos);
+        }
+        let name = rest[..len].to_string();
+        pos += len;
+        pos += text[pos..].len() - text[pos..].trim_start().len();
+        if !text[pos..].starts_with('=') {
+            return Err(pos);
+        }
+        pos += 1;
+        pos += text[pos..].len() - text[pos..].trim_start().len();
+        let quote = match text[pos..].chars().next() {
+            Some(c @ ('"' | '\'')) => c,
+            _ => return Err(pos),
+        };
+        let len = text[pos + 1..].find(quote).ok_or(pos)?;
+        let value = unescape(&text[pos + 1..pos + 1 + len]).ok_or(pos)?;
+        element.attributes.push((name, value));
+        pos += len + 2;
+    }
+}
+
+/// Replace the character and entity references of a attribute value.
+fn unescape(value: &str) -> Option<String> {
+    let mut result = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(start) = rest.find('&') {
+        result.push_str(&rest[..start]);
+        let end = rest[start..].find(';')? + start;
+        let entity = &rest[start + 1..end];
+        let c = match entity {
+            "amp" => '&',
+            "lt" => '<',
+            "gt" => '>',
+            "quot" => '"',
+            "apos" => '\'',
+            _ => {
+                let code = match entity.strip_prefix("#x") {
+                    Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                    None => entity.strip_prefix('#')?.parse().ok()?,
+                };
+                char::from_u32(code)?
+            }
+        };
+        result.push(c);
+        rest = &rest[end + 1..];
+    }
+    result.push_str(rest);
+    Some(result)
+}
@@ -0,0 +1,141 @@
+#[cfg(any(feature = "opengl", feature = "webgl"))]
+use crate::{shape::Quad, MaterialId, TextureId};
+
+/// A translation, rotation and scale applied to a static batch when drawing it, with
+/// [Renderer::draw_static_batch_transformed](crate::Renderer::draw_static_batch_transformed).
+///
+/// The sprites are scaled and rotated around the origin of the world space, and then translated.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Transform {
+    pub position: [f32; 2],
+    /// The rotation, in counterclockwise radians.
+    pub angle: f32,
+    pub scale: [f32; 2],
+}
+impl Default for Transform {
+    fn default() -> Self {
+        Self {
+            position: [0.0, 0.0],
+            angle: 0.0,
+            scale: [1.0, 1.0],
+        }
+    }
+}
+impl Transform {
+    /// Create a new Transform that translates by (x, y), with no rotation or scale.
+    pub fn new(x: f32, y: f32) -> Self {
+        Self {
+            position: [x, y],
+            ..Default::default()
+        }
+    }
+
+    /// set the rotation, in a functional way (get owership of the value, and return it modified).
+    #[inline]
+    pub fn with_angle(mut self, angle: f32) -> Self {
+        self.angle = angle;
+        self
+    }
+
+    /// set the scale, in a functional way (get owership of the value, and return it modified).
+    #[inline]
+    pub fn with_scale(mut self, x: f32, y: f32) -> Self {
+        self.scale = [x, y];
+        self
+    }
+
+    /// Multiply a view matrix by this transform, so it is applied before the view.
+    #[cfg(any(feature = "opengl", feature = "webgl"))]
+    pub(crate) fn apply(&self, view: &[f32; 9]) -> [f32; 9] {
+        let (sin, cos) = self.angle.sin_cos();
+        let [sx, sy] = self.scale;
+        let [x, y] = self.position;
+        let transform = [cos * sx, -sin * sy, x, sin * sx, cos * sy, y, 0.0, 0.0, 1.0];
+        let mut result = [0.0; 9];
+        for i in 0..3 {
+            for j in 0..3 {
+                result[i * 3 + j] = (0..3).map(|k| view[i * 3 + k] * transform[k * 3 + j]).sum();
+            }
+        }
+        result
+    }
+}
+
+/// The maximum number of sprites in a group, so they can be drawn with u16 indices.
+#[cfg(any(feature = "opengl", feature = "webgl"))]
+pub(crate) const MAX_GROUP_SPRITES: usize = (u16::MAX as usize + 1) / 4;
+
+/// A range of the sprites of a static batch that is drawn in a single draw call.
+#[cfg(any(feature = "opengl", feature = "webgl"))]
+#[derive(Clone, Debug)]
+pub(crate) struct BatchGroup {
+    pub material: MaterialId,
+    /// The texture bound to each texture unit.
+    pub textures: Vec<TextureId>,
+    /// The index of the first sprite of the group.
+    pub start: usize,
+    /// The number of sprites in the group.
+    pub len: usize,
+}
+
+/// The texture index (`unit | swizzle << 8`) of each quad, if the quads can replace the sprites of
+/// a static batch from `start` without changing its groups, or None if the batch must be grouped
+/// again.
+#[cfg(any(feature = "opengl", feature = "webgl"))]
+pub(crate) fn indices_in_place(
+    groups: &[BatchGroup],
+    start: usize,
+    quads: &[Quad],
+    swizzle: impl Fn(TextureId) -> u16,
+) -> Option<Vec<u16>> {
+    let mut indices = Vec::with_capacity(quads.len());
+    for (i, quad) in quads.iter().enumerate() {
+        let index = start + i;
+        let group = &groups[groups.partition_point(|x| x.start + x.len <= index)];
+        if group.material != quad.material {
+            return None;
+        }
+        let unit = group.textures.iter().position(|x| *x == quad.texture)?;
+        indices.push(unit as u16 | swizzle(quad.texture) << 8);
+    }
+    Some(indices)
+}
+
+/// Divide the quads of a static batch in groups, by material and by the number of texture units
+/// of the material, calling `write` with each quad and its texture index (`unit | swizzle << 8`).
+#[cfg(any(feature = "opengl", feature = "webgl"))]
+pub(crate) fn group_quads(
+    quads: impl Iterator<Item = Quad>,
+    texture_units: impl Fn(MaterialId) -> usize,
+    swizzle: impl Fn(TextureId) -> u16,
+    mut write: impl FnMut(&Quad, u16),
+) -> Vec<BatchGroup> {
+    let mut groups: Vec<BatchGroup> = Vec::new();
+    for (i, quad) in quads.enumerate() {
+        let fits = |group: &BatchGroup| {
+            group.material == quad.material
+                && group.len < MAX_GROUP_SPRITES
+                && (group.textures.contains(&quad.texture)
+                    || group.textures.len() < texture_units(quad.material))
+        };
+        if !groups.last().is_some_and(fits) {
+            groups.push(BatchGroup {
+                material: quad.material,
+                textures: Vec::new(),
+                start: i,
+                len: 0,
+            });
+        }
+        let group = groups.last_mut().unwrap();
+        let unit = match group.textures.iter().position(|x| *x == quad.texture) {
+            Some(unit) => unit,
+            None => {
+                group.textures.push(quad.texture);
+                group.textures.len() - 1
+            }
+        };
+        write(&quad, unit as u16 | swizzle(quad.texture) << 8);
+        group.len += 1;
+    }
+    groups
+}
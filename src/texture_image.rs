@@ -0,0 +1,144 @@
+//! Loading of textures from PNG, JPEG and QOI images, using the `image` crate.
+
+use std::path::Path;
+
+use image::DynamicImage;
+
+use crate::{Texture, TextureError, TextureFormat};
+
+/// Error generated when loading a texture from a image.
+#[derive(Debug)]
+pub enum ImageError {
+    /// The image file could not be read.
+    Io(std::io::Error),
+    /// The image could not be decoded.
+    Decode(image::ImageError),
+    /// The texture could not be created from the decoded image.
+    Texture(TextureError),
+}
+impl std::fmt::Display for ImageError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ImageError::Io(err) => write!(f, "failed to read image: {}", err),
+            ImageError::Decode(err) => write!(f, "failed to decode image: {}", err),
+            ImageError::Texture(err) => write!(f, "failed to create texture: {}", err),
+        }
+    }
+}
+impl std::error::Error for ImageError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ImageError::Io(err) => Some(err),
+            ImageError::Decode(err) => Some(err),
+            ImageError::Texture(err) => Some(err),
+        }
+    }
+}
+impl From<std::io::Error> for ImageError {
+    fn from(value: std::io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+impl From<image::ImageError> for ImageError {
+    fn from(value: image::ImageError) -> Self {
+        Self::Decode(value)
+    }
+}
+impl From<TextureError> for ImageError {
+    fn from(value: TextureError) -> Self {
+        Self::Texture(value)
+    }
+}
+
+/// How a decoded image is converted to the data of a texture.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ImageOptions {
+    /// Multiply the color channels by the alpha channel.
+    pub premultiply: bool,
+    /// Flip the image vertically, so the bottom row of the image is the first row of the texture.
+    pub flip_vertically: bool,
+}
+
+/// The pixels of a decoded image, in a [TextureFormat] supported by all backends.
+///
+/// Images with alpha are converted to [TextureFormat::Rgba8888], or to
+/// [TextureFormat::LuminanceAlpha] if they are grayscale. Images without alpha are converted to
+/// [TextureFormat::Rgb888], or to [TextureFormat::LuminanceAlpha] with opaque alpha.
+#[derive(Clone, Debug)]
+pub struct TextureImage {
+    /// The width of the image, in pixels.
+    pub width: u32,
+    /// The height of the image, in pixels.
+    pub height: u32,
+    /// The format of `data`.
+    pub format: TextureFormat,
+    /// The pixels of the image, in the given `format`.
+    pub data: Vec<u8>,
+}
+impl TextureImage {
+    /// Convert a image decoded by the `image` crate.
+    pub fn from_dynamic_image(image: DynamicImage, options: ImageOptions) -> Self {
+        let (width, height) = (image.width(), image.height());
+        let (format, mut data) = match image {
+            DynamicImage::ImageRgba8(x) => (TextureFormat::Rgba8888, x.into_raw()),
+            DynamicImage::ImageRgb8(x) => (TextureFormat::Rgb888, x.into_raw()),
+            DynamicImage::ImageLumaA8(x) => (TextureFormat::LuminanceAlpha, x.into_raw()),
+            DynamicImage::ImageLuma8(_) => (
+                TextureFormat::LuminanceAlpha,
+                image.into_luma_alpha8().into_raw(),
+            ),
+            x if x.color().has_alpha() => (TextureFormat::Rgba8888, x.into_rgba8().into_raw()),
+            x => (TextureFormat::Rgb888, x.into_rgb8().into_raw()),
+        };
+
+        let channels = match format {
+            TextureFormat::Rgba8888 => 4,
+            TextureFormat::LuminanceAlpha => 2,
+            _ => 0,
+        };
+        if options.premultiply && channels > 0 {
+            for pixel in data.chunks_exact_mut(channels) {
+                let (alpha, color) = pixel.split_last_mut().unwrap();
+                for c in color {
+                    *c = ((*c as u32 * *alpha as u32 + 127) / 255) as u8;
+                }
+            }
+        }
+
+        if options.flip_vertically && height > 0 {
+            let row_len = data.len() / height as usize;
+            let (top, bottom) = data.split_at_mut(row_len * (height as usize / 2));
+            let bottom_rows = bottom.rchunks_exact_mut(row_len);
+            for (a, b) in top.chunks_exact_mut(row_len).zip(bottom_rows) {
+                a.swap_with_slice(b);
+            }
+        }
+
+        Self {
+            width,
+            height,
+            format,
+            data,
+        }
+    }
+
+    /// Decode a PNG, JPEG or QOI image, detecting its format by its content.
+    pub fn from_bytes(bytes: &[u8], options: ImageOptions) -> Result<Self, ImageError> {
+        let image = image::load_from_memory(bytes)?;
+        Ok(Self::from_dynamic_image(image, options))
+    }
+
+    /// Read and decode a PNG, JPEG or QOI image file.
+    pub fn from_path(path: impl AsRef<Path>, options: ImageOptions) -> Result<Self, ImageError> {
+        Self::from_bytes(&std::fs::read(path)?, options)
+    }
+}
+
+impl<'a> Texture<'a> {
+    /// Creates a new Texture with the dimensions, format and data of the given image.
+    pub fn from_image(image: &'a TextureImage) -> Self {
+        Texture::new(image.width, image.height)
+            .format(image.format)
+            .data(&image.data)
+    }
+}
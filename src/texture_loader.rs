@@ -0,0 +1,353 @@
+//! Loading of textures in the background, decoding the images in worker threads and uploading them
+//! in small chunks between frames.
+
+use std::{
+    collections::HashMap,
+    collections::VecDeque,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, Arc, Mutex,
+    },
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    ImageError, ImageOptions, Sampler, SpriteRender, Texture, TextureError, TextureId, TextureImage,
+};
+
+/// The maximum number of bytes uploaded by a single `update_texture` call.
+const UPLOAD_CHUNK_BYTES: usize = 256 * 1024;
+
+/// The state of a texture loaded by a [TextureLoader].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LoadState {
+    /// The image is being decoded or uploaded. The texture has undefined content.
+    Loading,
+    /// The texture was fully uploaded.
+    Ready,
+    /// The image could not be read, decoded or uploaded. The error was logged.
+    Failed,
+}
+
+enum Source {
+    Bytes(Vec<u8>),
+    Path(PathBuf),
+}
+
+/// A image to be decoded.
+struct Job {
+    id: TextureId,
+    source: Source,
+    options: ImageOptions,
+    sampler: Sampler,
+}
+impl Job {
+    fn decode(self) -> Decoded {
+        let image = match self.source {
+            Source::Bytes(bytes) => TextureImage::from_bytes(&bytes, self.options),
+            Source::Path(path) => TextureImage::from_path(path, self.options),
+        };
+        Decoded {
+            id: self.id,
+            sampler: self.sampler,
+            image,
+        }
+    }
+}
+
+/// A image decoded by a worker.
+struct Decoded {
+    id: TextureId,
+    sampler: Sampler,
+    image: Result<TextureImage, ImageError>,
+}
+
+/// A decoded image being uploaded to its texture.
+struct Upload {
+    id: TextureId,
+    image: TextureImage,
+    /// The first row that was not uploaded yet.
+    next_row: u32,
+}
+
+/// Load textures from PNG, JPEG and QOI images without blocking the render thread.
+///
+/// The images are decoded in worker threads, and uploaded to the [SpriteRender] in chunks of rows
+/// by [TextureLoader::poll], which must be called every frame. The [TextureId] is returned
+/// immediately, and [TextureLoader::texture_or] can be used to draw a placeholder texture until it
+/// is ready.
+///
+/// Not supported on wasm, which has no threads to decode the images in.
+///
+/// The loaded textures have no mipmaps.
+pub struct TextureLoader {
+    states: HashMap<TextureId, LoadState>,
+    uploads: VecDeque<Upload>,
+    jobs: Option<mpsc::Sender<Job>>,
+    decoded: mpsc::Receiver<Decoded>,
+    workers: Vec<JoinHandle<()>>,
+    /// Set when the loader is dropped, so the workers skip the jobs still queued.
+    cancelled: Arc<AtomicBool>,
+}
+impl TextureLoader {
+    /// Create a new TextureLoader, that decodes images in the given number of worker threads.
+    pub fn new(workers: usize) -> Self {
+        let (jobs, job_receiver) = mpsc::channel::<Job>();
+        let (decoded_sender, decoded) = mpsc::channel();
+        let job_receiver = Arc::new(Mutex::new(job_receiver));
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let workers = (0..workers.max(1))
+            .map(|i| {
+                let job_receiver = job_receiver.clone();
+                let decoded_sender = decoded_sender.clone();
+                let cancelled = cancelled.clone();
+                std::thread::Builder::new()
+                    .name(format!("texture-loader-{}", i))
+                    .spawn(move || loop {
+                        let job = match job_receiver.lock() {
+                            Ok(receiver) => receiver.recv(),
+                            // another worker panicked while waiting for a job.
+                            Err(_) => break,
+                        };
+                        // the channel is closed when the loader is dropped.
+                        let Ok(job) = job else { break };
+                        if cancelled.load(Ordering::Relaxed) {
+                            break;
+                        }
+                        if decoded_sender.send(job.decode()).is_err() {
+                            break;
+                        }
+                    })
+                    .expect("failed to spawn texture loader thread")
+            })
+            .collect();
+        Self {
+            states: HashMap::new(),
+            uploads: VecDeque::new(),
+            jobs: Some(jobs),
+            decoded,
+            workers,
+            cancelled,
+        }
+    }
+
+    /// Start loading a texture from the bytes of a image file.
+    ///
+    /// A 1x1 transparent texture is created to reserve the returned id, and is replaced when the
+    /// image is decoded.
+    pub fn load_bytes(
+        &mut self,
+        sprite_render: &mut dyn SpriteRender,
+        bytes: Vec<u8>,
+        options: ImageOptions,
+        sampler: Sampler,
+    ) -> Result<TextureId, TextureError> {
+        self.load(sprite_render, Source::Bytes(bytes), options, sampler)
+    }
+
+    /// Start loading a texture from a image file. The file is read in the worker thread.
+    ///
+    /// A 1x1 transparent texture is created to reserve the returned id, and is replaced when the
+    /// image is decoded.
+    pub fn load_path(
+        &mut self,
+        sprite_render: &mut dyn SpriteRender,
+        path: impl Into<PathBuf>,
+        options: ImageOptions,
+        sampler: Sampler,
+    ) -> Result<TextureId, TextureError> {
+        self.load(sprite_render, Source::Path(path.into()), options, sampler)
+    }
+
+    fn load(
+        &mut self,
+        sprite_render: &mut dyn SpriteRender,
+        source: Source,
+        options: ImageOptions,
+        sampler: Sampler,
+    ) -> Result<TextureId, TextureError> {
+        let id = Texture::new(1, 1)
+            .sampler(sampler)
+            .data(&[0; 4])
+            .create(sprite_render)?;
+        self.states.insert(id, LoadState::Loading);
+        let job = Job {
+            id,
+            source,
+            options,
+            sampler,
+        };
+        if let Some(jobs) = &self.jobs {
+            jobs.send(job).unwrap();
+        }
+        Ok(id)
+    }
+
+    /// The state of a texture loaded by this loader, or None if it was not loaded by it.
+    ///
+    /// Once a texture is [Ready](LoadState::Ready) or [Failed](LoadState::Failed), its state is
+    /// returned only once, and after that the loader forgets the texture.
+    pub fn state(&mut self, texture: TextureId) -> Option<LoadState> {
+        let state = self.states.get(&texture).copied()?;
+        if state != LoadState::Loading {
+            self.states.remove(&texture);
+        }
+        Some(state)
+    }
+
+    /// Return `texture` if it is ready, or else `placeholder`. Textures not loaded by this loader
+    /// are considered ready.
+    ///
+    /// A ready texture is forgotten by the loader, as by [TextureLoader::state]. A failed texture
+    /// is only forgotten when its state is observed with [TextureLoader::state].
+    pub fn texture_or(&mut self, texture: TextureId, placeholder: TextureId) -> TextureId {
+        match self.states.get(&texture) {
+            None => texture,
+            Some(LoadState::Ready) => {
+                self.states.remove(&texture);
+                texture
+            }
+            Some(_) => placeholder,
+        }
+    }
+
+    /// If there are textures still loading.
+    pub fn is_loading(&self) -> bool {
+        self.states.values().any(|x| *x == LoadState::Loading)
+    }
+
+    /// Create the textures of the decoded images, and upload them in chunks, until `budget` is
+    /// elapsed. At least one chunk is uploaded in each call, if there is any.
+    pub fn poll(&mut self, sprite_render: &mut dyn SpriteRender, budget: Duration) {
+        let start = Instant::now();
+
+        while let Ok(decoded) = self.decoded.try_recv() {
+            self.start_upload(sprite_render, decoded);
+        }
+
+        while let Some(upload) = self.uploads.front_mut() {
+            let image = &upload.image;
+            // the image is already decoded, so its rows fit in memory.
+            let row_len = image.data.len() / image.height.max(1) as usize;
+            let rows = (UPLOAD_CHUNK_BYTES / row_len.max(1)).max(1) as u32;
+            let rows = rows.min(image.height - upload.next_row);
+            let start_byte = upload.next_row as usize * row_len;
+            let data = &image.data[start_byte..start_byte + rows as usize * row_len];
+            let sub_rect = [0, upload.next_row, image.width, rows];
+            if let Err(err) = sprite_render.update_texture(upload.id, Some(data), Some(sub_rect)) {
+                log::error!("failed to upload texture {}: {}", upload.id, err);
+                self.states.insert(upload.id, LoadState::Failed);
+                self.uploads.pop_front();
+                continue;
+            }
+            upload.next_row += rows;
+            if upload.next_row == image.height {
+                self.states.insert(upload.id, LoadState::Ready);
+                self.uploads.pop_front();
+            }
+            if start.elapsed() >= budget {
+                break;
+            }
+        }
+    }
+
+    /// Create the texture of a decoded image with its final size, to be uploaded by `poll`.
+    fn start_upload(&mut self, sprite_render: &mut dyn SpriteRender, decoded: Decoded) {
+        let Decoded { id, sampler, image } = decoded;
+        let image = match image {
+            Ok(x) => x,
+            Err(err) => {
+                log::error!("failed to load texture {}: {}", id, err);
+                self.states.insert(id, LoadState::Failed);
+                return;
+            }
+        };
+        let texture = Texture::new(image.width, image.height)
+            .id(id)
+            .format(image.format)
+            .sampler(sampler);
+        if let Err(err) = sprite_render.new_texture(texture) {
+            log::error!("failed to create texture {}: {}", id, err);
+            self.states.insert(id, LoadState::Failed);
+            return;
+        }
+        if image.height == 0 {
+            self.states.insert(id, LoadState::Ready);
+            return;
+        }
+        self.uploads.push_back(Upload {
+            id,
+            image,
+            next_row: 0,
+        });
+    }
+}
+impl Drop for TextureLoader {
+    fn drop(&mut self) {
+        // the closed channel still yields the queued jobs, so the workers must be told to skip them
+        // to stop after their current job.
+        self.cancelled.store(true, Ordering::Relaxed);
+        self.jobs = None;
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::NoopSpriteRender;
+
+    fn load_and_wait(bytes: Vec<u8>) -> (TextureLoader, TextureId) {
+        let mut render = NoopSpriteRender;
+        let mut loader = TextureLoader::new(1);
+        let id = loader
+            .load_bytes(
+                &mut render,
+                bytes,
+                ImageOptions::default(),
+                Sampler::default(),
+            )
+            .unwrap();
+        while loader.is_loading() {
+            loader.poll(&mut render, Duration::from_millis(1));
+            std::thread::yield_now();
+        }
+        (loader, id)
+    }
+
+    #[test]
+    fn finished_state_is_observed_once() {
+        let (mut loader, id) = load_and_wait(b"not a image".to_vec());
+        assert_eq!(loader.state(id), Some(LoadState::Failed));
+        assert_eq!(loader.state(id), None);
+    }
+
+    #[test]
+    fn ready_texture_is_forgotten() {
+        let mut png = Vec::new();
+        image::DynamicImage::new_rgba8(2, 2)
+            .write_to(
+                &mut std::io::Cursor::new(&mut png),
+                image::ImageOutputFormat::Png,
+            )
+            .unwrap();
+        let (mut loader, id) = load_and_wait(png);
+        let placeholder = TextureId(u32::MAX);
+        assert_eq!(loader.texture_or(id, placeholder), id);
+        assert_eq!(loader.state(id), None);
+    }
+
+    #[test]
+    fn failed_texture_keeps_placeholder_until_observed() {
+        let (mut loader, id) = load_and_wait(b"not a image".to_vec());
+        let placeholder = TextureId(u32::MAX);
+        assert_eq!(loader.texture_or(id, placeholder), placeholder);
+        assert_eq!(loader.texture_or(id, placeholder), placeholder);
+        assert_eq!(loader.state(id), Some(LoadState::Failed));
+        assert_eq!(loader.texture_or(id, placeholder), id);
+    }
+}
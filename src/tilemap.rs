@@ -0,0 +1,340 @@
+use crate::{
+    BatchError, BatchId, Camera, MaterialId, Renderer, SpriteInstance, SpriteRender, TextureId,
+};
+
+/// The default width and height of a chunk, in tiles.
+const DEFAULT_CHUNK_SIZE: u32 = 32;
+
+/// The texture of the tiles of a [Tilemap], and the region of the texture of each tile.
+#[derive(Clone, Debug)]
+pub struct Tileset {
+    pub texture: TextureId,
+    /// The region of the texture of each tile, in normalized texture coordinates, indexed by the
+    /// tile id.
+    pub uv_rects: Vec<[f32; 4]>,
+    /// The material used to draw the tiles.
+    pub material: MaterialId,
+}
+impl Tileset {
+    /// Create a new Tileset from a texture divided in a grid of `columns` x `rows` tiles of the
+    /// same size. The tiles are numbered from left to right, and from top to bottom.
+    pub fn grid(texture: TextureId, columns: u32, rows: u32) -> Self {
+        let (w, h) = (1.0 / columns as f32, 1.0 / rows as f32);
+        let uv_rects = (0..rows)
+            .flat_map(|y| (0..columns).map(move |x| [x as f32 * w, y as f32 * h, w, h]))
+            .collect();
+        Self::from_uv_rects(texture, uv_rects)
+    }
+
+    /// Create a new Tileset with the given region of the texture for each tile id.
+    pub fn from_uv_rects(texture: TextureId, uv_rects: Vec<[f32; 4]>) -> Self {
+        Self {
+            texture,
+            uv_rects,
+            material: MaterialId::default(),
+        }
+    }
+
+    /// set the material of the tiles, in a functional way (get owership of the value, and return
+    /// it modified).
+    pub fn with_material(mut self, material: MaterialId) -> Self {
+        self.material = material;
+        self
+    }
+
+    /// The number of tiles in the tileset.
+    pub fn len(&self) -> usize {
+        self.uv_rects.len()
+    }
+
+    /// If the tileset has no tiles.
+    pub fn is_empty(&self) -> bool {
+        self.uv_rects.is_empty()
+    }
+}
+
+/// A square region of a layer, uploaded as a single static batch.
+#[derive(Clone, Copy, Default)]
+struct Chunk {
+    batch: Option<BatchId>,
+    /// If the tiles of the chunk changed since they were last uploaded.
+    dirty: bool,
+}
+
+struct Layer {
+    /// The tile id of each cell, row by row, or None for empty cells.
+    tiles: Vec<Option<u32>>,
+    chunks: Vec<Chunk>,
+    visible: bool,
+}
+
+/// A grid of tiles with multiple layers, drawn from retained static batches.
+///
+/// The layers are divided in square chunks, and the tiles of each chunk are uploaded to the GPU
+/// once, by [Tilemap::update], using [SpriteRender::new_static_batch]. Changing a tile only
+/// uploads its chunk again. [Tilemap::draw] only draws the chunks that are visible by the camera.
+///
+/// The tile (0, 0) has its top left corner in the origin of the tilemap, and the tile (x, y) is
+/// `x` tiles to the right and `y` tiles below it. The layers are drawn in the order they were
+/// added.
+///
+/// Like the textures, the static batches are lost when the [SpriteRender] is suspended, so
+/// [Tilemap::invalidate] must be called after resuming it.
+pub struct Tilemap {
+    width: u32,
+    height: u32,
+    tile_size: [f32; 2],
+    origin: [f32; 2],
+    chunk_size: u32,
+    tileset: Tileset,
+    layers: Vec<Layer>,
+}
+impl Tilemap {
+    /// Create a new Tilemap with `width` x `height` tiles of the given size in world space, and no
+    /// layers. The origin is in (0, 0).
+    pub fn new(width: u32, height: u32, tile_size: [f32; 2], tileset: Tileset) -> Self {
+        Self {
+            width,
+            height,
+            tile_size,
+            origin: [0.0, 0.0],
+            chunk_size: DEFAULT_CHUNK_SIZE,
+            tileset,
+            layers: Vec::new(),
+        }
+    }
+
+    /// set the position of the top left corner of the tilemap, in a functional way (get owership of
+    /// the value, and return it modified).
+    pub fn with_origin(mut self, x: f32, y: f32) -> Self {
+        self.origin = [x, y];
+        self
+    }
+
+    /// set the width and height of the chunks, in tiles, in a functional way (get owership of the
+    /// value, and return it modified). The default is 32.
+    ///
+    /// Smaller chunks are culled more precisely and are faster to upload, but take more draw calls.
+    ///
+    /// Must be called before adding layers, whose chunks may already have static batches. Otherwise
+    /// the chunk size is not changed, and an error is logged.
+    pub fn with_chunk_size(mut self, chunk_size: u32) -> Self {
+        if !self.layers.is_empty() {
+            log::error!("the chunk size of a tilemap cannot be changed after adding layers");
+            return self;
+        }
+        self.chunk_size = chunk_size.max(1);
+        self
+    }
+
+    /// The `(width, height)` of the tilemap, in tiles.
+    pub fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    /// The size of each tile, in world space.
+    pub fn tile_size(&self) -> [f32; 2] {
+        self.tile_size
+    }
+
+    pub fn tileset(&self) -> &Tileset {
+        &self.tileset
+    }
+
+    /// The number of chunks in each axis.
+    fn chunk_count(&self) -> (usize, usize) {
+        (
+            self.width.div_ceil(self.chunk_size) as usize,
+            self.height.div_ceil(self.chunk_size) as usize,
+        )
+    }
+
+    /// Add a new layer, with all tiles empty, drawn over the previous layers. Returns the index of
+    /// the layer.
+    pub fn add_layer(&mut self) -> usize {
+        let (columns, rows) = self.chunk_count();
+        self.layers.push(Layer {
+            tiles: vec![None; self.width as usize * self.height as usize],
+            chunks: vec![Chunk::default(); columns * rows],
+            visible: true,
+        });
+        self.layers.len() - 1
+    }
+
+    /// The number of layers.
+    pub fn layer_count(&self) -> usize {
+        self.layers.len()
+    }
+
+    /// Show or hide a layer.
+    pub fn set_layer_visible(&mut self, layer: usize, visible: bool) {
+        match self.layers.get_mut(layer) {
+            Some(x) => x.visible = visible,
+            None => log::error!("tilemap layer {} not found", layer),
+        }
+    }
+
+    /// The tile id of a cell, or None if it is empty or out of bounds.
+    pub fn get_tile(&self, layer: usize, x: u32, y: u32) -> Option<u32> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        let layer = self.layers.get(layer)?;
+        layer.tiles[(y * self.width + x) as usize]
+    }
+
+    /// Set the tile id of a cell, or empty it if `tile` is None. The change is uploaded in the next
+    /// [Tilemap::update].
+    pub fn set_tile(&mut self, layer: usize, x: u32, y: u32, tile: Option<u32>) {
+        if x >= self.width || y >= self.height {
+            log::error!("tile ({}, {}) is out of the tilemap bounds", x, y);
+            return;
+        }
+        if tile.is_some_and(|x| x as usize >= self.tileset.len()) {
+            log::error!("tile id {} is not in the tileset", tile.unwrap());
+            return;
+        }
+        let (columns, _) = self.chunk_count();
+        let chunk = (y / self.chunk_size) as usize * columns + (x / self.chunk_size) as usize;
+        let Some(layer) = self.layers.get_mut(layer) else {
+            log::error!("tilemap layer {} not found", layer);
+            return;
+        };
+        let cell = &mut layer.tiles[(y * self.width + x) as usize];
+        if *cell != tile {
+            *cell = tile;
+            layer.chunks[chunk].dirty = true;
+        }
+    }
+
+    /// The cell that contains a position in world space, if any.
+    pub fn tile_at(&self, x: f32, y: f32) -> Option<(u32, u32)> {
+        let x = ((x - self.origin[0]) / self.tile_size[0]).floor();
+        let y = ((y - self.origin[1]) / self.tile_size[1]).floor();
+        if x < 0.0 || y < 0.0 || x >= self.width as f32 || y >= self.height as f32 {
+            return None;
+        }
+        Some((x as u32, y as u32))
+    }
+
+    /// Upload the chunks whose tiles changed since the last update.
+    pub fn update(&mut self, sprite_render: &mut dyn SpriteRender) -> Result<(), BatchError> {
+        let (columns, _) = self.chunk_count();
+        let mut sprites = Vec::new();
+        for l in 0..self.layers.len() {
+            for c in 0..self.layers[l].chunks.len() {
+                if !self.layers[l].chunks[c].dirty {
+                    continue;
+                }
+                sprites.clear();
+                self.chunk_sprites(l, c % columns, c / columns, &mut sprites);
+
+                let chunk = &mut self.layers[l].chunks[c];
+                chunk.batch = match chunk.batch {
+                    Some(batch) if sprites.is_empty() => {
+                        sprite_render.remove_static_batch(batch);
+                        None
+                    }
+                    Some(batch) => {
+                        sprite_render.update_static_batch(batch, &sprites)?;
+                        Some(batch)
+                    }
+                    None if sprites.is_empty() => None,
+                    None => Some(sprite_render.new_static_batch(&sprites)?),
+                };
+                chunk.dirty = false;
+            }
+        }
+        Ok(())
+    }
+
+    /// Push the sprites of the tiles of a chunk to `sprites`.
+    fn chunk_sprites(&self, layer: usize, cx: usize, cy: usize, sprites: &mut Vec<SpriteInstance>) {
+        let [w, h] = self.tile_size;
+        let size = self.chunk_size;
+        let (x0, y0) = (cx as u32 * size, cy as u32 * size);
+        for y in y0..(y0 + size).min(self.height) {
+            for x in x0..(x0 + size).min(self.width) {
+                let Some(tile) = self.layers[layer].tiles[(y * self.width + x) as usize] else {
+                    continue;
+                };
+                sprites.push(
+                    SpriteInstance::new(
+                        self.origin[0] + (x as f32 + 0.5) * w,
+                        self.origin[1] + (y as f32 + 0.5) * h,
+                        w,
+                        h,
+                        self.tileset.texture,
+                        self.tileset.uv_rects[tile as usize],
+                    )
+                    .with_material(self.tileset.material),
+                );
+            }
+        }
+    }
+
+    /// Draw the visible layers, skipping the chunks that are outside of the view of the camera.
+    ///
+    /// Chunks that were not uploaded by [Tilemap::update] are not drawn.
+    pub fn draw(&self, renderer: &mut dyn Renderer, camera: &mut Camera) {
+        let (screen_width, screen_height) = camera.screen_size();
+        if screen_width == 0 || screen_height == 0 {
+            return;
+        }
+
+        // the bounding box of the view in world space, which may be rotated.
+        let (sw, sh) = (screen_width as f32, screen_height as f32);
+        let corners = [(0.0, 0.0), (sw, 0.0), (0.0, sh), (sw, sh)]
+            .map(|(x, y)| camera.position_to_word_space(x, y));
+        let min_x = corners.iter().map(|x| x.0).fold(f32::INFINITY, f32::min);
+        let max_x = corners
+            .iter()
+            .map(|x| x.0)
+            .fold(f32::NEG_INFINITY, f32::max);
+        let min_y = corners.iter().map(|x| x.1).fold(f32::INFINITY, f32::min);
+        let max_y = corners
+            .iter()
+            .map(|x| x.1)
+            .fold(f32::NEG_INFINITY, f32::max);
+
+        let (columns, _) = self.chunk_count();
+        let chunk_width = self.chunk_size as f32 * self.tile_size[0];
+        let chunk_height = self.chunk_size as f32 * self.tile_size[1];
+        for layer in self.layers.iter().filter(|x| x.visible) {
+            for (c, chunk) in layer.chunks.iter().enumerate() {
+                let Some(batch) = chunk.batch else {
+                    continue;
+                };
+                let x = self.origin[0] + (c % columns) as f32 * chunk_width;
+                let y = self.origin[1] + (c / columns) as f32 * chunk_height;
+                if x > max_x || x + chunk_width < min_x || y > max_y || y + chunk_height < min_y {
+                    continue;
+                }
+                renderer.draw_static_batch(camera, batch);
+            }
+        }
+    }
+
+    /// Forget the static batches of the chunks, and mark all of them to be uploaded again in the
+    /// next [Tilemap::update]. Must be called after the [SpriteRender] is resumed, when its static
+    /// batches were lost.
+    pub fn invalidate(&mut self) {
+        for chunk in self.layers.iter_mut().flat_map(|x| &mut x.chunks) {
+            *chunk = Chunk {
+                batch: None,
+                dirty: true,
+            };
+        }
+    }
+
+    /// Remove the static batches of the chunks from the [SpriteRender]. They are uploaded again in
+    /// the next [Tilemap::update].
+    pub fn destroy(&mut self, sprite_render: &mut dyn SpriteRender) {
+        for chunk in self.layers.iter_mut().flat_map(|x| &mut x.chunks) {
+            if let Some(batch) = chunk.batch.take() {
+                sprite_render.remove_static_batch(batch);
+            }
+            chunk.dirty = true;
+        }
+    }
+}